@@ -0,0 +1,54 @@
+/**
+ * Never amplify more than this, otherwise silence turns into loud noise
+ **/
+const MAX_GAIN: f32 = 20.0;
+const MIN_GAIN: f32 = 0.1;
+
+/**
+ * Below this RMS level the audio is considered silence and the gain is left alone,
+ * also during speech, e.g. in the pauses between words
+ **/
+const NOISE_GATE: f32 = 0.001;
+
+/**
+ * How fast the gain follows the level, quick to turn down to prevent clipping
+ * and slow to turn up so speech pauses don't get boosted
+ **/
+const ATTACK: f32 = 0.5;
+const RELEASE: f32 = 0.02;
+
+/**
+ * Automatic gain control, keeps the level of the speech around the target level
+ * so quiet speakers are understood by Whisper and loud ones don't clip
+ **/
+pub struct Agc {
+    target: f32,
+    gain: f32,
+}
+
+impl Agc {
+    /**
+     * The target is the RMS level the audio should have, e.g. 0.1
+     **/
+    pub fn new(target: f32) -> Self {
+        Self { target, gain: 1.0 }
+    }
+
+    /**
+     * The gain only follows the level while someone speaks, so the room noise in between isn't turned up
+     **/
+    pub fn process(&mut self, samples: &[f32], speech: bool) -> Vec<f32> {
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+
+        if speech && rms > NOISE_GATE {
+            let wanted = (self.target / rms).clamp(MIN_GAIN, MAX_GAIN);
+            let rate = if wanted < self.gain { ATTACK } else { RELEASE };
+            self.gain += (wanted - self.gain) * rate;
+        }
+
+        samples
+            .iter()
+            .map(|s| (s * self.gain).clamp(-1.0, 1.0))
+            .collect()
+    }
+}
@@ -0,0 +1,81 @@
+use regex::Regex;
+
+/**
+ * Whether the host is ALSA, only there device names like hw:1,0 mean something
+ **/
+pub fn is_alsa(host: &cpal::Host) -> bool {
+    host.id().name() == "ALSA"
+}
+
+/**
+ * The index and id of every sound card, in the format of /proc/asound/cards:
+ * " 1 [Device         ]: USB-Audio - USB Audio Device"
+ **/
+fn parse_cards(cards: &str) -> Vec<(usize, String)> {
+    let regex = Regex::new(r"(?m)^\s*(\d+)\s+\[(\S+)\s*\]").unwrap();
+    regex
+        .captures_iter(cards)
+        .filter_map(|captures| Some((captures[1].parse().ok()?, captures[2].to_string())))
+        .collect()
+}
+
+/**
+ * Turn the short ALSA names known from arecord and aplay into the ones cpal lists,
+ * e.g. "hw:1,0" or "hw:Device" into "hw:CARD=Device,DEV=0". Other names stay as they are.
+ **/
+pub fn full_name(name: &str) -> String {
+    let regex = Regex::new(r"^(hw|plughw):([^,=]+)(?:,(\d+))?$").unwrap();
+    let Some(captures) = regex.captures(name) else {
+        return name.to_string();
+    };
+
+    let card = match captures[2].parse::<usize>() {
+        Ok(index) => {
+            let cards = std::fs::read_to_string("/proc/asound/cards").unwrap_or_default();
+            let card = parse_cards(&cards).into_iter().find(|(i, _)| *i == index);
+            match card {
+                Some((_, id)) => id,
+                None => return name.to_string(),
+            }
+        }
+        Err(_) => captures[2].to_string(),
+    };
+    let device = captures.get(3).map_or("0", |device| device.as_str());
+    format!("{}:CARD={},DEV={}", &captures[1], card, device)
+}
+
+/**
+ * The hw: device of the same sound card, this program has it to itself and the audio
+ * isn't converted or mixed with other programs on the way.
+ * None if the name doesn't belong to a sound card, like "default" or "pulse".
+ **/
+pub fn exclusive_name(name: &str) -> Option<String> {
+    let card = Regex::new(r"CARD=([^,]+)").unwrap();
+    let device = Regex::new(r"DEV=(\d+)").unwrap();
+
+    let card = card.captures(name)?[1].to_string();
+    let device =
+        (device.captures(name)).map_or("0".to_string(), |captures| captures[1].to_string());
+    Some(format!("hw:CARD={},DEV={}", card, device))
+}
+
+/**
+ * The name of the device to use with ALSA, with exclusive its hw: device
+ **/
+pub fn alsa_device(wanted: Option<&str>, exclusive: bool) -> Option<String> {
+    let name = wanted.map(full_name);
+    if !exclusive {
+        return name;
+    }
+
+    match name.as_deref().and_then(exclusive_name) {
+        Some(hw) => Some(hw),
+        None => {
+            println!(
+                "Exclusive audio needs the device of a sound card, e.g. \"hw:1,0\", using {}",
+                name.as_deref().unwrap_or("default")
+            );
+            name
+        }
+    }
+}
@@ -0,0 +1,15 @@
+/**
+ * Average interleaved multi-channel audio down to a single mono channel.
+ * VAD and Whisper both expect mono audio, feeding them interleaved stereo
+ * samples would corrupt the transcription.
+ **/
+pub fn downmix_to_mono(input: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return input.to_vec();
+    }
+
+    input
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
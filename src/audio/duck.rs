@@ -0,0 +1,176 @@
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tools::music::Player;
+
+/**
+ * How other audio is made quieter while the bot talks
+ **/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DuckKind {
+    Lower, // Lower the volume of all other applications with pactl (PulseAudio or PipeWire)
+    Pause, // Pause media players with playerctl (MPRIS)
+}
+
+/**
+ * Ducking related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct DuckConfig {
+    #[serde(default)]
+    pub duck: Option<DuckKind>, // Make other audio quieter while the bot talks: "lower" or "pause"
+    #[serde(default = "default_duck_volume")]
+    pub duck_volume: f32, // Volume of other applications while the bot talks, from 0 to 1
+}
+
+fn default_duck_volume() -> f32 {
+    0.3
+}
+
+/**
+ * Run a command and return what it printed, None if it isn't installed or failed
+ **/
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/**
+ * Lowers or pauses other audio while the bot talks and restores it afterwards.
+ * The player of the music tool is always made quieter, even if ducking is off.
+ **/
+pub struct Ducker {
+    kind: Option<DuckKind>,
+    volume: f32,
+    lowered: Vec<(String, u64)>, // Sink inputs with the volume they had before
+    paused: Vec<String>,         // Players that were playing before
+    music: Option<Player>,
+    music_volume: Option<f32>, // Volume of the music before it was lowered
+    music_paused: bool,
+}
+
+impl Ducker {
+    pub fn new(cfg: &DuckConfig, music: Option<Player>) -> Self {
+        Self {
+            kind: cfg.duck,
+            volume: cfg.duck_volume.clamp(0.0, 1.0),
+            lowered: Vec::new(),
+            paused: Vec::new(),
+            music,
+            music_volume: None,
+            music_paused: false,
+        }
+    }
+
+    pub fn duck(&mut self) {
+        match self.kind {
+            Some(DuckKind::Lower) => self.lower(),
+            Some(DuckKind::Pause) => self.pause(),
+            None => {}
+        }
+        self.duck_music();
+    }
+
+    pub fn restore(&mut self) {
+        for (index, volume) in self.lowered.drain(..) {
+            run(
+                "pactl",
+                &["set-sink-input-volume", &index, &volume.to_string()],
+            );
+        }
+        for player in self.paused.drain(..) {
+            run("playerctl", &["--player", &player, "play"]);
+        }
+        if let Some(music) = &self.music {
+            if let Some(volume) = self.music_volume.take() {
+                music.set_volume(volume);
+            }
+            if std::mem::take(&mut self.music_paused) {
+                let _ = music.resume();
+            }
+        }
+    }
+
+    /**
+     * Lower or pause the music of the music tool, unless it was already lowered with every other application
+     **/
+    fn duck_music(&mut self) {
+        let Some(music) = &self.music else {
+            return;
+        };
+        if !music.playing() {
+            return;
+        }
+
+        match self.kind {
+            Some(DuckKind::Lower) => {}
+            Some(DuckKind::Pause) => self.music_paused = music.pause().is_ok(),
+            None => {
+                if let Some(volume) = music.volume() {
+                    music.set_volume(volume * self.volume);
+                    self.music_volume = Some(volume);
+                }
+            }
+        }
+    }
+
+    /**
+     * Lower the volume of every application except this one
+     **/
+    fn lower(&mut self) {
+        let Some(json) = run("pactl", &["--format=json", "list", "sink-inputs"]) else {
+            eprintln!("Failed to list audio streams, is pactl installed?");
+            return;
+        };
+        let Ok(serde_json::Value::Array(inputs)) = serde_json::from_str(&json) else {
+            return;
+        };
+
+        let own_pid = std::process::id().to_string();
+        for input in inputs {
+            let pid = input["properties"]["application.process.id"].as_str();
+            if pid == Some(own_pid.as_str()) {
+                continue;
+            }
+
+            // The volume of the first channel, they are all set to the same value again
+            let index = input["index"].to_string();
+            let volume = input["volume"]
+                .as_object()
+                .and_then(|channels| channels.values().next())
+                .and_then(|channel| channel["value"].as_u64());
+
+            if let Some(volume) = volume {
+                let ducked = (volume as f32 * self.volume) as u64;
+                run(
+                    "pactl",
+                    &["set-sink-input-volume", &index, &ducked.to_string()],
+                );
+                self.lowered.push((index, volume));
+            }
+        }
+    }
+
+    /**
+     * Pause every media player that is currently playing
+     **/
+    fn pause(&mut self) {
+        let Some(players) = run("playerctl", &["--list-all"]) else {
+            eprintln!("Failed to list media players, is playerctl installed?");
+            return;
+        };
+
+        for player in players.lines() {
+            let status = run("playerctl", &["--player", player, "status"]);
+            if status.is_some_and(|status| status.trim() == "Playing") {
+                run("playerctl", &["--player", player, "pause"]);
+                self.paused.push(player.to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::f32::consts::PI;
+
+use rodio::Sink;
+use rodio::buffer::SamplesBuffer;
+use serde::{Deserialize, Serialize};
+
+use crate::tts::{Speech, decode_wav};
+
+const SAMPLE_RATE: u32 = 22_050;
+
+/**
+ * Earcon related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct EarconConfig {
+    #[serde(default)]
+    pub earcons: bool, // Play short sounds when speech is detected, when transcribing and on errors
+    #[serde(default)]
+    pub earcon_listening: Option<String>, // WAV file played when speech is detected, a rising beep by default
+    #[serde(default)]
+    pub earcon_thinking: Option<String>, // WAV file played when transcribing starts, a short beep by default
+    #[serde(default)]
+    pub earcon_error: Option<String>, // WAV file played when something went wrong, a falling beep by default
+}
+
+/**
+ * The states of the bot that are made audible
+ **/
+#[derive(Clone, Copy, Debug)]
+pub enum Earcon {
+    Listening,
+    Thinking,
+    Error,
+}
+
+/**
+ * A sequence of sine tones with the given frequencies in Hz, each one lasting duration_ms.
+ * Every tone fades in and out, so it doesn't click.
+ **/
+fn tones(frequencies: &[f32], duration_ms: u32) -> Speech {
+    let len = (SAMPLE_RATE * duration_ms / 1000) as usize;
+    let fade = len / 5;
+
+    let samples = frequencies
+        .iter()
+        .flat_map(|frequency| {
+            (0..len).map(move |i| {
+                let envelope = (i.min(len - i) as f32 / fade as f32).min(1.0);
+                let phase = 2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32;
+                0.3 * envelope * phase.sin()
+            })
+        })
+        .collect();
+
+    Speech {
+        samples,
+        sample_rate: SAMPLE_RATE,
+        channels: 1,
+    }
+}
+
+fn load(path: Option<&String>, default: Speech) -> Speech {
+    match path {
+        Some(path) => {
+            let bytes = std::fs::read(path).expect("Failed to read earcon");
+            decode_wav(bytes).expect("Failed to decode earcon, it has to be a WAV file")
+        }
+        None => default,
+    }
+}
+
+/**
+ * A chime for timers and alarms, played even if the earcons are turned off
+ **/
+pub fn chime(path: Option<&String>) -> Speech {
+    load(path, tones(&[880.0, 660.0, 880.0, 660.0], 200))
+}
+
+/**
+ * Short sounds that let users without a screen know what the bot is doing
+ **/
+#[derive(Clone)]
+pub struct Earcons {
+    sounds: Option<[Speech; 3]>, // Listening, thinking and error, None if turned off
+}
+
+impl Earcons {
+    pub fn new(cfg: &EarconConfig) -> Self {
+        let sounds = cfg.earcons.then(|| {
+            [
+                load(cfg.earcon_listening.as_ref(), tones(&[660.0, 880.0], 80)),
+                load(cfg.earcon_thinking.as_ref(), tones(&[520.0], 100)),
+                load(cfg.earcon_error.as_ref(), tones(&[330.0, 220.0], 150)),
+            ]
+        });
+
+        Self { sounds }
+    }
+
+    pub fn get(&self, earcon: Earcon) -> Option<&Speech> {
+        self.sounds.as_ref().map(|sounds| &sounds[earcon as usize])
+    }
+
+    pub fn play(&self, sink: Option<&Sink>, earcon: Earcon) {
+        if let (Some(sink), Some(sound)) = (sink, self.get(earcon)) {
+            sink.append(SamplesBuffer::new(
+                sound.channels,
+                sound.sample_rate,
+                sound.samples.clone(),
+            ));
+        }
+    }
+}
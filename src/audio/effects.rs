@@ -0,0 +1,180 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * Length of the grains the pitch shifter works with
+ **/
+const PITCH_WINDOW_MS: f32 = 40.0;
+
+/**
+ * Delays of the Schroeder reverb in milliseconds, chosen so they don't resonate with each other
+ **/
+const COMB_DELAYS_MS: [f32; 4] = [29.7, 37.1, 41.1, 43.7];
+const ALLPASS_DELAYS_MS: [f32; 2] = [5.0, 1.7];
+const ALLPASS_GAIN: f32 = 0.7;
+
+/**
+ * Silence added to the end so the reverb can fade out instead of being cut off
+ **/
+const REVERB_TAIL_MS: f32 = 250.0;
+
+/**
+ * An effect that changes how the voice sounds, e.g. {"effect": "pitch", "semitones": 3}
+ **/
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "effect", rename_all = "lowercase")]
+pub enum Effect {
+    Pitch {
+        semitones: f32, // Higher or lower voice without talking faster or slower
+    },
+    Reverb {
+        #[serde(default = "default_reverb_mix")]
+        mix: f32, // How much of the reverb is heard, from 0 to 1
+        #[serde(default = "default_room_size")]
+        room_size: f32, // How long the reverb lasts, from 0 to 1
+    },
+    Robot {
+        #[serde(default = "default_robot_frequency")]
+        frequency: f32, // Frequency in Hz the voice is ring modulated with, lower sounds more metallic
+    },
+}
+
+fn default_reverb_mix() -> f32 {
+    0.2
+}
+
+fn default_room_size() -> f32 {
+    0.7
+}
+
+fn default_robot_frequency() -> f32 {
+    50.0
+}
+
+/**
+ * Read between two samples by interpolating linearly
+ **/
+fn sample_at(samples: &[f32], position: f32) -> f32 {
+    if position < 0.0 {
+        return 0.0;
+    }
+    let index = position as usize;
+    let fraction = position - index as f32;
+    let a = samples.get(index).copied().unwrap_or(0.0);
+    let b = samples.get(index + 1).copied().unwrap_or(0.0);
+    a + (b - a) * fraction
+}
+
+/**
+ * Delay line pitch shifter: two read positions move through the audio at a different speed than
+ * it is written and jump back a window when they run too far ahead or behind. They are half a
+ * window apart and cross faded, so one is always quiet while the other jumps.
+ **/
+fn pitch(samples: &[f32], sample_rate: u32, semitones: f32) -> Vec<f32> {
+    let ratio = 2f32.powf(semitones / 12.0);
+    let window = sample_rate as f32 * PITCH_WINDOW_MS / 1000.0;
+
+    (0..samples.len())
+        .map(|i| {
+            let delay = ((1.0 - ratio) * i as f32).rem_euclid(window);
+            [delay, (delay + window / 2.0) % window]
+                .iter()
+                .map(|delay| {
+                    let gain = 1.0 - (2.0 * delay / window - 1.0).abs();
+                    gain * sample_at(samples, i as f32 - delay)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/**
+ * Schroeder reverb: parallel feedback combs make the echoes, allpass filters diffuse them
+ **/
+fn reverb(samples: &[f32], sample_rate: u32, mix: f32, room_size: f32) -> Vec<f32> {
+    let delay = |ms: f32| (sample_rate as f32 * ms / 1000.0) as usize;
+    let feedback = 0.7 + 0.28 * room_size.clamp(0.0, 1.0);
+
+    let mut input = samples.to_vec();
+    input.resize(samples.len() + delay(REVERB_TAIL_MS), 0.0);
+
+    let mut wet = vec![0.0; input.len()];
+    for ms in COMB_DELAYS_MS {
+        let delay = delay(ms);
+        let mut comb = vec![0.0; input.len()];
+        for i in 0..input.len() {
+            let echo = if i >= delay { comb[i - delay] } else { 0.0 };
+            comb[i] = input[i] + feedback * echo;
+        }
+        wet.iter_mut()
+            .zip(&comb)
+            .for_each(|(w, c)| *w += c / COMB_DELAYS_MS.len() as f32);
+    }
+
+    for ms in ALLPASS_DELAYS_MS {
+        let delay = delay(ms);
+        let mut allpass = vec![0.0; wet.len()];
+        for i in 0..wet.len() {
+            let delayed_in = if i >= delay { wet[i - delay] } else { 0.0 };
+            let delayed_out = if i >= delay { allpass[i - delay] } else { 0.0 };
+            allpass[i] = -ALLPASS_GAIN * wet[i] + delayed_in + ALLPASS_GAIN * delayed_out;
+        }
+        wet = allpass;
+    }
+
+    let mix = mix.clamp(0.0, 1.0);
+    input
+        .iter()
+        .zip(&wet)
+        .map(|(dry, wet)| dry * (1.0 - mix) + wet * mix)
+        .collect()
+}
+
+/**
+ * Ring modulation, multiplying the voice with a sine wave makes it sound like a robot
+ **/
+fn robot(samples: &[f32], sample_rate: u32, frequency: f32) -> Vec<f32> {
+    samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| sample * (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin())
+        .collect()
+}
+
+impl Effect {
+    fn apply_mono(&self, samples: &[f32], sample_rate: u32) -> Vec<f32> {
+        match *self {
+            Effect::Pitch { semitones } => pitch(samples, sample_rate, semitones),
+            Effect::Reverb { mix, room_size } => reverb(samples, sample_rate, mix, room_size),
+            Effect::Robot { frequency } => robot(samples, sample_rate, frequency),
+        }
+    }
+
+    /**
+     * Apply the effect to every channel of the interleaved audio
+     **/
+    pub fn apply(&self, samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+        let channels = channels.max(1) as usize;
+        if channels == 1 {
+            return self.apply_mono(samples, sample_rate);
+        }
+
+        let processed: Vec<Vec<f32>> = (0..channels)
+            .map(|channel| {
+                let mono: Vec<f32> = samples
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .copied()
+                    .collect();
+                self.apply_mono(&mono, sample_rate)
+            })
+            .collect();
+
+        let len = processed.iter().map(Vec::len).min().unwrap_or(0);
+        (0..len)
+            .flat_map(|i| processed.iter().map(move |channel| channel[i]))
+            .collect()
+    }
+}
@@ -0,0 +1,67 @@
+use cpal::traits::DeviceTrait;
+
+/**
+ * Find the audio host (backend) with the given name, e.g. "alsa" or "jack"
+ * Falls back to the default host if it isn't available
+ **/
+pub fn select_host(wanted: Option<&str>) -> cpal::Host {
+    let Some(wanted) = wanted else {
+        return cpal::default_host();
+    };
+
+    let host = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(wanted))
+        .and_then(|id| cpal::host_from_id(id).ok());
+
+    match host {
+        Some(host) => host,
+        None => {
+            let available: Vec<_> = cpal::available_hosts().iter().map(|id| id.name()).collect();
+            println!(
+                "Audio host {} not available, using default. Available hosts: {}",
+                wanted,
+                available.join(", ")
+            );
+            cpal::default_host()
+        }
+    }
+}
+
+/**
+ * All hosts that can be used on this system
+ **/
+pub fn available_hosts() -> Vec<cpal::Host> {
+    cpal::available_hosts()
+        .into_iter()
+        .filter_map(|id| cpal::host_from_id(id).ok())
+        .collect()
+}
+
+/**
+ * Find a device by its index or by (part of) its name. A device with exactly that name
+ * wins, so e.g. "hw:CARD=Device,DEV=0" doesn't pick "plughw:CARD=Device,DEV=0".
+ **/
+pub fn find_device(
+    devices: impl Iterator<Item = cpal::Device>,
+    wanted: &str,
+) -> Option<cpal::Device> {
+    let mut devices: Vec<cpal::Device> = devices.collect();
+    if let Ok(index) = wanted.parse::<usize>() {
+        return (index < devices.len()).then(|| devices.swap_remove(index));
+    }
+
+    let named = |exact: bool| {
+        devices.iter().position(|device| {
+            device.name().is_ok_and(|name| {
+                if exact {
+                    name == wanted
+                } else {
+                    name.contains(wanted)
+                }
+            })
+        })
+    };
+    let index = named(true).or_else(|| named(false))?;
+    Some(devices.swap_remove(index))
+}
@@ -0,0 +1,302 @@
+use std::error::Error;
+use std::io::{BufReader, Read};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use hound::WavReader;
+
+use crate::audio::downmix::downmix_to_mono;
+use crate::audio::host::find_device;
+
+// Whisper wants 16 kHz mono, recording it that way saves resampling and downmixing
+const PREFERRED_SAMPLE_RATE: u32 = 16_000;
+
+/**
+ * Where the audio comes from, either a microphone or a WAV file
+ * This has to be kept alive for as long as audio should be recorded
+ **/
+pub enum Input {
+    Device { _stream: cpal::Stream },
+    File { _reader: JoinHandle<()> },
+}
+
+/**
+ * Build an input stream for the sample type T of the device
+ * Every sample is converted to f32 and downmixed to mono before being passed on, so the
+ * rest of the program doesn't need to care about the format the sound card delivers
+ **/
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut on_data: impl FnMut(&[f32]) + Send + 'static,
+    on_error: impl FnMut(cpal::StreamError) + Send + 'static,
+    timeout: Option<Duration>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let mut samples = Vec::new();
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            samples.clear();
+            samples.extend(data.iter().map(|s| s.to_sample::<f32>()));
+            on_data(&downmix_to_mono(&samples, channels));
+        },
+        on_error,
+        timeout,
+    )
+}
+
+/**
+ * Find the input device given in the config, either by its index or by (part of) its name
+ * Falls back to the default input device if it can't be found
+ **/
+pub fn select_input_device(host: &cpal::Host, wanted: Option<&str>) -> Option<cpal::Device> {
+    if let Some(wanted) = wanted {
+        let device = (host.input_devices().ok()).and_then(|devices| find_device(devices, wanted));
+
+        match device {
+            Some(device) => return Some(device),
+            None => println!("Input device {} not found, using default", wanted),
+        }
+    }
+
+    host.default_input_device()
+}
+
+/**
+ * How much a sample format is preferred, lower is better, None if it can't be used
+ **/
+fn format_rank(format: cpal::SampleFormat) -> Option<u8> {
+    match format {
+        cpal::SampleFormat::F32 => Some(0),
+        cpal::SampleFormat::I16 => Some(1),
+        cpal::SampleFormat::I32 => Some(2),
+        cpal::SampleFormat::U16 => Some(3),
+        _ => None,
+    }
+}
+
+/**
+ * The config closest to 16 kHz mono that the device supports. Rates below 16 kHz are only
+ * used if there is nothing else, they lose detail Whisper needs.
+ * None if the device doesn't list its configs or can't record in a usable format.
+ **/
+fn preferred_input_config(device: &cpal::Device) -> Option<cpal::SupportedStreamConfig> {
+    let configs = device.supported_input_configs().ok()?;
+    configs
+        .filter(|range| format_rank(range.sample_format()).is_some())
+        .filter(|range| range.min_sample_rate() <= range.max_sample_rate())
+        .map(|range| {
+            let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+            let rate = PREFERRED_SAMPLE_RATE.clamp(min, max);
+            range.with_sample_rate(cpal::SampleRate(rate))
+        })
+        .min_by_key(|config| {
+            let rate = config.sample_rate().0;
+            (
+                rate < PREFERRED_SAMPLE_RATE,
+                rate.abs_diff(PREFERRED_SAMPLE_RATE),
+                config.channels(),
+                format_rank(config.sample_format()),
+            )
+        })
+}
+
+/**
+ * Open the input device and start recording from it
+ * buffer_size is the number of frames per callback, smaller means less latency but more CPU
+ * With negotiate the device is asked for 16 kHz mono, or what comes closest to it, and
+ * its default config is only used if that doesn't work
+ * make_on_data is called with the sample rate of the device to create the data callback
+ * Returns the running input and its sample rate
+ **/
+pub fn open_input_stream<D>(
+    host: &cpal::Host,
+    wanted: Option<&str>,
+    buffer_size: Option<u32>,
+    negotiate: bool,
+    make_on_data: impl Fn(usize) -> D,
+    on_error: impl FnMut(cpal::StreamError) + Send + Clone + 'static,
+    timeout: Option<Duration>,
+) -> Result<(Input, usize), Box<dyn Error>>
+where
+    D: FnMut(&[f32]) + Send + 'static,
+{
+    let device = select_input_device(host, wanted).ok_or("No input device available")?;
+    println!("Input device: {:?}", device.name());
+
+    let mut configs = Vec::new();
+    if negotiate {
+        configs.extend(preferred_input_config(&device));
+    }
+    match device.default_input_config() {
+        Ok(config) if configs.contains(&config) => {}
+        Ok(config) => configs.push(config),
+        Err(err) if configs.is_empty() => return Err(err.into()),
+        Err(err) => eprintln!("No default input config: {}", err),
+    }
+
+    let mut error = None;
+    for config in configs {
+        let sample_rate = config.sample_rate().0 as usize;
+        println!("Sample rate: {}", sample_rate);
+        println!("Sample format: {}", config.sample_format());
+        println!("Channels: {}", config.channels());
+
+        let on_data = make_on_data(sample_rate);
+        match start_stream(
+            &device,
+            config,
+            buffer_size,
+            on_data,
+            on_error.clone(),
+            timeout,
+        ) {
+            Ok(stream) => return Ok((Input::Device { _stream: stream }, sample_rate)),
+            Err(err) => {
+                eprintln!("Failed to record with this config: {}", err);
+                error = Some(err);
+            }
+        }
+    }
+    Err(error.unwrap_or_else(|| "No input config available".into()))
+}
+
+/**
+ * Build the stream for the config and start it
+ **/
+fn start_stream<D>(
+    device: &cpal::Device,
+    config: cpal::SupportedStreamConfig,
+    buffer_size: Option<u32>,
+    on_data: D,
+    on_error: impl FnMut(cpal::StreamError) + Send + 'static,
+    timeout: Option<Duration>,
+) -> Result<cpal::Stream, Box<dyn Error>>
+where
+    D: FnMut(&[f32]) + Send + 'static,
+{
+    let sample_format = config.sample_format();
+    let supported_sizes = *config.buffer_size();
+    let mut config: cpal::StreamConfig = config.into();
+
+    if let Some(frames) = buffer_size {
+        let frames = match supported_sizes {
+            cpal::SupportedBufferSize::Range { min, max } => frames.clamp(min, max),
+            cpal::SupportedBufferSize::Unknown => frames,
+        };
+        println!("Buffer size: {} frames", frames);
+        config.buffer_size = cpal::BufferSize::Fixed(frames);
+    }
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => {
+            build_input_stream::<f32>(device, &config, on_data, on_error, timeout)
+        }
+        cpal::SampleFormat::I16 => {
+            build_input_stream::<i16>(device, &config, on_data, on_error, timeout)
+        }
+        cpal::SampleFormat::U16 => {
+            build_input_stream::<u16>(device, &config, on_data, on_error, timeout)
+        }
+        cpal::SampleFormat::I32 => {
+            build_input_stream::<i32>(device, &config, on_data, on_error, timeout)
+        }
+        format => return Err(format!("Sample format {} is not supported.", format).into()),
+    }?;
+
+    stream.play()?;
+    Ok(stream)
+}
+
+/**
+ * Read the audio from a WAV file instead of a microphone, or from stdin if the path is "-"
+ * The samples are passed on in real time, so the VAD and silence detection behave just like
+ * with a microphone. on_end is called once all samples have been passed on.
+ **/
+pub fn open_wav_input<D>(
+    path: &str,
+    make_on_data: impl FnOnce(usize) -> D,
+    on_end: impl FnOnce() + Send + 'static,
+) -> Result<(Input, usize), Box<dyn Error>>
+where
+    D: FnMut(&[f32]) + Send + 'static,
+{
+    if path == "-" {
+        let reader = WavReader::new(BufReader::new(std::io::stdin()))?;
+        spawn_wav_reader(reader, make_on_data, on_end)
+    } else {
+        let reader = WavReader::open(path)?;
+        spawn_wav_reader(reader, make_on_data, on_end)
+    }
+}
+
+fn spawn_wav_reader<R, D>(
+    reader: WavReader<R>,
+    make_on_data: impl FnOnce(usize) -> D,
+    on_end: impl FnOnce() + Send + 'static,
+) -> Result<(Input, usize), Box<dyn Error>>
+where
+    R: Read + Send + 'static,
+    D: FnMut(&[f32]) + Send + 'static,
+{
+    let spec = reader.spec();
+    let sample_rate = spec.sample_rate as usize;
+    let channels = spec.channels as usize;
+    println!("Input file: {:?}", spec);
+
+    let samples: Box<dyn Iterator<Item = f32> + Send> = match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.into_samples::<f32>().map_while(Result::ok)),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            Box::new(
+                reader
+                    .into_samples::<i32>()
+                    .map_while(Result::ok)
+                    .map(move |s| s as f32 / scale),
+            )
+        }
+    };
+
+    let mut on_data = make_on_data(sample_rate);
+
+    // Pass the audio on in chunks of 10ms
+    let chunk_len = (sample_rate / 100).max(1) * channels;
+
+    let handle = std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut frames = 0;
+        let mut chunk = Vec::with_capacity(chunk_len);
+
+        for sample in samples {
+            chunk.push(sample);
+            if chunk.len() < chunk_len {
+                continue;
+            }
+
+            on_data(&downmix_to_mono(&chunk, channels));
+            chunk.clear();
+
+            // Wait until the audio would have been recorded by a real microphone
+            frames += chunk_len / channels;
+            let position = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+            if let Some(wait) = position.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+
+        if !chunk.is_empty() {
+            on_data(&downmix_to_mono(&chunk, channels));
+        }
+
+        on_end();
+    });
+
+    Ok((Input::File { _reader: handle }, sample_rate))
+}
@@ -0,0 +1,53 @@
+/**
+ * The loudness is measured in blocks of this many milliseconds
+ **/
+const BLOCK_MS: usize = 100;
+
+/**
+ * Blocks quieter than this energy (about -70 dB) are silence and not measured at all,
+ * blocks more than 10 dB quieter than the average are pauses between words
+ **/
+const ABSOLUTE_GATE: f32 = 1e-7;
+const RELATIVE_GATE: f32 = 0.1;
+
+/**
+ * The peak is never amplified above this, so the audio doesn't clip
+ **/
+const MAX_PEAK: f32 = 0.99;
+
+/**
+ * How loud the audio is, similar to EBU R128: the RMS level of the blocks that contain
+ * sound, so pauses and silence at the start and end don't make it seem quieter
+ **/
+pub fn loudness(samples: &[f32], sample_rate: u32, channels: u16) -> Option<f32> {
+    let block = (sample_rate as usize * channels as usize * BLOCK_MS / 1000).max(1);
+    let energies: Vec<f32> = samples
+        .chunks(block)
+        .map(|block| block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32)
+        .filter(|energy| *energy > ABSOLUTE_GATE)
+        .collect();
+    if energies.is_empty() {
+        return None;
+    }
+
+    let mean = energies.iter().sum::<f32>() / energies.len() as f32;
+    let loud: Vec<f32> = energies
+        .into_iter()
+        .filter(|energy| *energy > mean * RELATIVE_GATE)
+        .collect();
+
+    Some((loud.iter().sum::<f32>() / loud.len() as f32).sqrt())
+}
+
+/**
+ * Bring the audio to the target RMS level, e.g. 0.15, without letting it clip
+ **/
+pub fn normalize_loudness(samples: &mut [f32], sample_rate: u32, channels: u16, target: f32) {
+    let Some(loudness) = loudness(samples, sample_rate, channels) else {
+        return;
+    };
+
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let gain = (target / loudness).min(MAX_PEAK / peak);
+    samples.iter_mut().for_each(|s| *s *= gain);
+}
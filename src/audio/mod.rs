@@ -0,0 +1,13 @@
+pub mod agc;
+pub mod alsa;
+pub mod downmix;
+pub mod duck;
+pub mod earcon;
+pub mod effects;
+pub mod host;
+pub mod input;
+pub mod loudness;
+pub mod output;
+pub mod resample;
+pub mod ring;
+pub mod wav;
@@ -0,0 +1,34 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{OutputStream, OutputStreamHandle, StreamError};
+
+use crate::audio::host::find_device;
+
+/**
+ * Find the output device given in the config, either by its index or by (part of) its name
+ * Falls back to the default output device if it can't be found
+ **/
+pub fn select_output_device(host: &cpal::Host, wanted: Option<&str>) -> Option<cpal::Device> {
+    if let Some(wanted) = wanted {
+        let device = (host.output_devices().ok()).and_then(|devices| find_device(devices, wanted));
+
+        match device {
+            Some(device) => return Some(device),
+            None => println!("Output device {} not found, using default", wanted),
+        }
+    }
+
+    host.default_output_device()
+}
+
+/**
+ * Open the output device the responses are played on
+ * The stream has to be kept alive for as long as audio should be played
+ **/
+pub fn open_output_stream(
+    host: &cpal::Host,
+    wanted: Option<&str>,
+) -> Result<(OutputStream, OutputStreamHandle), StreamError> {
+    let device = select_output_device(host, wanted).ok_or(StreamError::NoDevice)?;
+    println!("Output device: {}", device.name().unwrap_or_default());
+    OutputStream::try_from_device(&device)
+}
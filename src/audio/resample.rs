@@ -0,0 +1,236 @@
+use std::f32::consts::PI;
+
+/**
+ * Number of zero crossings of the sinc function on each side of the filter.
+ * More zero crossings give a steeper anti-aliasing filter but cost more time.
+ **/
+const ZERO_CROSSINGS: usize = 16;
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/**
+ * Blackman window centered around 0, reaching 0 at +-half_width
+ **/
+fn blackman(x: f32, half_width: f32) -> f32 {
+    if x.abs() >= half_width {
+        return 0.0;
+    }
+    let t = PI * x / half_width;
+    0.42 + 0.5 * t.cos() + 0.08 * (2.0 * t).cos()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/**
+ * Resamples audio from one sample rate to another using a windowed sinc filter.
+ * When downsampling, the cutoff of the filter is lowered to the new Nyquist frequency,
+ * so frequencies that can't be represented anymore are removed instead of aliasing
+ * back into the speech band. This is needed because the Whisper model expects 16kHz audio.
+ *
+ * An output sample can only fall on as many different positions between two input samples
+ * as the reduced output rate, e.g. always on an input sample from 48kHz to 16kHz, so the
+ * filter is calculated once for each of them instead of for every output sample.
+ *
+ * Audio that comes in chunks is resampled as if it was one long signal, the input the
+ * filter needs is kept until the next chunk, so output samples come a few samples later.
+ **/
+pub struct Resampler {
+    from_rate: usize,
+    to_rate: usize,
+    reach: usize,
+    filters: Vec<Vec<f32>>, // The taps from -reach to reach for each position
+    pending: Vec<f32>,      // The input the next output samples still need
+    start: usize,           // Where the pending input starts in the whole input
+    next: usize,            // How many samples were output so far
+}
+
+impl Resampler {
+    pub fn new(from_rate: usize, to_rate: usize) -> Self {
+        let divisor = gcd(from_rate, to_rate).max(1);
+        let (from_rate, to_rate) = (from_rate / divisor, to_rate / divisor);
+        let ratio = from_rate as f64 / to_rate as f64;
+
+        // Cutoff relative to the input Nyquist frequency, only lowered when downsampling
+        let cutoff = (1.0 / ratio).min(1.0) as f32;
+        let half_width = ZERO_CROSSINGS as f32 / cutoff;
+        let reach = half_width.ceil() as usize;
+
+        let filters = (0..to_rate)
+            .map(|position| {
+                let offset = position as f64 / to_rate as f64;
+                (0..=2 * reach)
+                    .map(|tap| {
+                        let x = (tap as f64 - reach as f64 - offset) as f32;
+                        sinc(cutoff * x) * blackman(x, half_width)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            from_rate,
+            to_rate,
+            reach,
+            filters,
+            pending: Vec::new(),
+            start: 0,
+            next: 0,
+        }
+    }
+
+    /**
+     * The input sample output sample n is centered on
+     **/
+    fn center(&self, n: usize) -> usize {
+        n * self.from_rate / self.to_rate
+    }
+
+    /**
+     * Output sample n from the input that is there until end, the filter is cut at the edges
+     **/
+    fn sample(&self, n: usize, end: usize) -> f32 {
+        let center = self.center(n);
+        let filter = &self.filters[n * self.from_rate % self.to_rate];
+        let first = center.saturating_sub(self.reach).max(self.start);
+        let last = (center + self.reach).min(end - 1);
+
+        let mut sum = 0.0;
+        let mut weight = 0.0;
+        for i in first..=last {
+            let w = filter[i + self.reach - center];
+            sum += self.pending[i - self.start] * w;
+            weight += w;
+        }
+
+        // Normalizing by the sum of weights keeps the gain at 1, also at the edges
+        if weight != 0.0 { sum / weight } else { 0.0 }
+    }
+
+    /**
+     * The output samples for which all input the filter reaches is there now
+     **/
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            return input.to_vec();
+        }
+
+        self.pending.extend_from_slice(input);
+        let end = self.start + self.pending.len();
+        let mut output = Vec::new();
+        while self.center(self.next) + self.reach < end {
+            output.push(self.sample(self.next, end));
+            self.next += 1;
+        }
+
+        // Forget the input no later output sample reaches
+        let keep = (self.center(self.next).saturating_sub(self.reach)).max(self.start);
+        self.pending.drain(..keep - self.start);
+        self.start = keep;
+        output
+    }
+
+    /**
+     * The output samples that still wait for input once there is no more of it
+     **/
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            return Vec::new();
+        }
+
+        let end = self.start + self.pending.len();
+        let output_len = end * self.to_rate / self.from_rate;
+        let output = (self.next..output_len)
+            .map(|n| self.sample(n, end))
+            .collect();
+        self.next = output_len.max(self.next);
+        output
+    }
+}
+
+/**
+ * Resample audio that is only resampled once, audio that comes in chunks should keep a Resampler
+ **/
+pub fn resample(input: &[f32], from_rate: usize, to_rate: usize) -> Vec<f32> {
+    if from_rate == to_rate || input.is_empty() {
+        return input.to_vec();
+    }
+    let mut resampler = Resampler::new(from_rate, to_rate);
+    let mut output = resampler.process(input);
+    output.extend(resampler.flush());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_rate_passes_through() {
+        let input: Vec<f32> = (0..100).map(|i| (i as f32 * 0.1).sin()).collect();
+        assert_eq!(resample(&input, 16_000, 16_000), input);
+        let mut resampler = Resampler::new(48_000, 48_000);
+        assert_eq!(resampler.process(&input), input);
+        assert!(resampler.flush().is_empty());
+        assert!(resample(&[], 48_000, 16_000).is_empty());
+    }
+
+    #[test]
+    fn downsampling_48k_to_16k() {
+        let input: Vec<f32> = (0..48_000).map(|i| (i as f32 * 0.01).sin()).collect();
+        assert_eq!(resample(&input, 48_000, 16_000).len(), 16_000);
+        assert_eq!(resample(&input[..1000], 48_000, 16_000).len(), 333);
+    }
+
+    #[test]
+    fn keeps_constant_signal() {
+        let output = resample(&[0.5; 44_100], 44_100, 16_000);
+        assert_eq!(output.len(), 16_000);
+        assert!(output.iter().all(|s| (s - 0.5).abs() < 1e-4));
+    }
+
+    #[test]
+    fn matches_filter_calculated_per_sample() {
+        let input: Vec<f32> = (0..4410).map(|i| (i as f32 * 0.37).sin()).collect();
+        let output = resample(&input, 44_100, 16_000);
+
+        let ratio = 44_100.0 / 16_000.0;
+        let cutoff = (1.0 / ratio) as f32;
+        let half_width = ZERO_CROSSINGS as f32 / cutoff;
+        for (n, sample) in output.iter().enumerate().step_by(97) {
+            let center = n as f64 * ratio;
+            let (mut sum, mut weight) = (0.0, 0.0);
+            for (i, s) in input.iter().enumerate() {
+                let x = (i as f64 - center) as f32;
+                let w = sinc(cutoff * x) * blackman(x, half_width);
+                sum += s * w;
+                weight += w;
+            }
+            assert!((sample - sum / weight).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn chunks_match_one_go() {
+        let input: Vec<f32> = (0..20_000).map(|i| (i as f32 * 0.05).sin()).collect();
+        for (from_rate, to_rate) in [(48_000, 16_000), (44_100, 16_000), (8_000, 16_000)] {
+            let whole = resample(&input, from_rate, to_rate);
+
+            let mut resampler = Resampler::new(from_rate, to_rate);
+            let mut chunked = Vec::new();
+            for chunk in input.chunks(333) {
+                chunked.extend(resampler.process(chunk));
+            }
+            chunked.extend(resampler.flush());
+
+            assert_eq!(chunked, whole, "{} to {}", from_rate, to_rate);
+        }
+    }
+}
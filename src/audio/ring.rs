@@ -0,0 +1,35 @@
+use std::collections::VecDeque;
+
+/**
+ * Fixed size buffer that only keeps the most recent samples
+ **/
+pub struct RingBuffer {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /**
+     * Append the samples, dropping the oldest ones once the buffer is full
+     **/
+    pub fn push(&mut self, data: &[f32]) {
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        let overflow = (self.samples.len() + data.len()).saturating_sub(self.capacity);
+        self.samples.drain(..overflow);
+        self.samples.extend(data);
+    }
+
+    /**
+     * Take all samples out of the buffer, oldest first
+     **/
+    pub fn drain(&mut self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.drain(..)
+    }
+}
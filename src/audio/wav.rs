@@ -0,0 +1,19 @@
+use std::path::Path;
+
+/**
+ * Write mono audio to a 16 bit WAV file
+ **/
+pub fn save_wav(path: &Path, samples: &[f32], sample_rate: usize) -> hound::Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()
+}
@@ -0,0 +1,137 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::audio::input::open_input_stream;
+
+/**
+ * The config that gets compiled into the program, cargo rebuilds when it changes
+ **/
+const CONFIG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config.json");
+
+const QUIET_DURATION: Duration = Duration::from_secs(4);
+const SPEAK_DURATION: Duration = Duration::from_secs(6);
+const METER_WIDTH: usize = 50;
+
+/**
+ * Volume in the same unit as silence_threshold (mean of the squared samples)
+ **/
+fn level(samples: &[f32]) -> f32 {
+    samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32
+}
+
+/**
+ * Draw a level meter on the current line, using a logarithmic scale from -90dB to 0dB
+ **/
+fn draw_meter(level: f32) {
+    let db = (10.0 * level.max(1e-9).log10()).clamp(-90.0, 0.0);
+    let filled = ((db + 90.0) / 90.0 * METER_WIDTH as f32) as usize;
+    print!(
+        "\r[{}{}] {:>6.1} dB  {:.8}",
+        "#".repeat(filled),
+        " ".repeat(METER_WIDTH - filled),
+        db,
+        level
+    );
+    std::io::stdout().flush().unwrap();
+}
+
+/**
+ * Show the meter for the given duration and return all measured levels
+ **/
+fn record_levels(levels: &Arc<Mutex<Vec<f32>>>, duration: Duration) -> Vec<f32> {
+    levels.lock().unwrap().clear();
+    let start = Instant::now();
+
+    while start.elapsed() < duration {
+        std::thread::sleep(Duration::from_millis(100));
+        if let Some(level) = levels.lock().unwrap().last() {
+            draw_meter(*level);
+        }
+    }
+    println!();
+
+    let mut measured = levels.lock().unwrap().clone();
+    measured.sort_by(f32::total_cmp);
+    measured
+}
+
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted[((sorted.len() - 1) as f32 * p) as usize]
+}
+
+/**
+ * Replace the silence_threshold in the config file, keeping the rest of it untouched
+ **/
+fn write_threshold(threshold: f32) -> std::io::Result<()> {
+    let config = std::fs::read_to_string(CONFIG_PATH)?;
+    let regex = Regex::new(r#""silence_threshold"\s*:\s*[-+0-9.eE]+"#).unwrap();
+    let config = regex.replace(&config, format!(r#""silence_threshold": {}"#, threshold));
+    std::fs::write(CONFIG_PATH, config.as_bytes())
+}
+
+/**
+ * Interactive calibration, measures the level of the room and of the voice of the user,
+ * suggests a silence_threshold in between and writes it to the config if wanted
+ **/
+pub fn calibrate(host: &cpal::Host, input_device: Option<&str>, negotiate: bool) {
+    let levels = Arc::new(Mutex::new(Vec::new()));
+
+    let make_on_data = |_| {
+        let levels_clone = Arc::clone(&levels);
+        move |data: &[f32]| {
+            levels_clone.lock().unwrap().push(level(data));
+        }
+    };
+
+    let on_error = |err| eprintln!("Stream error: {:?}", err);
+    let (_input, _) = open_input_stream(
+        host,
+        input_device,
+        None,
+        negotiate,
+        make_on_data,
+        on_error,
+        None,
+    )
+    .expect("Failed to open input device");
+
+    println!("Please be quiet for a few seconds...");
+    let quiet = record_levels(&levels, QUIET_DURATION);
+
+    println!("Now talk normally for a few seconds...");
+    let speech = record_levels(&levels, SPEAK_DURATION);
+
+    let noise = percentile(&quiet, 0.95);
+    let voice = percentile(&speech, 0.5);
+    println!("Noise level: {:.8}", noise);
+    println!("Voice level: {:.8}", voice);
+
+    if voice <= noise {
+        println!(
+            "Your voice was not louder than the background noise, try moving closer to the microphone."
+        );
+        return;
+    }
+
+    // Levels are best compared on a logarithmic scale, so take the geometric mean
+    let threshold = (noise * voice).sqrt();
+    println!("Suggested silence_threshold: {:.8}", threshold);
+
+    print!("Write it to {}? [y/N] ", CONFIG_PATH);
+    std::io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).unwrap();
+    if answer.trim().eq_ignore_ascii_case("y") {
+        match write_threshold(threshold) {
+            Ok(()) => println!("Saved, it will be used the next time you run the program."),
+            Err(err) => eprintln!("Failed to write config: {}", err),
+        }
+    }
+}
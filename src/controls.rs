@@ -0,0 +1,131 @@
+use regex::Regex;
+
+// Every command changes the speech rate or volume by this factor
+const STEP: f32 = 1.2;
+const MIN_SPEECH_RATE: f32 = 0.5;
+const MAX_SPEECH_RATE: f32 = 2.0;
+const MIN_VOLUME: f32 = 0.1;
+const MAX_VOLUME: f32 = 2.0;
+
+#[derive(Debug, PartialEq)]
+pub enum Control {
+    Slower,
+    Faster,
+    Louder,
+    Quieter,
+}
+
+/**
+ * Whether the user wants the bot to talk differently, e.g. "Speak slower", "Louder please"
+ * or "Could you be a bit quieter?". Only short commands count, so "I like louder music"
+ * doesn't change anything.
+ **/
+pub fn control_command(text: &str) -> Option<Control> {
+    let regex = Regex::new(
+        r"(?i)^\W*(?:(?:can|could) you\s+)?(?:please\s+)?(?:(?:speak|talk|be)\s+)?(?:a\s+(?:bit|little)\s+)?(slower|more slowly|faster|quicker|louder|quieter|softer)\b(?:\s+please)?\W*$",
+    )
+    .unwrap();
+
+    let captures = regex.captures(text)?;
+    match captures[1].to_lowercase().as_str() {
+        "slower" | "more slowly" => Some(Control::Slower),
+        "faster" | "quicker" => Some(Control::Faster),
+        "louder" => Some(Control::Louder),
+        _ => Some(Control::Quieter),
+    }
+}
+
+/**
+ * One step up or down, rounded so the settings file stays readable
+ **/
+fn step(value: f32, up: bool, min: f32, max: f32) -> f32 {
+    let value = if up { value * STEP } else { value / STEP };
+    ((value * 100.0).round() / 100.0).clamp(min, max)
+}
+
+impl Control {
+    /**
+     * The speech rate after the command, None if it doesn't change the speech rate
+     **/
+    pub fn speech_rate(&self, speech_rate: Option<f32>) -> Option<f32> {
+        let rate = speech_rate.unwrap_or(1.0);
+        match self {
+            Control::Slower => Some(step(rate, false, MIN_SPEECH_RATE, MAX_SPEECH_RATE)),
+            Control::Faster => Some(step(rate, true, MIN_SPEECH_RATE, MAX_SPEECH_RATE)),
+            _ => None,
+        }
+    }
+
+    /**
+     * The volume after the command, None if it doesn't change the volume
+     **/
+    pub fn volume(&self, volume: f32) -> Option<f32> {
+        match self {
+            Control::Louder => Some(step(volume, true, MIN_VOLUME, MAX_VOLUME)),
+            Control::Quieter => Some(step(volume, false, MIN_VOLUME, MAX_VOLUME)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let cases = [
+            ("Speak slower", Some(Control::Slower)),
+            ("Talk more slowly please.", Some(Control::Slower)),
+            ("Faster", Some(Control::Faster)),
+            ("Quicker!", Some(Control::Faster)),
+            ("Louder please", Some(Control::Louder)),
+            ("Could you be a bit quieter?", Some(Control::Quieter)),
+            ("softer", Some(Control::Quieter)),
+            ("I like louder music", None),
+            ("Play something faster", None),
+            ("", None),
+        ];
+        for (text, control) in cases {
+            assert_eq!(control_command(text), control, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn speech_rate_steps() {
+        assert_eq!(Control::Faster.speech_rate(None), Some(1.2));
+        assert_eq!(Control::Slower.speech_rate(None), Some(0.83));
+        assert_eq!(Control::Faster.speech_rate(Some(1.2)), Some(1.44));
+        assert_eq!(Control::Louder.speech_rate(Some(1.0)), None);
+    }
+
+    #[test]
+    fn speech_rate_bounds() {
+        assert_eq!(
+            Control::Faster.speech_rate(Some(1.9)),
+            Some(MAX_SPEECH_RATE)
+        );
+        assert_eq!(
+            Control::Slower.speech_rate(Some(0.55)),
+            Some(MIN_SPEECH_RATE)
+        );
+        assert_eq!(
+            Control::Faster.speech_rate(Some(MAX_SPEECH_RATE)),
+            Some(MAX_SPEECH_RATE)
+        );
+    }
+
+    #[test]
+    fn volume_steps() {
+        assert_eq!(Control::Louder.volume(1.0), Some(1.2));
+        assert_eq!(Control::Quieter.volume(1.0), Some(0.83));
+        assert_eq!(Control::Faster.volume(1.0), None);
+    }
+
+    #[test]
+    fn volume_bounds() {
+        assert_eq!(Control::Louder.volume(1.8), Some(MAX_VOLUME));
+        assert_eq!(Control::Quieter.volume(0.1), Some(MIN_VOLUME));
+        assert_eq!(Control::Quieter.volume(MIN_VOLUME), Some(MIN_VOLUME));
+    }
+}
@@ -0,0 +1,46 @@
+use regex::Regex;
+
+// Corrections that don't say what was meant
+const VAGUE: [&str; 5] = [
+    "that",
+    "this",
+    "it",
+    "something else",
+    "something different",
+];
+
+/**
+ * Whether the user says that they were misunderstood without saying what they meant,
+ * e.g. "That's not what I said" or "You misheard me". When they say what they meant,
+ * like "No, I said Berlin", the model can take it from there.
+ **/
+pub fn misheard(text: &str) -> bool {
+    let complaint = Regex::new(
+        r"(?i)(^\W*no\b\W+i\s+(?:said|meant)\b|\b(?:that'?s not what i said|that is not what i said|i didn'?t say that|you misheard|you misunderstood|you got me wrong)\b)",
+    )
+    .unwrap();
+    if !complaint.is_match(text) {
+        return false;
+    }
+
+    let meant = Regex::new(r"(?i)\bi\s+(?:said|meant)\b(.*)$").unwrap();
+    let meant = (meant.captures(text).map(|captures| captures[1].to_string())).unwrap_or_default();
+    let meant = meant
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    meant.is_empty() || VAGUE.contains(&meant.as_str())
+}
+
+/**
+ * Whether two transcriptions say the same, ignoring case and punctuation
+ **/
+pub fn same_text(a: &str, b: &str) -> bool {
+    let simplify = |text: &str| {
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    simplify(a) == simplify(b)
+}
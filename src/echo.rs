@@ -0,0 +1,34 @@
+use std::collections::HashSet;
+
+use crate::hotword::normalize;
+
+// Fewer words are too likely to match by chance, e.g. "thank you"
+const MIN_WORDS: usize = 3;
+
+/**
+ * Pairs of neighbouring words, spelled the way they sound
+ **/
+fn word_pairs(text: &str) -> Vec<(String, String)> {
+    let words: Vec<String> = (text.split_whitespace().map(normalize))
+        .filter(|word| !word.is_empty())
+        .collect();
+    (words.windows(2))
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect()
+}
+
+/**
+ * How much of what was heard the bot said itself, from 0 to 1. The microphone
+ * often only picks up a part of the answer and Whisper spells some words differently,
+ * so it is the share of neighbouring word pairs heard that are also in what was spoken.
+ **/
+pub fn echo_similarity(heard: &str, spoken: &str) -> f32 {
+    let heard = word_pairs(heard);
+    if heard.len() + 1 < MIN_WORDS {
+        return 0.0;
+    }
+
+    let spoken: HashSet<(String, String)> = word_pairs(spoken).into_iter().collect();
+    let matching = heard.iter().filter(|pair| spoken.contains(pair)).count();
+    matching as f32 / heard.len() as f32
+}
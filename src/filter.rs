@@ -0,0 +1,42 @@
+use regex::{Regex, RegexBuilder};
+
+/**
+ * Replaces blacklisted words, e.g. to keep the bot family friendly on stream.
+ * Words are only matched as a whole and case doesn't matter.
+ **/
+pub struct WordFilter {
+    regex: Option<Regex>,
+    replacement: String,
+}
+
+impl WordFilter {
+    pub fn new(words: &[String], replacement: &str) -> Self {
+        let words: Vec<String> = words
+            .iter()
+            .map(|word| word.trim())
+            .filter(|word| !word.is_empty())
+            .map(regex::escape)
+            .collect();
+
+        let regex = (!words.is_empty()).then(|| {
+            RegexBuilder::new(&format!(r"\b(?:{})\b", words.join("|")))
+                .case_insensitive(true)
+                .build()
+                .expect("Invalid blacklist")
+        });
+
+        Self {
+            regex,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    pub fn censor(&self, text: &str) -> String {
+        match &self.regex {
+            Some(regex) => regex
+                .replace_all(text, regex::NoExpand(&self.replacement))
+                .to_string(),
+            None => text.to_string(),
+        }
+    }
+}
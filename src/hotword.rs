@@ -0,0 +1,66 @@
+/**
+ * Spell a word the way it sounds, so different spellings Whisper might come up
+ * with for the same word end up the same, e.g. "Kat" and "cat" or "fone" and "phone"
+ **/
+pub fn normalize(word: &str) -> String {
+    let word = word.to_lowercase().replace("ph", "f").replace("ck", "k");
+
+    let mut normalized = String::new();
+    for c in word.chars().filter(|c| c.is_alphanumeric()) {
+        let c = match c {
+            'c' | 'q' => 'k',
+            'z' => 's',
+            'y' => 'i',
+            c => c,
+        };
+        // Double letters sound like single ones
+        if !normalized.ends_with(c) {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+/**
+ * How many letters have to be inserted, removed or replaced to turn one word into the other
+ **/
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/**
+ * Whether the hotword was said, even if Whisper spelled it a bit differently.
+ * A hotword can consist of multiple words, max_distance is the number of letters
+ * that may be different after the spelling has been normalized. Without it one
+ * letter in four may differ, so short words like "cat" don't match "hat".
+ **/
+pub fn contains_hotword(text: &str, hotword: &str, max_distance: Option<usize>) -> bool {
+    let hotword_len = hotword.split_whitespace().count().max(1);
+    let hotword = normalize(hotword);
+    let max_distance = max_distance.unwrap_or(hotword.chars().count() / 4);
+
+    let words: Vec<String> = text.split_whitespace().map(normalize).collect();
+    if words.is_empty() {
+        return false;
+    }
+
+    words
+        .windows(hotword_len.min(words.len()))
+        .any(|window| levenshtein(&window.concat(), &hotword) <= max_distance)
+}
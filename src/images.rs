@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ollama_rs::generation::images::Image;
+use serde::{Deserialize, Serialize};
+
+const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "gif"];
+
+/**
+ * Image related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct ImageConfig {
+    #[serde(default)]
+    pub image_folder: Option<String>, // Images saved here are shown to the model with the next thing you say, needs a vision model
+}
+
+/**
+ * A folder that is watched for new images, e.g. where screenshots or webcam pictures are saved to
+ **/
+pub struct ImageFolder {
+    path: PathBuf,
+    seen: HashMap<PathBuf, SystemTime>, // When the images were changed the last time they were looked at
+}
+
+impl ImageFolder {
+    /**
+     * Images that are already in the folder are ignored
+     **/
+    pub fn new(path: &str) -> Self {
+        let mut folder = Self {
+            path: PathBuf::from(path),
+            seen: HashMap::new(),
+        };
+        folder.changed();
+        folder
+    }
+
+    /**
+     * Images that were added or changed since the last time
+     **/
+    fn changed(&mut self) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(&self.path) else {
+            eprintln!("Failed to read the image folder {}", self.path.display());
+            return Vec::new();
+        };
+
+        let mut changed = Vec::new();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let is_image = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            let Ok(modified) = std::fs::metadata(&path).and_then(|meta| meta.modified()) else {
+                continue;
+            };
+
+            if is_image && self.seen.insert(path.clone(), modified) != Some(modified) {
+                changed.push(path);
+            }
+        }
+        changed
+    }
+
+    /**
+     * All images that were added or changed since the last time
+     **/
+    pub fn new_images(&mut self) -> Vec<Image> {
+        let mut images = Vec::new();
+        for path in self.changed() {
+            match std::fs::read(&path) {
+                Ok(bytes) => {
+                    println!("Showing image {}", path.display());
+                    images.push(Image::from_base64(STANDARD.encode(bytes)));
+                }
+                Err(err) => eprintln!("Failed to read image {}: {}", path.display(), err),
+            }
+        }
+        images
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::BufRead;
+use std::sync::mpsc::{Receiver, channel};
+use std::time::Duration;
+
+// Lines of a pasted text arrive this close after each other
+const PASTE_GAP: Duration = Duration::from_millis(50);
+
+/**
+ * Lines typed or pasted into the terminal while the bot listens, for things that are
+ * hopeless to dictate like links or code. They are read on their own thread, so the
+ * microphone keeps working meanwhile.
+ **/
+pub struct Keyboard {
+    lines: Receiver<String>,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { lines }
+    }
+
+    /**
+     * What was typed since the last call, lines that arrive together like a pasted
+     * text are one message. None if nothing was typed.
+     **/
+    pub fn typed(&self) -> Option<String> {
+        let mut lines = vec![self.lines.try_recv().ok()?];
+        while let Ok(line) = self.lines.recv_timeout(PASTE_GAP) {
+            lines.push(line);
+        }
+
+        // Keep the indentation, it matters in code
+        let text = lines.join("\n");
+        let text = text.trim_end().trim_start_matches(['\r', '\n']);
+        (!text.is_empty()).then(|| text.to_string())
+    }
+}
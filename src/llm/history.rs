@@ -0,0 +1,271 @@
+use std::error::Error;
+
+use ollama_rs::generation::chat::{ChatMessage, MessageRole};
+use serde::{Deserialize, Serialize};
+
+/**
+ * Conversation history related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryConfig {
+    #[serde(default)]
+    pub max_turns: Option<usize>, // Only this many of the last things you said are remembered, with their answers
+    #[serde(default)]
+    pub max_history_tokens: Option<usize>, // Forget the oldest turns once the conversation gets longer than this, e.g. 3000 for a 4096 context
+    #[serde(default)]
+    pub summarize_history: bool, // Let the LLM summarize forgotten turns so important things are still remembered
+    #[serde(default)]
+    pub history_file: Option<String>, // Remember the conversation across restarts by saving it to this file, e.g. "history.json"
+    #[serde(default)]
+    pub fresh_start: bool, // Ignore the saved conversation and start over, same as running with --fresh
+    #[serde(default = "default_max_log_turns")]
+    pub max_log_turns: usize, // The history file also keeps this many turns for the history tool to search, 0 to keep none
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_turns: None,
+            max_history_tokens: None,
+            summarize_history: false,
+            history_file: None,
+            fresh_start: false,
+            max_log_turns: default_max_log_turns(),
+        }
+    }
+}
+
+fn default_max_log_turns() -> usize {
+    1000
+}
+
+/**
+ * The conversation as it is saved, without the system prompt so changes to it are used
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct SavedHistory {
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub log: Vec<LoggedTurn>,
+}
+
+impl SavedHistory {
+    /**
+     * Returns an empty history if the file doesn't exist yet
+     **/
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/**
+ * Something the user said and the answer with the time, unlike the messages
+ * these are never trimmed or summarized, so earlier conversations can be searched
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoggedTurn {
+    pub time: String,
+    pub user: String,
+    pub assistant: String,
+}
+
+pub const SUMMARY_PROMPT: &str = "You keep the memory of a conversation between a user and an assistant. \
+Summarize the conversation below in a few short sentences. Keep everything the user told about themselves, \
+their wishes and anything that was agreed on, leave out small talk. Only answer with the summary.";
+
+/**
+ * Rough number of tokens of a message, most models use about one token per four characters
+ **/
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    let tool_calls: usize = message
+        .tool_calls
+        .iter()
+        .map(|call| call.function.arguments.to_string().len())
+        .sum();
+    (message.content.chars().count() + tool_calls) / 4 + 4
+}
+
+/**
+ * Drop the oldest turns until the history fits the limits and return them. A turn is something
+ * the user said together with everything the LLM and tools answered. Everything before the first
+ * turn, like the system prompt, is always kept and so is the latest turn.
+ **/
+pub fn trim_history(history: &mut Vec<ChatMessage>, cfg: &HistoryConfig) -> Vec<ChatMessage> {
+    let starts: Vec<usize> = history
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.role == MessageRole::User)
+        .map(|(i, _)| i)
+        .collect();
+    if starts.len() < 2 {
+        return Vec::new();
+    }
+
+    let mut tokens: usize = history.iter().map(estimate_tokens).sum();
+    let mut turns = starts.len();
+    let mut end = starts[0];
+
+    for next in &starts[1..] {
+        let too_many = cfg.max_turns.is_some_and(|max| turns > max);
+        let too_long = cfg.max_history_tokens.is_some_and(|max| tokens > max);
+        if !too_many && !too_long {
+            break;
+        }
+
+        tokens -= history[end..*next]
+            .iter()
+            .map(estimate_tokens)
+            .sum::<usize>();
+        turns -= 1;
+        end = *next;
+    }
+
+    history.drain(starts[0]..end).collect()
+}
+
+/**
+ * Write messages as a plain text conversation, so they can be summarized
+ **/
+pub fn transcript(summary: Option<&str>, messages: &[ChatMessage]) -> String {
+    let mut text = String::new();
+    if let Some(summary) = summary {
+        text.push_str(&format!("Summary of what was said before: {}\n\n", summary));
+    }
+
+    for message in messages {
+        let speaker = match message.role {
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+            MessageRole::Tool => "Tool",
+            MessageRole::System => continue,
+        };
+        if !message.content.trim().is_empty() {
+            text.push_str(&format!("{}: {}\n", speaker, message.content.trim()));
+        }
+    }
+    text
+}
+
+/**
+ * The message that keeps the summary in the conversation
+ **/
+pub fn summary_message(summary: &str) -> ChatMessage {
+    ChatMessage::system(format!("Memory of the earlier conversation: {}", summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use ollama_rs::generation::tools::{ToolCall, ToolCallFunction};
+
+    use super::*;
+
+    fn contents(messages: &[ChatMessage]) -> Vec<&str> {
+        messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect()
+    }
+
+    /**
+     * A turn in which the LLM asked for the weather before answering
+     **/
+    fn tool_turn(user: &str) -> Vec<ChatMessage> {
+        let mut call = ChatMessage::assistant(String::new());
+        call.tool_calls = vec![ToolCall {
+            function: ToolCallFunction {
+                name: "weather".to_string(),
+                arguments: serde_json::json!({ "location": "Vienna" }),
+            },
+        }];
+        vec![
+            ChatMessage::user(user.to_string()),
+            call,
+            ChatMessage::tool("Sunny, 24°C".to_string()),
+            ChatMessage::assistant("It is sunny".to_string()),
+        ]
+    }
+
+    #[test]
+    fn keeps_system_prompt_and_summary() {
+        let mut history = vec![
+            ChatMessage::system("You are a friend".to_string()),
+            summary_message("The user likes tea"),
+            ChatMessage::user("Hi".to_string()),
+            ChatMessage::assistant("Hello".to_string()),
+            ChatMessage::user("How are you?".to_string()),
+            ChatMessage::assistant("Good".to_string()),
+            ChatMessage::user("Bye".to_string()),
+        ];
+        let cfg = HistoryConfig {
+            max_turns: Some(1),
+            ..Default::default()
+        };
+
+        let dropped = trim_history(&mut history, &cfg);
+        assert_eq!(contents(&dropped), ["Hi", "Hello", "How are you?", "Good"]);
+        assert_eq!(
+            contents(&history),
+            [
+                "You are a friend",
+                "Memory of the earlier conversation: The user likes tea",
+                "Bye"
+            ]
+        );
+        assert_eq!(history[1].role, MessageRole::System);
+    }
+
+    #[test]
+    fn keeps_tool_calls_with_their_results() {
+        let mut history = vec![ChatMessage::system("You are a friend".to_string())];
+        history.extend(tool_turn(&"Is it warm outside? ".repeat(20)));
+        history.extend(tool_turn("And tomorrow?"));
+        history.push(ChatMessage::user("Thanks".to_string()));
+
+        // Only dropping the long first turn makes the history short enough
+        let cfg = HistoryConfig {
+            max_history_tokens: Some(60),
+            ..Default::default()
+        };
+        let dropped = trim_history(&mut history, &cfg);
+
+        assert_eq!(dropped.len(), 4);
+        assert_eq!(dropped[0].role, MessageRole::User);
+        assert_eq!(dropped[2].role, MessageRole::Tool);
+        assert_eq!(history.len(), 6);
+        assert_eq!(history[1].content, "And tomorrow?");
+        assert_eq!(history[2].tool_calls.len(), 1);
+        assert_eq!(history[3].role, MessageRole::Tool);
+    }
+
+    #[test]
+    fn keeps_latest_turn() {
+        let mut history = vec![ChatMessage::system("You are a friend".to_string())];
+        history.extend(tool_turn(&"Is it warm outside? ".repeat(20)));
+        let cfg = HistoryConfig {
+            max_turns: Some(0),
+            max_history_tokens: Some(1),
+            ..Default::default()
+        };
+
+        assert!(trim_history(&mut history, &cfg).is_empty());
+        assert_eq!(history.len(), 5);
+    }
+
+    #[test]
+    fn summary_is_a_system_message() {
+        let message = summary_message("The user likes tea");
+        assert_eq!(message.role, MessageRole::System);
+        assert!(message.content.ends_with("The user likes tea"));
+    }
+}
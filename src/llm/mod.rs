@@ -0,0 +1,620 @@
+pub mod history;
+pub mod ollama;
+pub mod openai;
+pub mod template;
+pub mod tools;
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Local;
+use ollama_rs::generation::chat::ChatMessage;
+use ollama_rs::generation::images::Image;
+use ollama_rs::generation::tools::{Tool, ToolCall, ToolInfo};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::llm::history::{
+    HistoryConfig, LoggedTurn, SUMMARY_PROMPT, SavedHistory, summary_message, transcript,
+    trim_history,
+};
+use crate::llm::ollama::{OllamaBackend, OllamaConfig};
+use crate::llm::openai::{OpenAiBackend, OpenAiConfig};
+use crate::llm::template::fill_template;
+use crate::llm::tools::{Tools, confirmation_question, confirmed};
+use crate::tools::plugin::Plugin;
+
+const PICK_PROMPT: &str = "Speech recognition wasn't sure what the user said last and offers several guesses. \
+Pick the guess that makes the most sense in the conversation. Only answer with its number.";
+
+// How many of the last messages are shown when picking a guess
+const PICK_CONTEXT: usize = 6;
+
+/**
+ * How many tokens the model read and wrote
+ **/
+#[derive(Serialize, Default, Clone, Copy, Debug)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub response_tokens: u64,
+}
+
+/**
+ * A piece of the response, tool calls can come with any piece
+ **/
+pub struct Delta {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Option<Usage>, // Only known at the end, if the backend reports it
+    pub truncated: bool,      // The answer was cut off because it got too long
+    pub done: bool,           // The last piece
+}
+
+pub type Chunk = Result<Delta, Box<dyn Error + Send + Sync>>;
+
+/**
+ * Something that runs the LLM. The response arrives piece by piece on the
+ * returned channel while it is being generated, the last piece is marked as done.
+ **/
+pub trait LlmBackend: Send + Sync {
+    fn stream_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolInfo>,
+        options: &GenerationOptions,
+    ) -> UnboundedReceiver<Chunk>;
+
+    /**
+     * Load the model in the background, so the first answer isn't delayed by it
+     **/
+    fn warm_up(&self, _model: &str) {}
+}
+
+/**
+ * The available LLM backends, selected with `llm` in the config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmKind {
+    #[default]
+    Ollama,
+    Openai, // Any server with an OpenAI compatible API
+}
+
+/**
+ * How the model generates its answers, everything not set is left to the model
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct GenerationOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>, // Higher is more creative, lower more focused, e.g. 0.7
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>, // Only pick from the most likely words that together have this probability, e.g. 0.9
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>, // Size of the context in tokens, only used by Ollama
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>, // The same seed and conversation give the same answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>, // Maximum number of tokens of an answer, thoughts of thinking models count too
+}
+
+/**
+ * LLM related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct LlmConfig {
+    #[serde(default)]
+    pub llm: LlmKind, // Which backend runs the model: "ollama" or "openai"
+    #[serde(default)]
+    pub generation: GenerationOptions, // e.g. {"temperature": 0.7, "num_ctx": 8192}
+
+    #[serde(flatten)]
+    pub ollama: OllamaConfig,
+    #[serde(flatten)]
+    pub openai: OpenAiConfig,
+}
+
+pub fn create_llm(cfg: &LlmConfig) -> Box<dyn LlmBackend> {
+    match cfg.llm {
+        LlmKind::Ollama => Box::new(OllamaBackend::new(&cfg.ollama)),
+        LlmKind::Openai => Box::new(OpenAiBackend::new(&cfg.openai)),
+    }
+}
+
+/**
+ * A conversation with the LLM, it remembers everything that was said and lets the LLM use tools
+ **/
+pub struct Chat {
+    backend: Box<dyn LlmBackend>,
+    model: String,
+    fallback: Option<String>,
+    timeout: Option<Duration>,
+    retries: u32,
+    options: GenerationOptions,
+    system: String,
+    variables: HashMap<String, String>,
+    context: Option<String>,
+    history: Vec<ChatMessage>,
+    limits: HistoryConfig,
+    summary: Option<String>,
+    file: Option<String>,
+    log: Vec<LoggedTurn>,
+    usage: Usage,
+    truncated: bool,
+    tools: Tools,
+    pending: Option<(ToolCall, String)>, // A tool call waiting for a yes and what the user asked for
+}
+
+impl Chat {
+    pub fn new(backend: Box<dyn LlmBackend>, model: String, system: String) -> Self {
+        Self {
+            backend,
+            model,
+            fallback: None,
+            timeout: None,
+            retries: 0,
+            options: GenerationOptions::default(),
+            history: vec![ChatMessage::system(fill_template(&system, &HashMap::new()))],
+            system,
+            variables: HashMap::new(),
+            context: None,
+            limits: HistoryConfig::default(),
+            summary: None,
+            file: None,
+            log: Vec::new(),
+            usage: Usage::default(),
+            truncated: false,
+            tools: Tools::default(),
+            pending: None,
+        }
+    }
+
+    /**
+     * Model that answers when the normal one fails or doesn't answer within the timeout
+     **/
+    pub fn fallback(mut self, model: Option<String>, timeout: Option<Duration>) -> Self {
+        self.fallback = model;
+        self.timeout = timeout;
+        self
+    }
+
+    /**
+     * How often to try again when no model answered, waiting 1, 2, 4, ... seconds in between
+     **/
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /**
+     * Values for the placeholders in the system prompt, e.g. {user_name}
+     **/
+    pub fn variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables = variables;
+        self.history[0] = self.system_message();
+        self
+    }
+
+    pub fn limit_history(mut self, limits: HistoryConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /**
+     * Continue the conversation saved in the file, unless starting fresh, and save it there after every answer.
+     * Starting fresh keeps the log of earlier conversations.
+     **/
+    pub fn persist(mut self, path: String, fresh: bool) -> Self {
+        match SavedHistory::load(&path) {
+            Ok(saved) => {
+                if !fresh {
+                    if let Some(summary) = &saved.summary {
+                        self.history.push(summary_message(summary));
+                    }
+                    self.summary = saved.summary;
+                    self.history.extend(saved.messages);
+                }
+                self.log = saved.log;
+            }
+            Err(err) => println!(
+                "Error failed to load the conversation from {}: {}",
+                path, err
+            ),
+        }
+        self.file = Some(path);
+        self
+    }
+
+    pub fn add_tool<T: Tool + 'static>(mut self, tool: T) -> Self {
+        self.tools.add(tool);
+        self
+    }
+
+    pub fn add_plugin(mut self, plugin: Plugin) -> Self {
+        self.tools.add_plugin(plugin);
+        self
+    }
+
+    /**
+     * Ask the user before these tools are used, e.g. for things that can't be undone
+     **/
+    pub fn confirm_tools(mut self, names: &[String]) -> Self {
+        for name in names {
+            self.tools.confirm(name);
+        }
+        self
+    }
+
+    /**
+     * Append every tool call to the file, so the user can check what the model did
+     **/
+    pub fn log_tools(mut self, path: Option<String>) -> Self {
+        self.tools.log_to(path);
+        self
+    }
+
+    /**
+     * Where tools put their images, they are shown to the model with the result of the tool
+     **/
+    pub fn attachments(&self) -> Arc<Mutex<Vec<Image>>> {
+        self.tools.attachments()
+    }
+
+    pub fn retain_tools(mut self, allowed: impl Fn(&str) -> bool) -> Self {
+        self.tools.retain(allowed);
+        self
+    }
+
+    pub fn warm_up(&self) {
+        self.backend.warm_up(&self.model);
+    }
+
+    /**
+     * Tokens used by the last message, the prompt is the whole conversation the model
+     * read the last time and the response everything it wrote, including tool calls
+     **/
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /**
+     * If the last answer was cut off because it reached num_predict
+     **/
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /**
+     * Added to the system prompt for the next messages, e.g. what is remembered about the user
+     **/
+    pub fn set_context(&mut self, context: Option<String>) {
+        self.context = context;
+    }
+
+    /**
+     * Continue the conversation with another system prompt, model and options
+     **/
+    pub fn switch(&mut self, system: String, model: String, options: GenerationOptions) {
+        self.system = system;
+        self.history[0] = self.system_message();
+        self.model = model;
+        self.options = options;
+    }
+
+    /**
+     * Send a message and return the answer, on_token is called with every piece of the
+     * answer as soon as it arrives. When the LLM wants to use tools they are called and
+     * their results sent back until it answers. Only when it answered the message and
+     * answer are added to the history, so dropping the future cancels everything.
+     * The oldest turns are forgotten first when the history gets too long, if enabled
+     * they are summarized, so the LLM still remembers what was important.
+     * When the model fails before it started answering the fallback model is asked instead
+     * and if that fails too, both are asked again after waiting a bit longer every time.
+     * Tools that need confirmation are not called right away, instead the user is asked
+     * and they are only called if the next message is a yes.
+     **/
+    pub async fn send(
+        &mut self,
+        message: ChatMessage,
+        mut on_token: impl FnMut(&str),
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        // The date and time in the system prompt have to be up to date
+        self.history[0] = self.system_message();
+        self.make_room(&message).await;
+        self.usage = Usage::default();
+        self.truncated = false;
+
+        let answered = Cell::new(false);
+        let mut on_token = |token: &str| {
+            answered.set(answered.get() || !token.is_empty());
+            on_token(token);
+        };
+
+        // The last answer asked if a tool should really be used
+        let mut start = vec![message.clone()];
+        if let Some((call, request)) = self.pending.take() {
+            match confirmed(&message.content) {
+                Some(true) => {
+                    let arguments = call.function.arguments.clone();
+                    let result = self
+                        .tools
+                        .call(&call.function.name, arguments, &request)
+                        .await;
+                    let mut message = ChatMessage::assistant(String::new());
+                    message.tool_calls = vec![call];
+                    start.push(message);
+                    start.push(result);
+                }
+                Some(false) => println!("Tool {} cancelled", call.function.name),
+                None => println!("Tool {} not confirmed", call.function.name),
+            }
+        }
+
+        let mut models = vec![self.model.clone()];
+        models.extend(self.fallback.clone());
+
+        let mut delay = Duration::from_secs(1);
+        // Tools that already ran stay in the turn, so another attempt doesn't run them again
+        let mut turn = start;
+        let mut response = Err("No model was asked".into());
+        'attempts: for attempt in 0..=self.retries {
+            if attempt > 0 {
+                println!("Trying again in {} seconds", delay.as_secs());
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+
+            for model in &models {
+                response = self.respond(model, &mut turn, &mut on_token).await;
+                match &response {
+                    Ok(_) => break 'attempts,
+                    // Part of the answer might already be spoken
+                    Err(_) if answered.get() => break 'attempts,
+                    Err(err) => println!("Model {} failed: {}", model, err),
+                }
+            }
+        }
+
+        if let Ok(answer) = &response {
+            self.history.extend(turn);
+            if self.file.is_some() {
+                self.log.push(LoggedTurn {
+                    time: Local::now().to_rfc3339(),
+                    user: message.content.clone(),
+                    assistant: answer.clone(),
+                });
+                // The oldest turns go first, so the file that is saved after every answer stays small
+                let max = self.limits.max_log_turns;
+                if self.log.len() > max {
+                    self.log.drain(..self.log.len() - max);
+                }
+            }
+            self.save();
+        }
+        response
+    }
+
+    /**
+     * Forget the oldest turns if the history would get too long with the message
+     **/
+    async fn make_room(&mut self, message: &ChatMessage) {
+        let mut history = self.history.clone();
+        history.push(message.clone());
+        let dropped = trim_history(&mut history, &self.limits);
+        if dropped.is_empty() {
+            return;
+        }
+        history.pop();
+
+        if self.limits.summarize_history {
+            match self.summarize(&dropped).await {
+                Ok(summary) => {
+                    if self.summary.is_some() {
+                        history[1] = summary_message(&summary);
+                    } else {
+                        history.insert(1, summary_message(&summary));
+                    }
+                    self.summary = Some(summary);
+                }
+                Err(err) => println!("Error failed to summarize the conversation: {}", err),
+            }
+        }
+        self.history = history;
+    }
+
+    fn system_message(&self) -> ChatMessage {
+        let system = fill_template(&self.system, &self.variables);
+        match &self.context {
+            Some(context) => ChatMessage::system(format!("{}\n\n{}", system, context)),
+            None => ChatMessage::system(system),
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+
+        let start = if self.summary.is_some() { 2 } else { 1 };
+        let saved = SavedHistory {
+            summary: self.summary.clone(),
+            messages: self.history[start..].to_vec(),
+            log: self.log.clone(),
+        };
+        if let Err(err) = saved.save(path) {
+            println!("Error failed to save the conversation to {}: {}", path, err);
+        }
+    }
+
+    /**
+     * Merge the forgotten messages into the summary, which is kept right after the system prompt
+     **/
+    async fn summarize(
+        &self,
+        dropped: &[ChatMessage],
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let messages = vec![
+            ChatMessage::system(SUMMARY_PROMPT.to_string()),
+            ChatMessage::user(transcript(self.summary.as_deref(), dropped)),
+        ];
+        self.ask(messages).await
+    }
+
+    /**
+     * Let the model pick the guess of what the user said that fits the conversation best,
+     * returns its index. The conversation isn't changed by this.
+     **/
+    pub async fn pick(&self, guesses: &[String]) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let start = self.history.len().saturating_sub(PICK_CONTEXT).max(1);
+        let mut text = transcript(self.summary.as_deref(), &self.history[start..]);
+        text.push_str("\nWhat the user said next, guesses:\n");
+        for (index, guess) in guesses.iter().enumerate() {
+            text.push_str(&format!("{}. {}\n", index + 1, guess));
+        }
+
+        let messages = vec![
+            ChatMessage::system(PICK_PROMPT.to_string()),
+            ChatMessage::user(text),
+        ];
+        let answer = self.ask(messages).await?;
+        let number: String = (answer.chars())
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        match number.parse::<usize>() {
+            Ok(number) if (1..=guesses.len()).contains(&number) => Ok(number - 1),
+            _ => Err(format!("The model didn't pick a guess: {}", answer).into()),
+        }
+    }
+
+    /**
+     * Ask the model something on the side without tools, returns the whole answer
+     **/
+    async fn ask(
+        &self,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut receiver =
+            self.backend
+                .stream_chat(&self.model, messages, Vec::new(), &self.options);
+        let mut answer = String::new();
+        while let Some(chunk) = receive(&mut receiver, self.timeout).await {
+            let chunk = chunk?;
+            answer.push_str(&chunk.content);
+            if chunk.done {
+                break;
+            }
+        }
+
+        // Thinking models put their thoughts before the answer
+        if let Some(end) = answer.find("</think>") {
+            answer = answer[end + 8..].to_string();
+        }
+        Ok(answer.trim().to_string())
+    }
+
+    /**
+     * Let the model answer, everything said in this turn is added to `turn`
+     **/
+    async fn respond(
+        &mut self,
+        model: &str,
+        turn: &mut Vec<ChatMessage>,
+        on_token: &mut impl FnMut(&str),
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        loop {
+            let mut receiver = self.backend.stream_chat(
+                model,
+                [self.history.as_slice(), turn.as_slice()].concat(),
+                self.tools.infos().to_vec(),
+                &self.options,
+            );
+
+            let mut content = String::new();
+            let mut tool_calls = Vec::new();
+            while let Some(chunk) = receive(&mut receiver, self.timeout).await {
+                let chunk = chunk?;
+                on_token(&chunk.content);
+                content.push_str(&chunk.content);
+                tool_calls.extend(chunk.tool_calls);
+                if let Some(usage) = chunk.usage {
+                    self.usage.prompt_tokens = usage.prompt_tokens;
+                    self.usage.response_tokens += usage.response_tokens;
+                }
+                self.truncated |= chunk.truncated;
+                if chunk.done {
+                    break;
+                }
+            }
+
+            let mut message = ChatMessage::assistant(content.clone());
+            message.tool_calls = tool_calls.clone();
+            turn.push(message);
+
+            if tool_calls.is_empty() {
+                return Ok(content);
+            }
+
+            let request = turn[0].content.clone();
+            let mut pending = None;
+            for call in tool_calls {
+                let result = if !self.tools.needs_confirmation(&call.function.name) {
+                    self.tools
+                        .call(&call.function.name, call.function.arguments, &request)
+                        .await
+                } else if pending.is_none() {
+                    pending = Some(call.clone());
+                    ChatMessage::tool(
+                        "The user is asked to confirm this, it is only done if they say yes"
+                            .to_string(),
+                    )
+                } else {
+                    ChatMessage::tool(
+                        "Only one thing can be confirmed at a time, ask again once the user answered"
+                            .to_string(),
+                    )
+                };
+                turn.push(result);
+            }
+
+            // Stop here and let the user decide, their next message is the answer
+            if let Some(call) = pending {
+                let question = confirmation_question(&call);
+                on_token(&format!(" {}", question));
+                turn.push(ChatMessage::assistant(question.clone()));
+                self.pending = Some((call, request));
+                return Ok(question);
+            }
+        }
+    }
+}
+
+/**
+ * Wait for the next piece of the response, a timeout is returned as error
+ **/
+async fn receive(
+    receiver: &mut UnboundedReceiver<Chunk>,
+    timeout: Option<Duration>,
+) -> Option<Chunk> {
+    let Some(timeout) = timeout else {
+        return receiver.recv().await;
+    };
+
+    match tokio::time::timeout(timeout, receiver.recv()).await {
+        Ok(chunk) => chunk,
+        Err(_) => Some(Err(format!(
+            "No answer within {} seconds",
+            timeout.as_secs_f32()
+        )
+        .into())),
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::Instant;
+
+use ollama_rs::Ollama;
+use ollama_rs::generation::chat::ChatMessage;
+use ollama_rs::generation::chat::request::ChatMessageRequest;
+use ollama_rs::generation::parameters::{KeepAlive, TimeUnit};
+use ollama_rs::generation::tools::ToolInfo;
+use ollama_rs::models::ModelOptions;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+use tokio_stream::StreamExt;
+
+use crate::llm::{Chunk, Delta, GenerationOptions, LlmBackend, Usage};
+
+/**
+ * Ollama related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct OllamaConfig {
+    #[serde(default = "default_ollama_host")]
+    pub ollama_host: String, // Machine Ollama is running on, e.g. "http://192.168.1.10"
+    #[serde(default = "default_ollama_port")]
+    pub ollama_port: u16, // Port of the Ollama server
+    #[serde(default)]
+    pub ollama_keep_alive: Option<String>, // How long the model stays loaded after an answer, e.g. "30m", "-1m" is forever
+}
+
+impl OllamaConfig {
+    /**
+     * Where the Ollama API is, e.g. "http://localhost:11434"
+     **/
+    pub fn url(&self) -> String {
+        let host = if self.ollama_host.contains("://") {
+            self.ollama_host.clone()
+        } else {
+            format!("http://{}", self.ollama_host)
+        };
+        format!("{}:{}", host, self.ollama_port)
+    }
+}
+
+fn default_ollama_host() -> String {
+    "http://localhost".to_string()
+}
+
+fn default_ollama_port() -> u16 {
+    11434
+}
+
+/**
+ * The keep alive of the config, e.g. "30m", "2h", "90s" or "-1m" to keep the model loaded forever
+ **/
+fn parse_keep_alive(keep_alive: &str) -> Option<KeepAlive> {
+    let keep_alive = keep_alive.trim();
+    let (time, unit) = match keep_alive.char_indices().last() {
+        Some((end, 's')) => (&keep_alive[..end], TimeUnit::Seconds),
+        Some((end, 'm')) => (&keep_alive[..end], TimeUnit::Minutes),
+        Some((end, 'h')) => (&keep_alive[..end], TimeUnit::Hours),
+        _ => (keep_alive, TimeUnit::Seconds),
+    };
+
+    match time.trim().parse::<i64>() {
+        Ok(time) if time < 0 => Some(KeepAlive::Indefinitely),
+        Ok(0) => Some(KeepAlive::UnloadOnCompletion),
+        Ok(time) => Some(KeepAlive::Until {
+            time: time as u64,
+            unit,
+        }),
+        Err(_) => {
+            eprintln!(
+                "Ignoring ollama_keep_alive {:?}, use e.g. \"30m\"",
+                keep_alive
+            );
+            None
+        }
+    }
+}
+
+fn model_options(options: &GenerationOptions) -> ModelOptions {
+    let mut model_options = ModelOptions::default();
+    if let Some(temperature) = options.temperature {
+        model_options = model_options.temperature(temperature);
+    }
+    if let Some(top_p) = options.top_p {
+        model_options = model_options.top_p(top_p);
+    }
+    if let Some(num_ctx) = options.num_ctx {
+        model_options = model_options.num_ctx(num_ctx as u64);
+    }
+    if let Some(seed) = options.seed {
+        model_options = model_options.seed(seed as i32);
+    }
+    if let Some(num_predict) = options.num_predict {
+        model_options = model_options.num_predict(num_predict);
+    }
+    model_options
+}
+
+pub struct OllamaBackend {
+    ollama: Ollama,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl OllamaBackend {
+    pub fn new(cfg: &OllamaConfig) -> Self {
+        // By default, it will connect to localhost:11434
+        Self {
+            ollama: Ollama::try_new(cfg.url()).expect("invalid ollama_host"),
+            keep_alive: cfg.ollama_keep_alive.as_deref().and_then(parse_keep_alive),
+        }
+    }
+
+    fn request(&self, model: &str, messages: Vec<ChatMessage>) -> ChatMessageRequest {
+        let request = ChatMessageRequest::new(model.to_string(), messages);
+        match &self.keep_alive {
+            Some(keep_alive) => request.keep_alive(keep_alive.clone()),
+            None => request,
+        }
+    }
+}
+
+impl LlmBackend for OllamaBackend {
+    fn stream_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolInfo>,
+        options: &GenerationOptions,
+    ) -> UnboundedReceiver<Chunk> {
+        let request = self
+            .request(model, messages)
+            .tools(tools)
+            .options(model_options(options));
+        // Ollama doesn't tell ollama-rs why it stopped, so an answer is cut if it used every token it may
+        let num_predict = options.num_predict.filter(|max| *max > 0);
+
+        let ollama = self.ollama.clone();
+        let (sender, receiver) = unbounded_channel();
+        tokio::spawn(async move {
+            let mut stream = match ollama.send_chat_messages_stream(request).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    let _ = sender.send(Err(format!("Ollama: {}", err).into()));
+                    return;
+                }
+            };
+
+            while let Some(response) = stream.next().await {
+                let Ok(response) = response else {
+                    let _ = sender.send(Err("Ollama stopped answering".into()));
+                    return;
+                };
+
+                let done = response.done;
+                let delta = Delta {
+                    content: response.message.content,
+                    tool_calls: response.message.tool_calls,
+                    usage: response.final_data.as_ref().map(|data| Usage {
+                        prompt_tokens: data.prompt_eval_count,
+                        response_tokens: data.eval_count,
+                    }),
+                    truncated: response.final_data.is_some_and(|data| {
+                        num_predict.is_some_and(|max| data.eval_count >= max as u64)
+                    }),
+                    done,
+                };
+
+                // Nobody is listening anymore
+                if sender.send(Ok(delta)).is_err() || done {
+                    return;
+                }
+            }
+
+            // Errors like an unknown model come as a line ollama-rs can't read, the stream just ends
+            let _ = sender.send(Err("Ollama stopped before the answer was done".into()));
+        });
+        receiver
+    }
+
+    /**
+     * A chat without messages only loads the model
+     **/
+    fn warm_up(&self, model: &str) {
+        let request = self.request(model, Vec::new());
+        let ollama = self.ollama.clone();
+        let model = model.to_string();
+        tokio::spawn(async move {
+            let start = Instant::now();
+            match ollama.send_chat_messages(request).await {
+                Ok(_) => println!(
+                    "Loaded {} in {:.1} seconds",
+                    model,
+                    start.elapsed().as_secs_f32()
+                ),
+                Err(err) => eprintln!("Failed to load {}: {}", model, err),
+            }
+        });
+    }
+}
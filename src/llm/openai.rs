@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+
+use ollama_rs::generation::chat::{ChatMessage, MessageRole};
+use ollama_rs::generation::tools::{ToolCall, ToolCallFunction, ToolInfo};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use crate::llm::{Chunk, Delta, GenerationOptions, LlmBackend, Usage};
+
+/**
+ * Settings for OpenAI compatible servers, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct OpenAiConfig {
+    #[serde(default = "default_openai_url")]
+    pub openai_url: String, // Base URL of the API, e.g. "https://openrouter.ai/api/v1"
+    #[serde(default)]
+    pub openai_key: Option<String>, // API key, if not set the OPENAI_API_KEY environment variable is used
+}
+
+fn default_openai_url() -> String {
+    "http://localhost:8080/v1".to_string()
+}
+
+/**
+ * Talks to anything that speaks the OpenAI chat completions API, like the llama.cpp server, vLLM or OpenRouter
+ **/
+pub struct OpenAiBackend {
+    url: String,
+    key: Option<String>,
+}
+
+impl OpenAiBackend {
+    pub fn new(cfg: &OpenAiConfig) -> Self {
+        Self {
+            url: cfg.openai_url.trim_end_matches('/').to_string(),
+            key: cfg
+                .openai_key
+                .clone()
+                .or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+        }
+    }
+}
+
+impl LlmBackend for OpenAiBackend {
+    fn stream_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolInfo>,
+        options: &GenerationOptions,
+    ) -> UnboundedReceiver<Chunk> {
+        let url = format!("{}/chat/completions", self.url);
+        let key = self.key.clone();
+
+        let mut request = json!({
+            "model": model,
+            "messages": convert_messages(&messages),
+            "stream": true,
+            "stream_options": { "include_usage": true },
+            "temperature": options.temperature,
+            "top_p": options.top_p,
+            "seed": options.seed,
+            "max_tokens": options.num_predict,
+        });
+        // Options that aren't set are left out, not every server accepts null
+        if let Some(request) = request.as_object_mut() {
+            request.retain(|_, value| !value.is_null());
+        }
+        if !tools.is_empty() {
+            request["tools"] = tools
+                .iter()
+                .map(|tool| json!({ "type": "function", "function": tool.function }))
+                .collect();
+        }
+
+        let (sender, receiver) = unbounded_channel();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = read_stream(&url, key.as_deref(), &request, &sender) {
+                let _ = sender.send(Err(err));
+            }
+        });
+        receiver
+    }
+}
+
+/**
+ * The OpenAI API links the tool results to the calls with ids, they are
+ * made up here as the results always follow the calls in the same order.
+ * Only users can show images, so the images of a tool follow its result as a user message.
+ **/
+fn convert_messages(messages: &[ChatMessage]) -> Vec<Value> {
+    let mut pending = VecDeque::new();
+    let mut next_id = 0;
+
+    messages
+        .iter()
+        .flat_map(|message| {
+            let mut converted = json!({ "role": message.role, "content": message.content });
+            let mut images_message = None;
+
+            if let Some(images) = &message.images {
+                let mut content = vec![json!({ "type": "text", "text": message.content })];
+                for image in images {
+                    let url = format!(
+                        "data:{};base64,{}",
+                        mime_type(image.to_base64()),
+                        image.to_base64()
+                    );
+                    content.push(json!({ "type": "image_url", "image_url": { "url": url } }));
+                }
+                if message.role == MessageRole::Tool {
+                    content[0] = json!({ "type": "text", "text": "The images from the tool" });
+                    images_message = Some(json!({ "role": "user", "content": content }));
+                } else {
+                    converted["content"] = content.into();
+                }
+            }
+
+            if !message.tool_calls.is_empty() {
+                converted["tool_calls"] = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        let id = format!("call_{}", next_id);
+                        next_id += 1;
+                        pending.push_back(id.clone());
+                        json!({
+                            "id": id,
+                            "type": "function",
+                            "function": {
+                                "name": call.function.name,
+                                "arguments": call.function.arguments.to_string(),
+                            },
+                        })
+                    })
+                    .collect();
+            }
+
+            if message.role == MessageRole::Tool {
+                converted["tool_call_id"] = json!(pending.pop_front().unwrap_or_default());
+            }
+            [Some(converted), images_message].into_iter().flatten()
+        })
+        .collect()
+}
+
+/**
+ * Images are sent as data URLs, their type can be told by how they start
+ **/
+fn mime_type(base64: &str) -> &'static str {
+    if base64.starts_with("iVBOR") {
+        "image/png"
+    } else if base64.starts_with("R0lG") {
+        "image/gif"
+    } else if base64.starts_with("UklG") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/**
+ * The response is streamed as server sent events, tool calls arrive in pieces and are sent when it is done
+ **/
+fn read_stream(
+    url: &str,
+    key: Option<&str>,
+    request: &Value,
+    sender: &UnboundedSender<Chunk>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut post = ureq::post(url).set("Content-Type", "application/json");
+    if let Some(key) = key {
+        post = post.set("Authorization", &format!("Bearer {}", key));
+    }
+
+    let response = match post.send_string(&request.to_string()) {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            return Err(format!("Server returned {}: {}", code, body).into());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut calls: Vec<(String, String)> = Vec::new();
+    let mut usage = None;
+    let mut truncated = false;
+
+    for line in BufReader::new(response.into_reader()).lines() {
+        let line = line?;
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            continue;
+        };
+        if data == "[DONE]" {
+            break;
+        }
+
+        let value: Value = serde_json::from_str(data)?;
+        if let Some(error) = value.get("error") {
+            return Err(format!("Server: {}", error).into());
+        }
+
+        // Sent with the last piece or on its own at the end
+        if let (Some(prompt), Some(response)) = (
+            value["usage"]["prompt_tokens"].as_u64(),
+            value["usage"]["completion_tokens"].as_u64(),
+        ) {
+            usage = Some(Usage {
+                prompt_tokens: prompt,
+                response_tokens: response,
+            });
+        }
+
+        truncated |= value["choices"][0]["finish_reason"] == "length";
+        let delta = &value["choices"][0]["delta"];
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0) as usize;
+            if calls.len() <= index {
+                calls.resize(index + 1, Default::default());
+            }
+            if let Some(name) = call["function"]["name"].as_str() {
+                calls[index].0.push_str(name);
+            }
+            if let Some(arguments) = call["function"]["arguments"].as_str() {
+                calls[index].1.push_str(arguments);
+            }
+        }
+
+        let content = delta["content"].as_str().unwrap_or_default().to_string();
+        let delta = Delta {
+            content,
+            tool_calls: Vec::new(),
+            usage: None,
+            truncated: false,
+            done: false,
+        };
+
+        // Nobody is listening anymore
+        if sender.send(Ok(delta)).is_err() {
+            return Ok(());
+        }
+    }
+
+    let mut tool_calls = Vec::new();
+    for (name, arguments) in calls {
+        let arguments = if arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&arguments)?
+        };
+        tool_calls.push(ToolCall {
+            function: ToolCallFunction { name, arguments },
+        });
+    }
+
+    let _ = sender.send(Ok(Delta {
+        content: String::new(),
+        tool_calls,
+        usage,
+        truncated,
+        done: true,
+    }));
+    Ok(())
+}
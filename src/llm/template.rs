@@ -0,0 +1,24 @@
+use std::collections::HashMap;
+
+use chrono::Local;
+use regex::{Captures, Regex};
+
+/**
+ * Fill in placeholders like {date}, {time} or {user_name} in the system prompt.
+ * {date} and {time} are always known, placeholders that have no value are kept as they are.
+ **/
+pub fn fill_template(template: &str, variables: &HashMap<String, String>) -> String {
+    let now = Local::now();
+    let regex = Regex::new(r"\{([A-Za-z_]+)\}").unwrap();
+
+    regex
+        .replace_all(template, |caps: &Captures| match &caps[1] {
+            "date" => now.format("%A, %B %-d, %Y").to_string(),
+            "time" => now.format("%H:%M").to_string(),
+            name => variables
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| caps[0].to_string()),
+        })
+        .into_owned()
+}
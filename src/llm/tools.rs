@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::Local;
+use ollama_rs::generation::chat::ChatMessage;
+use ollama_rs::generation::images::Image;
+use ollama_rs::generation::tools::{Tool, ToolCall, ToolFunctionInfo, ToolInfo, ToolType};
+use schemars::r#gen::SchemaSettings;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::tools::plugin::Plugin;
+
+type ToolResult = Result<String, Box<dyn Error + Send + Sync>>;
+
+const YES: [&str; 10] = [
+    "yes", "yeah", "yep", "sure", "ok", "okay", "correct", "right", "ahead", "confirm",
+];
+const NO: [&str; 9] = [
+    "no", "nope", "don't", "dont", "not", "stop", "cancel", "wait", "never",
+];
+
+/**
+ * A tool that takes its parameters as JSON, so tools with different parameters can be stored together
+ **/
+trait JsonTool: Send + Sync {
+    fn call(&mut self, arguments: Value) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>>;
+}
+
+impl<T: Tool> JsonTool for T {
+    fn call(&mut self, arguments: Value) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>> {
+        Box::pin(async move {
+            let parameters = serde_json::from_value(arguments)?;
+            T::call(self, parameters).await
+        })
+    }
+}
+
+impl JsonTool for Plugin {
+    fn call(&mut self, arguments: Value) -> Pin<Box<dyn Future<Output = ToolResult> + Send + '_>> {
+        Box::pin(Plugin::call(self, arguments))
+    }
+}
+
+/**
+ * Describes the tool and its parameters to the LLM
+ **/
+fn tool_info<T: Tool>() -> ToolInfo {
+    let mut settings = SchemaSettings::draft07();
+    settings.inline_subschemas = true;
+
+    ToolInfo {
+        tool_type: ToolType::Function,
+        function: ToolFunctionInfo {
+            name: T::name().to_string(),
+            description: T::description().to_string(),
+            parameters: settings
+                .into_generator()
+                .into_root_schema_for::<T::Params>(),
+        },
+    }
+}
+
+/**
+ * One line of the tool log, what the model did and why
+ **/
+#[derive(Serialize)]
+struct LogEntry<'a> {
+    time: String,
+    turn: &'a str, // What the user said that made the model use the tool
+    tool: &'a str,
+    arguments: &'a Value,
+    result: &'a str,
+    ok: bool,
+    confirmed: bool, // The user was asked and said yes
+    ms: u64,
+}
+
+fn append_line(path: &str, entry: &LogEntry) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/**
+ * The tools the LLM can use
+ **/
+#[derive(Default)]
+pub struct Tools {
+    infos: Vec<ToolInfo>,
+    tools: HashMap<String, Box<dyn JsonTool>>,
+    confirm: HashSet<String>,
+    log: Option<String>,
+    attachments: Arc<Mutex<Vec<Image>>>, // Images the tools took, shown to the model with their result
+}
+
+impl Tools {
+    pub fn add<T: Tool + 'static>(&mut self, tool: T) {
+        self.infos.push(tool_info::<T>());
+        self.tools.insert(T::name().to_string(), Box::new(tool));
+    }
+
+    pub fn add_plugin(&mut self, plugin: Plugin) {
+        let name = plugin.name().to_string();
+        assert!(
+            !self.tools.contains_key(&name),
+            "There already is a tool called {}",
+            name
+        );
+        if plugin.confirm() {
+            self.confirm.insert(name.clone());
+        }
+        self.infos.push(plugin.info());
+        self.tools.insert(name, Box::new(plugin));
+    }
+
+    /**
+     * The tool is only used once the user said yes
+     **/
+    pub fn confirm(&mut self, name: &str) {
+        self.confirm.insert(name.to_string());
+    }
+
+    pub fn needs_confirmation(&self, name: &str) -> bool {
+        self.confirm.contains(name)
+    }
+
+    /**
+     * Remove the tools the user doesn't want the LLM to use
+     **/
+    pub fn retain(&mut self, allowed: impl Fn(&str) -> bool) {
+        self.infos.retain(|info| allowed(&info.function.name));
+        self.tools.retain(|name, _| allowed(name));
+    }
+
+    /**
+     * Append every call to this file as a JSON line
+     **/
+    pub fn log_to(&mut self, path: Option<String>) {
+        self.log = path;
+    }
+
+    pub fn infos(&self) -> &[ToolInfo] {
+        &self.infos
+    }
+
+    /**
+     * Tools put their images here, e.g. a picture from the webcam
+     **/
+    pub fn attachments(&self) -> Arc<Mutex<Vec<Image>>> {
+        self.attachments.clone()
+    }
+
+    /**
+     * Call the tool, errors are returned as text so the LLM can tell the user what went wrong.
+     * `turn` is what the user said that made the model use it.
+     * Returns the message with the result, images the tool took are attached to it.
+     **/
+    pub async fn call(&mut self, name: &str, arguments: Value, turn: &str) -> ChatMessage {
+        let result = self.call_tool(name, arguments, turn).await;
+        let images = std::mem::take(&mut *self.attachments.lock().unwrap());
+        if images.is_empty() {
+            ChatMessage::tool(result)
+        } else {
+            ChatMessage::tool(result).with_images(images)
+        }
+    }
+
+    async fn call_tool(&mut self, name: &str, arguments: Value, turn: &str) -> String {
+        let Some(tool) = self.tools.get_mut(name) else {
+            return format!("There is no tool called {}", name);
+        };
+
+        let start = Instant::now();
+        let (result, ok) = match tool.call(arguments.clone()).await {
+            Ok(result) => (result, true),
+            Err(err) => (format!("The tool failed: {}", err), false),
+        };
+
+        if let Some(path) = &self.log {
+            let entry = LogEntry {
+                time: Local::now().to_rfc3339(),
+                turn,
+                tool: name,
+                arguments: &arguments,
+                result: &result,
+                ok,
+                confirmed: self.confirm.contains(name),
+                ms: start.elapsed().as_millis() as u64,
+            };
+            if let Err(err) = append_line(path, &entry) {
+                eprintln!("Failed to write the tool log to {}: {}", path, err);
+            }
+        }
+        result
+    }
+}
+
+/**
+ * What the user is asked before the tool is used, e.g. "Should I really use mqtt with topic home/fan/set, payload OFF?"
+ **/
+pub fn confirmation_question(call: &ToolCall) -> String {
+    let name = call.function.name.replace('_', " ");
+    let details: Vec<String> = match &call.function.arguments {
+        Value::Object(arguments) => arguments
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(text) => format!("{} {}", key, text),
+                value => format!("{} {}", key, value),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    if details.is_empty() {
+        format!("Should I really use {}?", name)
+    } else {
+        format!("Should I really use {} with {}?", name, details.join(", "))
+    }
+}
+
+/**
+ * If the answer to a confirmation question is yes or no, None if it is neither
+ **/
+pub fn confirmed(answer: &str) -> Option<bool> {
+    let answer = answer.to_lowercase();
+    let words: Vec<&str> = answer
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if words.iter().any(|word| NO.contains(word)) {
+        Some(false)
+    } else if words.iter().any(|word| YES.contains(word)) {
+        Some(true)
+    } else {
+        None
+    }
+}
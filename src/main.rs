@@ -1,34 +1,125 @@
 extern crate chrono;
+mod audio;
+mod calibrate;
+mod controls;
+mod correction;
+mod echo;
+mod filter;
+mod hotword;
+mod images;
+mod keyboard;
+mod llm;
+mod models;
+mod moderation;
+mod persona;
+mod repeat;
+mod sentences;
+mod settings;
+mod speaker;
+mod standby;
+mod stats;
+mod stt;
+mod subtitles;
+mod textproc;
 mod tools;
+mod tts;
+mod vad;
 
-use ollama_rs::Ollama;
-use ollama_rs::coordinator::Coordinator;
+use chrono::Local;
 use ollama_rs::generation::chat::ChatMessage;
-use piper_rs::synth::PiperSpeechSynthesizer;
-use rodio::{Decoder, OutputStream, Sink};
+use rodio::buffer::SamplesBuffer;
+use rodio::Sink;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
 use std::path::Path;
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::traits::{DeviceTrait, HostTrait};
 use regex::Regex;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::error::TryRecvError;
 
+use crate::audio::agc::Agc;
+use crate::audio::alsa::{alsa_device, is_alsa};
+use crate::audio::duck::{DuckConfig, Ducker};
+use crate::audio::earcon::{Earcon, EarconConfig, Earcons, chime};
+use crate::audio::host::{available_hosts, select_host};
+use crate::audio::input::{Input, open_input_stream, open_wav_input};
+use crate::audio::output::open_output_stream;
+use crate::audio::resample::resample;
+use crate::audio::ring::RingBuffer;
+use crate::audio::wav::save_wav;
+use crate::calibrate::calibrate;
+use crate::controls::control_command;
+use crate::correction::{misheard, same_text};
+use crate::echo::echo_similarity;
+use crate::filter::WordFilter;
+use crate::hotword::contains_hotword;
+use crate::images::{ImageConfig, ImageFolder};
+use crate::keyboard::Keyboard;
+use crate::llm::history::HistoryConfig;
+use crate::llm::tools::confirmed;
+use crate::llm::{Chat, LlmConfig, create_llm};
+use crate::models::{Models, ModelsConfig};
+use crate::moderation::{ModerationConfig, Moderator};
+use crate::persona::{PersonaConfig, persona_command};
+use crate::repeat::{Repeat, Said, repeat_command};
+use crate::sentences::SentenceStream;
+use crate::settings::Settings;
+use crate::speaker::{Diarizer, SpeakerConfig, create_diarizer};
+use crate::standby::{Standby, StandbyConfig};
+use crate::stats::{Stats, StatsConfig, TurnStats, millis};
+use crate::stt::{SttConfig, SttEngine, Transcription, create_stt};
+use crate::subtitles::{SubtitleConfig, Subtitles};
+use crate::textproc::normalize;
+use crate::tools::calc::CalcTool;
+use crate::tools::calendar::{CalendarTool, spawn_warnings};
+use crate::tools::convert::ConvertTool;
+use crate::tools::documents::DocumentsTool;
+use crate::tools::fetch::FetchTool;
+use crate::tools::history::HistoryTool;
+use crate::tools::knowledge::{Knowledge, KnowledgeTool};
+use crate::tools::lights::LightsTool;
+use crate::tools::lists::{Lists, ListsTool};
+use crate::tools::memory::{Memory, MemoryTool};
+use crate::tools::mqtt::{Mqtt, MqttTool};
+use crate::tools::music::{MusicTool, Player};
+use crate::tools::persona::PersonaTool;
+use crate::tools::plugin::Plugin;
+use crate::tools::random::RandomTool;
+use crate::tools::reminder::{ReminderTool, Reminders, spawn_reminders};
 use crate::tools::time::TimeTool;
-use crate::tools::timeout::TimeoutTool;
+use crate::tools::timeout::{Timeout, TimeoutTool};
+use crate::tools::timer::{TimerTool, spawn_scheduler};
+use crate::tools::translate::TranslateTool;
+use crate::tools::weather::WeatherTool;
+use crate::tools::webcam::WebcamTool;
+use crate::tools::wikipedia::WikipediaTool;
+use crate::tools::{ToolsConfig, UseTools};
+use crate::tts::style::{VoiceStyle, split_styles};
+use crate::tts::{Speech, TtsConfig, TtsEngine, TtsKind, create_tts};
+use crate::vad::{VadConfig, create_vad};
+
+// Echoes start while the bot is talking or right after it stopped
+const ECHO_WINDOW: Duration = Duration::from_secs(1);
 
 /**
- * Removes the <think></think> tags used in AI models like Gwen and DeepSeek
+ * The part of a streamed response that should be spoken, models like Qwen and DeepSeek
+ * first think inside <think></think> tags. None while they are still thinking.
  **/
-fn remove_think_tags(input: &str) -> String {
-    let i = input
-        .find("</think>")
-        .and_then(|i| Some(i + 8))
-        .unwrap_or(0);
-    String::from(&input[i..])
+fn spoken_part(response: &str) -> Option<&str> {
+    let start = response.trim_start();
+    if start.starts_with("<think>") {
+        return response.find("</think>").map(|i| &response[i + 8..]);
+    }
+
+    // Wait until it is clear whether the response starts with the tag
+    if "<think>".starts_with(start) {
+        return None;
+    }
+    Some(response)
 }
 
 /**
@@ -51,188 +142,1487 @@ fn remove_emoji(string: String) -> String {
     regex.replace_all(&string, "").to_string()
 }
 
+fn print_configs(configs: impl Iterator<Item = cpal::SupportedStreamConfigRange>) {
+    for config in configs {
+        println!(
+            "      {} channel(s), {} - {} Hz, {}",
+            config.channels(),
+            config.min_sample_rate().0,
+            config.max_sample_rate().0,
+            config.sample_format()
+        );
+    }
+}
+
 /**
- * Downsample the audio to 16kHz
- * This is needed because the Whisper model expects 16kHz audio
+ * Print all input and output devices together with the configurations they support
+ * The index printed in front of the devices can be used for `input_device` and `output_device` in the config
  **/
-fn downsample_to_16k(input: &[f32], input_rate: usize) -> Vec<f32> {
-    let ratio = input_rate as f32 / 16_000.0;
-    input
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| (*i as f32 % ratio).round() == 0.0)
-        .map(|(_, &v)| v)
-        .collect()
+fn list_devices(host: &cpal::Host) {
+    println!("Host: {:?}", host.id());
+
+    println!("Input devices:");
+    for (index, device) in host.input_devices().unwrap().enumerate() {
+        println!("  {}: {}", index, device.name().unwrap_or_default());
+        if let Ok(configs) = device.supported_input_configs() {
+            print_configs(configs);
+        }
+    }
+
+    println!("Output devices:");
+    for (index, device) in host.output_devices().unwrap().enumerate() {
+        println!("  {}: {}", index, device.name().unwrap_or_default());
+        if let Ok(configs) = device.supported_output_configs() {
+            print_configs(configs);
+        }
+    }
+}
+
+/**
+ * State shared between the audio callback and the main loop
+ **/
+#[derive(Clone)]
+struct Capture {
+    speech_buffer: Arc<Mutex<Vec<f32>>>,
+    has_talked: Arc<Mutex<bool>>,
+    last_voice_time: Arc<Mutex<Instant>>,
+    speaking: Arc<Mutex<bool>>, // Set while the bot is talking
+    failed: Arc<Mutex<bool>>,   // Set when the input stream stopped working
+    ended: Arc<Mutex<bool>>,    // Set when the input file has been read completely
+}
+
+impl Capture {
+    fn new() -> Self {
+        Self {
+            speech_buffer: Arc::new(Mutex::new(Vec::new())),
+            has_talked: Arc::new(Mutex::new(false)),
+            last_voice_time: Arc::new(Mutex::new(Instant::now())),
+            speaking: Arc::new(Mutex::new(false)),
+            failed: Arc::new(Mutex::new(false)),
+            ended: Arc::new(Mutex::new(false)),
+        }
+    }
+}
+
+/**
+ * Open the microphone, or the input file if one is given, and start feeding the
+ * speech buffer through the VAD
+ * Returns the running input and its sample rate
+ **/
+fn start_capture(
+    host: &cpal::Host,
+    cfg: &Config,
+    capture: &Capture,
+    input_file: Option<&str>,
+) -> Result<(Input, usize), Box<dyn std::error::Error>> {
+    let state = capture.clone();
+    let failed = Arc::clone(&capture.failed);
+    // To be interrupted the bot has to keep listening while it talks
+    let mute_while_speaking = cfg.mute_while_speaking && cfg.barge_in.is_none();
+    let silence_threshold = cfg.silence_threshold;
+    let pre_roll_ms = cfg.pre_roll;
+    let agc_target = cfg.agc_target;
+
+    let make_on_data = |sample_rate| {
+        let state = state.clone();
+        let mut vad = create_vad(&cfg.vad, sample_rate, silence_threshold);
+        let mut pre_roll = RingBuffer::new(sample_rate * pre_roll_ms as usize / 1000);
+        let mut agc = agc_target.map(Agc::new);
+
+        move |data: &[f32]| {
+            // Don't listen to our own voice
+            if mute_while_speaking && *state.speaking.lock().unwrap() {
+                return;
+            }
+
+            let mut buffer = state.speech_buffer.lock().unwrap();
+            let mut has_talked = state.has_talked.lock().unwrap();
+            let mut last_time = state.last_voice_time.lock().unwrap();
+
+            // Update last_audio_time if speech was detected, on the audio as it is, since
+            // the gain control would turn the room noise up until it sounds like speech
+            let speech = vad.is_speech(data);
+            if speech {
+                *last_time = Instant::now();
+            }
+
+            let data = match &mut agc {
+                Some(agc) => &agc.process(data, speech),
+                None => data,
+            };
+
+            // Only keep the audio from the start of the speech, including a bit before
+            // it, so quiet beginnings of words don't get cut off
+            if *has_talked {
+                buffer.extend_from_slice(data);
+            } else if speech {
+                buffer.extend(pre_roll.drain());
+                buffer.extend_from_slice(data);
+                *has_talked = true;
+            } else {
+                pre_roll.push(data);
+            }
+        }
+    };
+
+    if let Some(path) = input_file {
+        let ended = Arc::clone(&capture.ended);
+        let on_end = move || *ended.lock().unwrap() = true;
+        return open_wav_input(path, make_on_data, on_end);
+    }
+
+    let on_error = move |err| {
+        eprintln!("Stream error: {:?}", err);
+        *failed.lock().unwrap() = true;
+    };
+
+    let timeout = Some(Duration::from_millis(cfg.silence_duration));
+    let wanted = cfg.input_device.as_deref();
+    open_input_stream(
+        host,
+        wanted,
+        cfg.buffer_size,
+        cfg.negotiate_input,
+        make_on_data,
+        on_error,
+        timeout,
+    )
+}
+
+/**
+ * Keep trying to reopen the input stream, waiting longer after every failed attempt
+ **/
+fn reconnect(host: &cpal::Host, cfg: &Config, capture: &Capture) -> (Input, usize) {
+    let mut backoff = Duration::from_millis(500);
+
+    loop {
+        println!("Reconnecting input device in {:?}...", backoff);
+        std::thread::sleep(backoff);
+
+        *capture.failed.lock().unwrap() = false;
+        match start_capture(host, cfg, capture, None) {
+            Ok(result) => return result,
+            Err(err) => eprintln!("Failed to reconnect input device: {}", err),
+        }
+
+        backoff = (backoff * 2).min(Duration::from_secs(30));
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 struct Config {
-    system: String, // System prompt used for the AI
-    ollama: String, // Model used for the Ollama AI
-    whisper: String,
-    piper: String,
+    system: String, // System prompt used for the AI, {date}, {time} and the prompt_variables are filled in
+    #[serde(default)]
+    prompt_variables: HashMap<String, String>, // Values for placeholders in the system prompt, e.g. {"user_name": "Daniel"}
+    #[serde(default)]
+    name: Option<String>, // Name of the bot, filled in for {name} in the system prompt and spelled right by Whisper, e.g. "VCat"
+    #[serde(default)]
+    require_name: bool, // Only answer when the name was said, so the bot stays out of conversations around it
+    ollama: String, // Model used for the AI, also with OpenAI compatible servers
+    #[serde(flatten)]
+    llm: LlmConfig,
+    #[serde(default)]
+    ollama_fallback: Option<String>, // Model used when the normal one fails, e.g. a small and fast one
+    #[serde(default = "default_true")]
+    warm_up: bool, // Load the model at startup, so the first answer isn't delayed by it
+    #[serde(default)]
+    ollama_timeout: Option<f32>, // Seconds to wait for the model before giving up on it
+    #[serde(default = "default_llm_retries")]
+    llm_retries: u32, // How often to ask again when the model fails, waiting longer every time
+    #[serde(default = "default_error_message")]
+    error_message: String, // Said when the model still fails after retrying, empty to stay silent
+    #[serde(default)]
+    max_sentences: Option<usize>, // Ask for answers this short and stop speaking after this many sentences
+    #[serde(flatten)]
+    history: HistoryConfig,
+    #[serde(flatten)]
+    personas: PersonaConfig,
+    #[serde(flatten)]
+    stats: StatsConfig,
+    #[serde(flatten)]
+    images: ImageConfig,
+    #[serde(flatten)]
+    tts: TtsConfig,
+    #[serde(default = "default_settings_file")]
+    settings_file: String, // Speech rate and volume changed by voice are saved here and used instead of the config
+    #[serde(default = "default_true")]
+    normalize_text: bool, // Spell out numbers, units, abbreviations and links before they are spoken
+    #[serde(flatten)]
+    subtitles: SubtitleConfig,
+
+    #[serde(flatten)]
+    models: ModelsConfig,
+
+    #[serde(flatten)]
+    stt: SttConfig,
+    #[serde(default = "default_min_confidence")]
+    min_confidence: f32, // Transcriptions the engine is less sure about are ignored
+    #[serde(default)]
+    nbest_confidence: Option<f32>, // Below this confidence the engine makes more guesses and the model picks the one that fits, e.g. 0.7
+    #[serde(default = "default_nbest")]
+    nbest: usize, // How many guesses the model picks from
+    #[serde(default)]
+    clarify_confidence: Option<f32>, // Below this confidence the bot asks "Did you say ...?" instead of answering, e.g. 0.6
+
+    #[serde(flatten)]
+    speaker: SpeakerConfig,
+    #[serde(flatten)]
+    standby: StandbyConfig,
 
-    silence_threshold: f32, // Volume level to be considered silence
+    #[serde(default)]
+    audio_host: Option<String>, // Audio backend to use, e.g. "alsa" or "jack"
+    input_device: Option<String>, // Name or index of the microphone, uses the default if not set
+    #[serde(default)]
+    output_device: Option<String>, // Name or index of the speaker, uses the default if not set
+    #[serde(default)]
+    exclusive_audio: bool, // Use the hw: devices of the sound cards directly, only with ALSA
+    #[serde(default)]
+    buffer_size: Option<u32>, // Frames per audio callback, lower means less latency but more CPU
+    #[serde(default = "default_true")]
+    negotiate_input: bool, // Ask the microphone for 16 kHz mono, false uses its default config
+    #[serde(default)]
+    agc_target: Option<f32>, // Enables automatic gain control towards this RMS level, e.g. 0.1
+
+    #[serde(flatten)]
+    duck: DuckConfig,
+    #[serde(flatten)]
+    earcons: EarconConfig,
+
+    #[serde(flatten)]
+    vad: VadConfig,
+
+    silence_threshold: f32, // Volume level to be considered silence, only used by energy
     silence_duration: u64,  // The duration of silence necessary to trigger the AI in milliseconds
+    #[serde(default = "default_pre_roll")]
+    pre_roll: u64, // Milliseconds of audio to keep from before the speech was detected
+    #[serde(default = "default_max_utterance_ms")]
+    max_utterance_ms: u64, // Transcribe early if someone talks for longer than this
+    #[serde(default)]
+    partial_interval: Option<u64>, // Transcribe every this many milliseconds while the user talks
+    #[serde(default)]
+    partial_stable: Option<u64>, // Stop listening once the partial transcription didn't change for this long
+
+    #[serde(default)]
+    save_utterances: Option<String>, // Directory to store every utterance and its transcription in
+    #[serde(default = "default_true")]
+    keyboard_input: bool, // Lines typed into the terminal are answered too, e.g. to paste links or code
+
+    use_tools: UseTools, // Some LLMs dont support tools, set to false if you still want to use them, or turn single tools off with e.g. {"timeout": false}
+    #[serde(default)]
+    confirm_tools: Vec<String>, // Tools that are only used after you said yes, e.g. ["mqtt", "lights"]
+    #[serde(default)]
+    tool_log: Option<String>, // Append every tool call as a JSON line to this file, e.g. "tools.jsonl"
+    #[serde(flatten)]
+    tools: ToolsConfig,
 
-    use_tools: bool, // Some LLMs dont support tools, set to false if you still want to use them
+    #[serde(default)]
+    hotword_distance: Option<usize>, // How many letters the magic word may be misspelled by, one in four by default
+
+    #[serde(default)]
+    blacklist: Vec<String>, // Words that are replaced in what was said and in the responses
+    #[serde(default = "default_blacklist_replacement")]
+    blacklist_replacement: String, // What blacklisted words are replaced with
+    #[serde(flatten)]
+    moderation: ModerationConfig,
+
+    #[serde(default = "default_true")]
+    mute_while_speaking: bool, // Discard microphone input while the bot is talking
+    #[serde(default)]
+    barge_in: Option<u64>, // Stop talking once the user talked for this many milliseconds, needs a headset
+    #[serde(default = "default_cancel_generation")]
+    cancel_generation: Option<u64>, // Forget the answer if the user talks this many milliseconds before it is spoken
+    #[serde(default = "default_echo_similarity")]
+    echo_similarity: Option<f32>, // Ignore what was heard right after talking if it is this similar to what the bot said, null turns it off
 }
 
-#[tokio::main]
-async fn main() {
-    let cfg: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+fn default_cancel_generation() -> Option<u64> {
+    Some(500)
+}
 
-    // load a context and model
-    let ctx = WhisperContext::new_with_params(&cfg.whisper, WhisperContextParameters::default())
-        .expect("failed to load model");
+fn default_echo_similarity() -> Option<f32> {
+    Some(0.7)
+}
 
-    let model = piper_rs::from_config_path(Path::new(&cfg.piper))
-        .expect("Failed to load config file for Piper model");
+fn default_llm_retries() -> u32 {
+    2
+}
 
-    let synth = PiperSpeechSynthesizer::new(model).expect("Failed to load Piper model");
+fn default_error_message() -> String {
+    "Sorry, I can't think right now. Please try again later.".to_string()
+}
 
-    // Shared state
-    let speech_buffer = Arc::new(Mutex::new(Vec::<f32>::new()));
-    let has_talked = Arc::new(Mutex::new(false));
-    let last_voice_time = Arc::new(Mutex::new(Instant::now()));
+fn default_settings_file() -> String {
+    "settings.json".to_string()
+}
 
-    // Setup CPAL
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .expect("No input device available");
-    let config = device.default_input_config().unwrap();
-    let sample_rate = config.sample_rate().0 as usize;
-    println!("Sample rate: {}", sample_rate);
-    println!("Input device: {:?}", device.name());
-
-    // Currently only f32 is supported, should be easy to add support for other formats though
-    assert_eq!(
-        config.sample_format(),
-        cpal::SampleFormat::F32,
-        "Only f32 is supported right now."
-    );
+fn default_true() -> bool {
+    true
+}
 
-    let speech_buffer_clone = Arc::clone(&speech_buffer);
-    let has_talked_clone = Arc::clone(&has_talked);
-    let last_voice_time_clone = Arc::clone(&last_voice_time);
+fn default_blacklist_replacement() -> String {
+    "beep".to_string()
+}
 
-    let stream = device
-        .build_input_stream(
-            &config.into(),
-            move |data: &[f32], _| {
-                let mut buffer = speech_buffer_clone.lock().unwrap();
-                let mut has_talked = has_talked_clone.lock().unwrap();
-                let mut last_time = last_voice_time_clone.lock().unwrap();
+fn default_min_confidence() -> f32 {
+    0.4
+}
 
-                buffer.extend_from_slice(data);
+fn default_nbest() -> usize {
+    3
+}
+
+fn default_pre_roll() -> u64 {
+    500
+}
+
+fn default_max_utterance_ms() -> u64 {
+    30_000
+}
+
+/**
+ * Play the sentences as soon as they have been synthesized and show their text while they are
+ * spoken. If barge_in is set, playback stops once the user talked for that many samples, what
+ * they said is the next prompt. While nothing is playing the same happens after `cancel` samples.
+ * Dropping the receiver also stops the synthesis of the remaining sentences.
+ * Returns if the user interrupted.
+ **/
+async fn play(
+    sink: Option<&Sink>,
+    mut receiver: UnboundedReceiver<(String, Speech)>,
+    capture: &Capture,
+    barge_in: Option<usize>,
+    cancel: Option<usize>,
+    subtitles: &Subtitles,
+) -> bool {
+    let mut synthesizing = true;
+    let mut interrupted = false;
+    let mut queued = VecDeque::new(); // Text of the sentences in the sink, the first one is playing
+    let mut shown = false;
 
-                // Append samples and update last_audio_time if not silent
-                let rms = data.iter().map(|s| s * s).sum::<f32>() / data.len() as f32;
-                if rms > cfg.silence_threshold {
-                    *last_time = Instant::now();
-                    *has_talked = true;
+    loop {
+        match receiver.try_recv() {
+            Ok((text, speech)) => {
+                match sink {
+                    Some(sink) => {
+                        *capture.speaking.lock().unwrap() = true;
+                        sink.append(SamplesBuffer::new(
+                            speech.channels,
+                            speech.sample_rate,
+                            speech.samples,
+                        ));
+                        queued.push_back(text);
+                    }
+                    None => subtitles.show(&text),
                 }
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => synthesizing = false,
+            Err(TryRecvError::Empty) => {}
+        }
 
-                println!("{}", rms);
-            },
-            |err| eprintln!("Stream error: {:?}", err),
-            Some(Duration::from_millis(cfg.silence_duration)),
-        )
-        .unwrap();
+        // The sink only knows how many sounds are left, every one less means a sentence is done
+        if let Some(sink) = sink {
+            while queued.len() > sink.len() {
+                queued.pop_front();
+                shown = false;
+            }
+            if !shown && let Some(text) = queued.front() {
+                subtitles.show(text);
+                shown = true;
+            }
+        }
+
+        if !synthesizing && sink.is_none_or(|sink| sink.empty()) {
+            break;
+        }
+
+        let talked = *capture.has_talked.lock().unwrap();
+        let buffered = capture.speech_buffer.lock().unwrap().len();
+        let silent = sink.is_none_or(|sink| sink.empty());
+        interrupted = talked
+            && (barge_in.is_some_and(|samples| buffered >= samples)
+                || silent && cancel.is_some_and(|samples| buffered >= samples));
+        if interrupted {
+            println!("Interrupted");
+            if let Some(sink) = sink {
+                sink.stop();
+            }
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    *capture.speaking.lock().unwrap() = false;
+    subtitles.clear();
+    interrupted
+}
+
+/**
+ * Synthesize one sentence after the other on a blocking thread, so the first one can already
+ * be played while the rest is still being generated. The written sentence is sent along with
+ * its audio for the subtitles. Stops once the returned receiver is dropped.
+ * The sentences replace what is in `said`, so the last thing the bot said can be repeated.
+ **/
+fn spawn_synthesis(
+    tts: Arc<dyn TtsEngine>,
+    sentences: std::sync::mpsc::Receiver<(VoiceStyle, String)>,
+    normalize_text: bool,
+    error_sound: Option<Speech>,
+    tts_time: Arc<Mutex<Duration>>,
+    said: Arc<Mutex<Vec<Said>>>,
+) -> UnboundedReceiver<(String, Speech)> {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    said.lock().unwrap().clear();
+
+    tokio::task::spawn_blocking(move || {
+        for (style, sentence) in sentences {
+            let spoken = if normalize_text {
+                normalize(&sentence)
+            } else {
+                sentence.clone()
+            };
+            let start = Instant::now();
+            let speech = tts.synthesize(&spoken, &style);
+            *tts_time.lock().unwrap() += start.elapsed();
+            match speech {
+                Ok(source) => {
+                    said.lock().unwrap().push(Said {
+                        style,
+                        text: sentence.clone(),
+                        speech: source.clone(),
+                    });
+                    if sender.send((sentence, source)).is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Failed to synthesize speech: {}", err);
+                    if let Some(sound) = error_sound.clone() {
+                        let _ = sender.send((String::new(), sound));
+                    }
+                }
+            }
+        }
+    });
+
+    receiver
+}
 
-    stream.play().unwrap();
+/**
+ * Run the speech to text engine on a blocking thread, so the async runtime stays responsive
+ **/
+async fn transcribe(
+    stt: &Arc<Mutex<Box<dyn SttEngine>>>,
+    samples: Vec<f32>,
+) -> Result<Transcription, Box<dyn std::error::Error + Send + Sync>> {
+    let stt = stt.clone();
+    tokio::task::spawn_blocking(move || stt.lock().unwrap().transcribe(&samples)).await?
+}
+
+/**
+ * Find out who is talking on a blocking thread, the voice embedding takes a while to compute
+ **/
+async fn identify(diarizer: &Arc<Mutex<Box<dyn Diarizer>>>, samples: Vec<f32>) -> Option<usize> {
+    let diarizer = diarizer.clone();
+    tokio::task::spawn_blocking(move || diarizer.lock().unwrap().identify(&samples))
+        .await
+        .ok()
+        .flatten()
+}
+
+/**
+ * Transcribe more carefully on a blocking thread, after the user said they were misunderstood
+ **/
+async fn retranscribe(
+    stt: &Arc<Mutex<Box<dyn SttEngine>>>,
+    samples: Vec<f32>,
+    language: Option<String>,
+) -> Result<Transcription, Box<dyn std::error::Error + Send + Sync>> {
+    let stt = stt.clone();
+    tokio::task::spawn_blocking(move || {
+        stt.lock()
+            .unwrap()
+            .retranscribe(&samples, language.as_deref())
+    })
+    .await?
+}
+
+/**
+ * Let the engine make more guesses of what was said on a blocking thread
+ **/
+async fn alternatives(
+    stt: &Arc<Mutex<Box<dyn SttEngine>>>,
+    samples: Vec<f32>,
+    count: usize,
+    language: Option<String>,
+) -> Result<Vec<Transcription>, Box<dyn std::error::Error + Send + Sync>> {
+    let stt = stt.clone();
+    tokio::task::spawn_blocking(move || {
+        stt.lock()
+            .unwrap()
+            .alternatives(&samples, count, language.as_deref())
+    })
+    .await?
+}
+
+/**
+ * The speech to text engine, it is loaded again if it was unloaded for standby
+ **/
+fn loaded_stt<'a>(
+    stt: &'a mut Option<Arc<Mutex<Box<dyn SttEngine>>>>,
+    cfg: &SttConfig,
+    hotwords: &[String],
+) -> &'a Arc<Mutex<Box<dyn SttEngine>>> {
+    stt.get_or_insert_with(|| Arc::new(Mutex::new(create_stt(cfg, hotwords))))
+}
+
+/**
+ * The text to speech engine, it is loaded again if it was unloaded for standby
+ **/
+fn loaded_tts<'a>(
+    tts: &'a mut Option<Arc<dyn TtsEngine>>,
+    cfg: &TtsConfig,
+) -> &'a Arc<dyn TtsEngine> {
+    tts.get_or_insert_with(|| Arc::from(create_tts(cfg)))
+}
+
+/**
+ * Store the audio Whisper got together with what it understood, useful to find out
+ * why something was misheard
+ **/
+fn save_utterance(
+    dir: &Path,
+    samples: &[f32],
+    transcription: &Transcription,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let name = Local::now().format("%Y-%m-%d_%H-%M-%S%.3f").to_string();
+
+    std::fs::create_dir_all(dir)?;
+    save_wav(
+        &dir.join(format!("{}.wav", name)),
+        samples,
+        stt::SAMPLE_RATE,
+    )?;
+    std::fs::write(dir.join(format!("{}.txt", name)), &transcription.text)?;
+    if !transcription.segments.is_empty() {
+        std::fs::write(dir.join(format!("{}.srt", name)), transcription.to_srt())?;
+    }
+    Ok(())
+}
+
+/**
+ * The system prompt together with the instruction to keep the answers short
+ **/
+fn system_prompt(cfg: &Config, system: &str) -> String {
+    match cfg.max_sentences {
+        Some(max) => format!("{}\nAnswer in at most {} sentences.", system, max),
+        None => system.to_string(),
+    }
+}
+
+/**
+ * Switch the LLM to the persona and return the voice it speaks with, unknown personas switch back to normal
+ **/
+fn become_persona(chat: &mut Chat, cfg: &Config, name: &str) -> VoiceStyle {
+    let persona = cfg.personas.personas.get(name).cloned().unwrap_or_default();
+
+    let mut options = cfg.llm.generation.clone();
+    options.temperature = persona.temperature.or(options.temperature);
+
+    chat.switch(
+        system_prompt(cfg, persona.system.as_deref().unwrap_or(&cfg.system)),
+        persona.model.unwrap_or_else(|| cfg.ollama.clone()),
+        options,
+    );
+    println!("Persona: {}", name);
+    persona.voice
+}
+
+/**
+ * Returns the value following the given command line flag, e.g. `--input file.wav`
+ **/
+fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == flag)?;
+    args.next()
+}
+
+#[tokio::main]
+async fn main() {
+    if std::env::args().any(|arg| arg == "--list-devices") {
+        for host in available_hosts() {
+            list_devices(&host);
+        }
+        return;
+    }
+
+    let mut cfg: Config = serde_json::from_str(include_str!("../config.json")).unwrap();
+    let mut settings = Settings::load(&cfg.settings_file).unwrap_or_else(|err| {
+        println!(
+            "Error failed to load the settings from {}: {}",
+            cfg.settings_file, err
+        );
+        Settings::default()
+    });
+    if let Some(rate) = settings.speech_rate {
+        cfg.tts.speech_rate = Some(rate);
+    }
+    if let Some(volume) = settings.tts_volume {
+        cfg.tts.tts_volume = volume;
+    }
+    assert!(
+        !cfg.require_name || cfg.name.is_some(),
+        "require_name needs a name"
+    );
+    if let Some(name) = &cfg.name {
+        (cfg.prompt_variables.entry("name".to_string())).or_insert_with(|| name.clone());
+    }
+
+    // Short ALSA names like "hw:1,0" are turned into the ones cpal lists, once so reconnecting uses the same device
+    let host = select_host(cfg.audio_host.as_deref());
+    if is_alsa(&host) {
+        cfg.input_device = alsa_device(cfg.input_device.as_deref(), cfg.exclusive_audio);
+        cfg.output_device = alsa_device(cfg.output_device.as_deref(), cfg.exclusive_audio);
+    } else if cfg.exclusive_audio {
+        println!("Exclusive audio only works with ALSA");
+    }
+
+    if std::env::args().any(|arg| arg == "--calibrate") {
+        calibrate(&host, cfg.input_device.as_deref(), cfg.negotiate_input);
+        return;
+    }
+
+    // load the speech to text engine
+    // Download the models if they are missing
+    let models = Models::new(&cfg.models);
+    cfg.stt.whisper.whisper = models
+        .whisper(&cfg.stt.whisper.whisper)
+        .expect("Failed to get Whisper model");
+    if cfg.tts.tts == TtsKind::Piper {
+        cfg.tts.piper.piper = models
+            .piper(&cfg.tts.piper.piper)
+            .expect("Failed to get Piper voice");
+    }
+
+    // The magic and wake words have to be understood, or there is no way out of a timeout or standby
+    let hotwords = [
+        cfg.tools.timeout.magic_words.as_slice(),
+        cfg.standby.wake_words.as_slice(),
+        cfg.name.as_slice(),
+    ]
+    .concat();
+    let mut stt = Some(Arc::new(Mutex::new(create_stt(&cfg.stt, &hotwords))));
+    let mut standby = Standby::new(&cfg.standby, cfg.hotword_distance);
+    let diarizer = create_diarizer(&cfg.speaker).map(|d| Arc::new(Mutex::new(d)));
+    let filter = WordFilter::new(&cfg.blacklist, &cfg.blacklist_replacement);
+    let moderator = Moderator::new(&cfg.moderation, create_llm(&cfg.llm));
+    let music = Player::new(&cfg.tools.music);
+    let mut ducker = Ducker::new(&cfg.duck, music.clone());
+    let earcons = Earcons::new(&cfg.earcons);
+    let subtitles = Subtitles::new(&cfg.subtitles);
+
+    // load the text to speech engine
+    let mut tts: Option<Arc<dyn TtsEngine>> = Some(Arc::from(create_tts(&cfg.tts)));
+
+    // Setup CPAL
+    let capture = Capture::new();
+    let input_file = arg_value("--input");
+    let (mut input, mut sample_rate) =
+        start_capture(&host, &cfg, &capture, input_file.as_deref()).expect("Failed to open input");
     println!("Listening with VAD...");
 
-    // Open the default audio output stream
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+    // Stdin can't be typed into when the audio is read from it
+    let keyboard = (cfg.keyboard_input && input_file.as_deref() != Some("-")).then(Keyboard::new);
+
+    // Open the audio output stream, there might be none when testing with --input
+    let output = open_output_stream(&host, cfg.output_device.as_deref());
+    if let Err(err) = &output {
+        println!("No output device, responses won't be played: {}", err);
+    }
 
     // Create a sink (something that plays audio)
-    let sink = Sink::try_new(&stream_handle).unwrap();
+    let sink = output
+        .as_ref()
+        .ok()
+        .map(|(_, stream_handle)| Sink::try_new(stream_handle).unwrap());
+    if let Some(sink) = &sink {
+        sink.set_volume(cfg.tts.tts_volume);
+    }
+
+    let timeout = Timeout::new(&cfg.tools.timeout);
+
+    let system = system_prompt(&cfg, &cfg.system);
+    let mut chat = Chat::new(create_llm(&cfg.llm), cfg.ollama.clone(), system)
+        .fallback(
+            cfg.ollama_fallback.clone(),
+            cfg.ollama_timeout.map(Duration::from_secs_f32),
+        )
+        .retries(cfg.llm_retries)
+        .options(cfg.llm.generation.clone())
+        .variables(cfg.prompt_variables.clone())
+        .limit_history(cfg.history.clone());
+    if let Some(path) = &cfg.history.history_file {
+        let fresh = cfg.history.fresh_start || std::env::args().any(|arg| arg == "--fresh");
+        chat = chat.persist(path.clone(), fresh);
+    }
 
-    // By default, it will connect to localhost:11434
-    let ollama = Ollama::default();
-    let history = vec![ChatMessage {
-        role: ollama_rs::generation::chat::MessageRole::System,
-        content: cfg.system,
-        tool_calls: vec![],
-        images: None,
-    }];
+    let timers = Arc::new(Mutex::new(Vec::new()));
+    let mut expired_timers = spawn_scheduler(timers.clone());
+    let chime = chime(cfg.tools.timer.timer_sound.as_ref());
 
-    let timeout = Arc::new(Mutex::new(Instant::now()));
+    let reminders = cfg.tools.reminders.reminders_file.as_deref();
+    let reminders = reminders.map(|path| Arc::new(Mutex::new(Reminders::load(path))));
+    let mut due_reminders = reminders.clone().map(spawn_reminders);
 
-    let mut coordinator = Coordinator::new(ollama, cfg.ollama, history);
+    let memory = cfg.tools.memory.memory_file.as_deref();
+    let memory = memory.map(|path| Arc::new(Mutex::new(Memory::load(path))));
+
+    let calendar = &cfg.tools.calendar;
+    let mut upcoming_events = calendar
+        .warnings()
+        .map(|minutes| spawn_warnings(calendar.clone(), minutes));
 
     // TODO: Add other tools that the AI should use here:
-    if cfg.use_tools {
-        coordinator = coordinator
+    if cfg.use_tools.any() {
+        chat = chat
             .add_tool(TimeoutTool {
                 timeout: timeout.clone(),
+                magic_words: cfg.tools.timeout.magic_words.clone(),
+            })
+            .add_tool(TimeTool {})
+            .add_tool(CalcTool {})
+            .add_tool(ConvertTool::default())
+            .add_tool(RandomTool {})
+            .add_tool(TimerTool {
+                timers: timers.clone(),
             })
-            .add_tool(TimeTool {});
+            .add_tool(WikipediaTool {
+                cfg: cfg.tools.wikipedia.clone(),
+            });
+
+        if let Some(memory) = &memory {
+            chat = chat.add_tool(MemoryTool {
+                memory: memory.clone(),
+            });
+        }
+        if let Some(path) = &cfg.history.history_file
+            && cfg.history.max_log_turns > 0
+        {
+            chat = chat.add_tool(HistoryTool { path: path.clone() });
+        }
+        if let Some(path) = &cfg.tools.lists.lists_file {
+            chat = chat.add_tool(ListsTool {
+                lists: Lists::load(path),
+            });
+        }
+        if let Some(reminders) = &reminders {
+            chat = chat.add_tool(ReminderTool {
+                reminders: reminders.clone(),
+            });
+        }
+        if cfg.tools.calendar.calendar.is_some() {
+            chat = chat.add_tool(CalendarTool {
+                cfg: cfg.tools.calendar.clone(),
+            });
+        }
+        if cfg.tools.documents.documents_dir.is_some() {
+            chat = chat.add_tool(DocumentsTool {
+                cfg: cfg.tools.documents.clone(),
+            });
+        }
+        if cfg.tools.knowledge.knowledge_dir.is_some() {
+            let knowledge = Knowledge::new(&cfg.tools.knowledge, &cfg.llm.ollama);
+            knowledge.spawn_indexing();
+            chat = chat.add_tool(KnowledgeTool { knowledge });
+        }
+        if !cfg.tools.fetch.fetch_domains.is_empty() {
+            chat = chat.add_tool(FetchTool {
+                cfg: cfg.tools.fetch.clone(),
+            });
+        }
+        if cfg.tools.translate.enabled() {
+            chat = chat.add_tool(TranslateTool::new(
+                &cfg.tools.translate,
+                create_llm(&cfg.llm),
+            ));
+        }
+        if cfg.tools.weather.weather_location.is_some() {
+            chat = chat.add_tool(WeatherTool::new(&cfg.tools.weather));
+        }
+        if let Some(player) = &music {
+            chat = chat.add_tool(MusicTool {
+                player: player.clone(),
+            });
+        }
+
+        let mqtt = cfg.tools.mqtt.mqtt_host.as_ref();
+        let mqtt = mqtt.map(|_| Mqtt::connect(&cfg.tools.mqtt));
+        if let Some(mqtt) = &mqtt
+            && !(cfg.tools.mqtt.mqtt_publish.is_empty() && cfg.tools.mqtt.mqtt_subscribe.is_empty())
+        {
+            chat = chat.add_tool(MqttTool::new(&cfg.tools.mqtt, mqtt.clone()));
+        }
+        if !cfg.tools.lights.lights.is_empty() {
+            chat = chat.add_tool(LightsTool::new(&cfg.tools.lights, mqtt));
+        }
+        if cfg.tools.webcam.webcam.is_some() {
+            let attachments = chat.attachments();
+            chat = chat.add_tool(WebcamTool::new(&cfg.tools.webcam, attachments));
+        }
+
+        for plugin in &cfg.tools.plugins.plugins {
+            chat = chat.add_plugin(Plugin::new(plugin));
+        }
+    }
+
+    let persona_switch = Arc::new(Mutex::new(None));
+    if cfg.use_tools.any() && !cfg.personas.personas.is_empty() {
+        chat = chat.add_tool(PersonaTool {
+            personas: cfg.personas.personas.keys().cloned().collect(),
+            switch: persona_switch.clone(),
+        });
+    }
+    chat = chat
+        .retain_tools(|name| cfg.use_tools.allows(name))
+        .confirm_tools(&cfg.confirm_tools)
+        .log_tools(cfg.tool_log.clone());
+
+    let mut stats = Stats::new(&cfg.stats);
+    let mut image_folder = cfg.images.image_folder.as_deref().map(ImageFolder::new);
+
+    let mut voice = VoiceStyle::default();
+    if let Some(name) = &cfg.personas.persona {
+        voice = become_persona(&mut chat, &cfg, name);
+    }
+
+    if cfg.warm_up {
+        chat.warm_up();
     }
 
+    let mut last_partial = Instant::now();
+    let mut partial_changed = Instant::now();
+    let mut partial_text = String::new();
+    let mut was_talking = false;
+
+    // Said without a chime once the user isn't talking, e.g. "Did you say …?"
+    let mut notices = VecDeque::new();
+    // The audio and text of what was said last, to transcribe it again when it was misheard
+    let mut last_utterance: Option<(Vec<f32>, String)> = None;
+    // What the user probably said instead, waiting for a yes
+    let mut alternative: Option<String> = None;
+    // What the bot said last and when it stopped, to recognize when it hears itself
+    let mut last_spoken: Option<(String, Instant)> = None;
+    // The sentences the bot said last with their audio, to say them again when asked
+    let said = Arc::new(Mutex::new(Vec::new()));
+
     loop {
-        std::thread::sleep(Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(100)).await;
 
-        let elapsed = last_voice_time.lock().unwrap().elapsed();
+        // The microphone got disconnected, try to get it back
+        if *capture.failed.lock().unwrap() {
+            drop(input);
+            (input, sample_rate) = reconnect(&host, &cfg, &capture);
+            println!("Listening with VAD...");
+        }
 
-        if elapsed > Duration::from_millis(cfg.silence_duration) {
-            let mut buffer = speech_buffer.lock().unwrap();
-            let mut has_talked = has_talked.lock().unwrap();
+        // Everything from the input file has been answered
+        if *capture.ended.lock().unwrap() && !*capture.has_talked.lock().unwrap() {
+            println!("End of input");
+            break;
+        }
 
-            if *has_talked {
-                println!("Silence detected — transcribing...");
+        // Nobody talked for a long time, free the memory of Whisper and the voice
+        if standby.due() {
+            standby.enter();
+            stt = None;
+            tts = None;
+        }
+
+        // Let the user know that they are being listened to
+        let talking = *capture.has_talked.lock().unwrap();
+        if talking && !was_talking {
+            earcons.play(sink.as_ref(), Earcon::Listening);
+        }
+        was_talking = talking;
+
+        // A timer, reminder or calendar event is due, ring and say which one once the user isn't talking
+        let notice = if talking { None } else { notices.pop_front() };
+        let ring = notice.is_none();
+        if !talking
+            && let Some(announcement) = notice
+                .or_else(|| {
+                    expired_timers
+                        .try_recv()
+                        .ok()
+                        .map(|timer| timer.announcement())
+                })
+                .or_else(|| Some(due_reminders.as_mut()?.try_recv().ok()?.announcement()))
+                .or_else(|| Some(upcoming_events.as_mut()?.try_recv().ok()?.announcement()))
+        {
+            println!("Alert: {}", announcement);
+            if ring && let Some(sink) = &sink {
+                sink.append(SamplesBuffer::new(
+                    chime.channels,
+                    chime.sample_rate,
+                    chime.samples.clone(),
+                ));
+            }
+
+            let (sentence_sender, sentences) = std::sync::mpsc::channel();
+            let _ = sentence_sender.send((voice.clone(), announcement.clone()));
+            drop(sentence_sender);
+            let tts_time = Arc::new(Mutex::new(Duration::ZERO));
+            let tts_engine = loaded_tts(&mut tts, &cfg.tts).clone();
+            let receiver = spawn_synthesis(
+                tts_engine,
+                sentences,
+                cfg.normalize_text,
+                None,
+                tts_time,
+                said.clone(),
+            );
+
+            let barge_in = cfg.barge_in.map(|ms| sample_rate * ms as usize / 1000);
+            ducker.duck();
+            play(
+                sink.as_ref(),
+                receiver,
+                &capture,
+                barge_in,
+                None,
+                &subtitles,
+            )
+            .await;
+            ducker.restore();
+            last_spoken = Some((announcement, Instant::now()));
+
+            // The voice was only loaded for the announcement
+            if standby.active() {
+                tts = None;
+            }
+            continue;
+        }
+
+        // Transcribe what has been said so far while the user is still talking
+        if let Some(interval) = cfg.partial_interval
+            && !standby.active()
+        {
+            let talking = *capture.has_talked.lock().unwrap();
+            if talking && last_partial.elapsed() > Duration::from_millis(interval) {
+                let samples = capture.speech_buffer.lock().unwrap().clone();
+                let samples = resample(&samples, sample_rate, stt::SAMPLE_RATE);
+                let engine = loaded_stt(&mut stt, &cfg.stt, &hotwords);
+                let text = transcribe(engine, samples).await.map(|t| t.text);
+                let text = text.unwrap_or_default();
+                if text != partial_text {
+                    println!("Partial: {}", text);
+                    partial_text = text;
+                    partial_changed = Instant::now();
+                }
+                last_partial = Instant::now();
+            }
+        }
+
+        // The partial transcription didn't change for a while, so the user is probably done
+        let stable = cfg.partial_stable.is_some_and(|stable| {
+            !partial_text.is_empty() && partial_changed.elapsed() > Duration::from_millis(stable)
+        });
+
+        let elapsed = capture.last_voice_time.lock().unwrap().elapsed();
+
+        // Don't let the buffer grow forever if someone doesn't stop talking
+        let buffered = capture.speech_buffer.lock().unwrap().len();
+        let too_long = buffered > sample_rate * cfg.max_utterance_ms as usize / 1000;
+
+        // A typed message is answered like something that was said, with nothing to transcribe
+        let typed = keyboard.as_ref().and_then(|keyboard| keyboard.typed());
+
+        if typed.is_some()
+            || elapsed > Duration::from_millis(cfg.silence_duration)
+            || too_long
+            || stable
+        {
+            // Take what was said out of the buffer, so the microphone isn't blocked meanwhile
+            let buffer = if typed.is_some() {
+                Some(Vec::new())
+            } else {
+                let mut buffer = capture.speech_buffer.lock().unwrap();
+                let mut has_talked = capture.has_talked.lock().unwrap();
+                let talked = std::mem::replace(&mut *has_talked, false);
+                talked.then(|| std::mem::take(&mut *buffer))
+            };
+
+            if let Some(buffer) = buffer {
+                let is_typed = typed.is_some();
+                if is_typed {
+                    println!("Typed message");
+                } else if too_long {
+                    println!("Maximum utterance length reached — transcribing...");
+                } else if stable {
+                    println!("Transcription stable — transcribing...");
+                } else {
+                    println!("Silence detected — transcribing...");
+                }
+                if !standby.active() && !is_typed {
+                    earcons.play(sink.as_ref(), Earcon::Thinking);
+                }
+
+                // Transcribe what was said
+                let samples = resample(&buffer, sample_rate, stt::SAMPLE_RATE);
+                let stt_start = Instant::now();
+                let transcription = match typed {
+                    Some(text) => Ok(Transcription {
+                        text,
+                        confidence: 1.0,
+                        language: None,
+                        segments: Vec::new(),
+                    }),
+                    None => {
+                        let engine = loaded_stt(&mut stt, &cfg.stt, &hotwords);
+                        transcribe(engine, samples.clone()).await
+                    }
+                };
+                let stt_time = stt_start.elapsed();
+                let transcription = transcription.unwrap_or_else(|err| {
+                    eprintln!("Transcription failed: {}", err);
+                    earcons.play(sink.as_ref(), Earcon::Error);
+                    Transcription {
+                        text: String::new(),
+                        confidence: 0.0,
+                        language: None,
+                        segments: Vec::new(),
+                    }
+                });
+
+                if let Some(dir) = &cfg.save_utterances
+                    && !is_typed
+                    && let Err(err) = save_utterance(Path::new(dir), &samples, &transcription)
+                {
+                    eprintln!("Failed to save utterance: {}", err);
+                }
+
+                let mut prompt = transcription.text;
+                let confidence = transcription.confidence;
+                println!("Transcription: {} (confidence {:.2})", prompt, confidence);
+                if let Some(language) = &transcription.language {
+                    println!("Language: {}", language);
+                }
+
+                partial_text.clear();
+
+                // In standby only the wake words are listened for, otherwise Whisper is unloaded again
+                if standby.active() {
+                    if !is_typed && !standby.wakes(&prompt) {
+                        stt = None;
+                        continue;
+                    }
+                    standby.leave();
+                }
+
+                // Whisper likes to hallucinate things like "Thank you." for noise
+                if prompt.is_empty() || confidence < cfg.min_confidence {
+                    println!("Ignoring transcription");
+                    continue;
+                }
+
+                // The microphone picked up the speakers, answering that would make the bot talk to itself
+                let length = samples.len() * 1000 / stt::SAMPLE_RATE;
+                let heard_since = stt_start - Duration::from_millis(length as u64);
+                if let Some(min) = cfg.echo_similarity
+                    && !is_typed
+                    && let Some((spoken, spoken_until)) = &last_spoken
+                    && heard_since < *spoken_until + ECHO_WINDOW
+                    && echo_similarity(&prompt, spoken) >= min
+                {
+                    println!("Ignoring echo of the last answer");
+                    continue;
+                }
+
+                // Only answer when called by name, the answer to "Did you say ...?" doesn't need it
+                if cfg.require_name
+                    && !is_typed
+                    && alternative.is_none()
+                    && !timeout.active()
+                    && let Some(name) = &cfg.name
+                    && !contains_hotword(&prompt, name, cfg.hotword_distance)
+                {
+                    println!("Not called by name");
+                    continue;
+                }
+                standby.activity();
+
+                if standby.requested(&prompt) {
+                    standby.enter();
+                    stt = None;
+                    tts = None;
+                    continue;
+                }
+
+                // Talk slower, faster, louder or quieter from now on, also after a restart
+                if let Some(control) = control_command(&prompt)
+                    && !timeout.active()
+                {
+                    if let Some(rate) = control.speech_rate(cfg.tts.speech_rate) {
+                        println!("Speech rate: {}", rate);
+                        cfg.tts.speech_rate = Some(rate);
+                        settings.speech_rate = Some(rate);
+                        tts = None;
+                    }
+                    if let Some(volume) = control.volume(cfg.tts.tts_volume) {
+                        println!("Volume: {}", volume);
+                        cfg.tts.tts_volume = volume;
+                        settings.tts_volume = Some(volume);
+                        if let Some(sink) = &sink {
+                            sink.set_volume(volume);
+                        }
+                    }
+                    if let Err(err) = settings.save(&cfg.settings_file) {
+                        println!(
+                            "Error failed to save the settings to {}: {}",
+                            cfg.settings_file, err
+                        );
+                    }
+                    notices.push_back("Okay, is this better?".to_string());
+                    continue;
+                }
+
+                // Say the last answer again without asking the model, synthesized again if it should be slower
+                if let Some(repeat) = repeat_command(&prompt)
+                    && !timeout.active()
+                    && !said.lock().unwrap().is_empty()
+                {
+                    let sentences = said.lock().unwrap().clone();
+                    let receiver = match repeat {
+                        Repeat::Again => {
+                            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+                            for sentence in sentences.iter().cloned() {
+                                let _ = sender.send((sentence.text, sentence.speech));
+                            }
+                            receiver
+                        }
+                        Repeat::Slower => {
+                            let (sentence_sender, slower) = std::sync::mpsc::channel();
+                            for sentence in &sentences {
+                                let _ = sentence_sender.send(sentence.slower());
+                            }
+                            drop(sentence_sender);
+                            spawn_synthesis(
+                                loaded_tts(&mut tts, &cfg.tts).clone(),
+                                slower,
+                                cfg.normalize_text,
+                                None,
+                                Arc::new(Mutex::new(Duration::ZERO)),
+                                said.clone(),
+                            )
+                        }
+                    };
+                    let text = (sentences.iter().map(|sentence| sentence.text.as_str()))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    println!("Repeating: {}", text);
 
-                // Transcribe with Whisper
-                let mut state = ctx.create_state().unwrap();
-                let params = FullParams::new(SamplingStrategy::Greedy { best_of: 3 });
+                    let barge_in = cfg.barge_in.map(|ms| sample_rate * ms as usize / 1000);
+                    ducker.duck();
+                    play(
+                        sink.as_ref(),
+                        receiver,
+                        &capture,
+                        barge_in,
+                        None,
+                        &subtitles,
+                    )
+                    .await;
+                    ducker.restore();
+                    last_spoken = Some((text, Instant::now()));
+                    continue;
+                }
+
+                // Not sure what was said, so the model picks the guess that fits the conversation
+                if cfg.nbest_confidence.is_some_and(|min| confidence < min) && !timeout.active() {
+                    let engine = loaded_stt(&mut stt, &cfg.stt, &hotwords);
+                    let count = cfg.nbest.saturating_sub(1);
+                    let language = transcription.language.clone();
+                    let others = alternatives(engine, samples.clone(), count, language).await;
+                    let others = others.unwrap_or_else(|err| {
+                        eprintln!("Failed to make more guesses: {}", err);
+                        Vec::new()
+                    });
 
-                let k16 = downsample_to_16k(&buffer, sample_rate);
-                state.full(params, &k16).expect("Whisper failed");
+                    let mut guesses = vec![prompt.clone()];
+                    for other in others {
+                        if !other.text.is_empty()
+                            && !guesses.iter().any(|guess| same_text(guess, &other.text))
+                        {
+                            guesses.push(other.text);
+                        }
+                    }
+                    if guesses.len() > 1 {
+                        println!("Guesses: {:?}", guesses);
+                        match chat.pick(&guesses).await {
+                            Ok(index) => prompt = guesses.swap_remove(index),
+                            Err(err) => eprintln!("Failed to pick a guess: {}", err),
+                        }
+                        println!("Picked: {}", prompt);
+                    }
+                }
 
-                let prompt = state.full_get_segment_text(0).unwrap_or_default();
-                println!("Transcription: {}", prompt);
+                let mut prompt = filter.censor(&prompt);
+
+                // The last question was whether the user said something else
+                let answering = alternative.is_some();
+                let mut accepted = false;
+                if let Some(meant) = alternative.take() {
+                    match confirmed(&prompt) {
+                        Some(true) => {
+                            prompt = meant;
+                            accepted = true;
+                        }
+                        Some(false) => {
+                            notices.push_back("Sorry, please say it again.".to_string());
+                            continue;
+                        }
+                        None => {}
+                    }
+                }
 
-                buffer.clear();
-                *has_talked = false;
+                // Misheard, so transcribe the last utterance more carefully and ask if that was it
+                if misheard(&prompt) && !timeout.active() {
+                    let Some((last_samples, last_text)) = last_utterance.take() else {
+                        notices.push_back("Sorry, please say it again.".to_string());
+                        continue;
+                    };
+                    let engine = loaded_stt(&mut stt, &cfg.stt, &hotwords);
+                    let language = transcription.language.clone();
+                    let text = retranscribe(engine, last_samples, language).await;
+                    let text = text.map(|t| filter.censor(&t.text)).unwrap_or_default();
+                    println!("Transcribed again: {}", text);
+
+                    if text.is_empty() || same_text(&text, &last_text) {
+                        notices.push_back(format!(
+                            "Sorry, I keep understanding \"{}\". Please say it again.",
+                            last_text
+                        ));
+                    } else {
+                        notices.push_back(format!("Did you say \"{}\"?", text));
+                        alternative = Some(text);
+                    }
+                    continue;
+                }
+                // A yes keeps the audio of what was asked about, typed messages have none
+                if !accepted && !is_typed {
+                    last_utterance = Some((samples.clone(), prompt.clone()));
+                }
 
-                if (*timeout.lock().unwrap())
-                    .duration_since(Instant::now())
-                    .as_secs()
-                    > 0
-                    && !prompt.to_lowercase().contains(TimeoutTool::MAGIC_WORD)
+                // Rather ask than answer something the user never asked
+                if cfg.clarify_confidence.is_some_and(|min| confidence < min)
+                    && !answering
+                    && !timeout.active()
                 {
+                    notices.push_back(format!("Did you say \"{}\"?", prompt));
+                    alternative = Some(prompt);
+                    continue;
+                }
+
+                // Let the LLM know who said it when multiple people are talking to it
+                let speaker = match &diarizer {
+                    Some(diarizer) if !is_typed => identify(diarizer, samples.clone()).await,
+                    _ => None,
+                };
+                let prompt = match speaker {
+                    Some(speaker) => format!("Speaker {}: {}", speaker, prompt),
+                    None => prompt,
+                };
+
+                let magic_word = (cfg.tools.timeout.magic_words.iter())
+                    .any(|word| contains_hotword(&prompt, word, cfg.hotword_distance));
+                if timeout.active() && !magic_word {
                     println!("Timeout");
                 } else {
-                    *timeout.lock().unwrap() = Instant::now();
+                    timeout.clear();
+
+                    if let Some(name) = persona_command(&prompt, &cfg.personas.personas) {
+                        voice = become_persona(&mut chat, &cfg, name);
+                    }
+
+                    // Remind the model of what it knows about the user that fits what was said
+                    if let Some(memory) = &memory {
+                        let memory = memory.lock().unwrap();
+                        chat.set_context(memory.context(&prompt, cfg.tools.memory.memory_facts));
+                    }
+
+                    let (sentence_sender, sentences) = std::sync::mpsc::channel();
+                    let error_sound = earcons.get(Earcon::Error).cloned();
+                    let tts_time = Arc::new(Mutex::new(Duration::ZERO));
+                    let receiver = spawn_synthesis(
+                        loaded_tts(&mut tts, &cfg.tts).clone(),
+                        sentences,
+                        cfg.normalize_text,
+                        error_sound,
+                        tts_time.clone(),
+                        said.clone(),
+                    );
+
+                    // Hand every sentence to the synthesis as soon as the LLM finished it
+                    let filter = &filter;
+                    let voice_tags = &cfg.tts.voice_tags;
+                    let base = &voice;
+                    let mut style = voice.clone();
+                    let moderator = &moderator;
+                    let mut blocked = false;
+                    let mut say = move |sentence: &str| {
+                        // Nothing more is said once a sentence was blocked
+                        if blocked {
+                            return;
+                        }
+                        let sentence = match moderator.rewrite(&filter.censor(sentence)) {
+                            Some(sentence) => remove_emoji(sentence),
+                            None => {
+                                blocked = true;
+                                moderator.message.clone()
+                            }
+                        };
+                        for part in split_styles(&sentence, voice_tags, base, &mut style) {
+                            let _ = sentence_sender.send(part);
+                        }
+                    };
 
                     // Ask ollama to generate a response, it might use a tool here
-                    let Ok(res) = coordinator.chat(vec![ChatMessage::user(prompt)]).await else {
-                        println!("Error failed to get response from AI");
-                        continue;
+                    let mut llm_time = Duration::ZERO;
+                    let mut first_token = None;
+                    let generate = async {
+                        let llm_start = Instant::now();
+                        let mut response = String::new();
+                        let mut stream = SentenceStream::new();
+                        let mut streamed = 0;
+                        let mut sentences = 0;
+                        // Sentences waiting for the moderation model to read the whole answer
+                        let mut pending = Vec::new();
+
+                        let mut message = ChatMessage::user(prompt);
+                        let images = image_folder.as_mut().map(|f| f.new_images());
+                        if let Some(images) = images.filter(|images| !images.is_empty()) {
+                            message = message.with_images(images);
+                        }
+
+                        print!("Response: ");
+                        let result = chat
+                            .send(message, |token| {
+                                if first_token.is_none() && !token.is_empty() {
+                                    first_token = Some(llm_start.elapsed());
+                                }
+                                print!("{}", token);
+                                let _ = std::io::stdout().flush();
+
+                                response.push_str(token);
+                                if let Some(spoken) = spoken_part(&response) {
+                                    for sentence in stream.push(&spoken[streamed..]) {
+                                        // The rest of a too long answer isn't spoken
+                                        if cfg.max_sentences.is_none_or(|max| sentences < max) {
+                                            if moderator.waits_for_answer() {
+                                                pending.push(sentence);
+                                            } else {
+                                                say(&sentence);
+                                            }
+                                        }
+                                        sentences += 1;
+                                    }
+                                    streamed = spoken.len();
+                                }
+                            })
+                            .await;
+                        llm_time = llm_start.elapsed();
+                        println!();
+
+                        // The last sentence of an answer that reached num_predict is unfinished
+                        if let Some(rest) = stream.finish()
+                            && !chat.truncated()
+                            && cfg.max_sentences.is_none_or(|max| sentences < max)
+                        {
+                            if moderator.waits_for_answer() {
+                                pending.push(rest);
+                            } else {
+                                say(&rest);
+                            }
+                        }
+                        if !pending.is_empty() {
+                            if moderator.allowed(&pending.join(" ")).await {
+                                for sentence in &pending {
+                                    say(sentence);
+                                }
+                            } else {
+                                say(&moderator.message);
+                            }
+                        }
+                        if result.is_err() && !cfg.error_message.is_empty() {
+                            say(&cfg.error_message);
+                        }
+                        // Lets the synthesis know that there are no more sentences
+                        drop(say);
+                        result
                     };
 
-                    let result = res.message.content;
-                    println!("Response: {}", result);
+                    let barge_in = cfg.barge_in.map(|ms| sample_rate * ms as usize / 1000);
+                    let cancel = cfg
+                        .cancel_generation
+                        .map(|ms| sample_rate * ms as usize / 1000);
+                    ducker.duck();
+                    let playback = play(
+                        sink.as_ref(),
+                        receiver,
+                        &capture,
+                        barge_in,
+                        cancel,
+                        &subtitles,
+                    );
 
-                    let output_path = Path::new("output.wav");
+                    // When the user interrupts, the answer is thrown away and what they said is the next prompt
+                    let result = {
+                        tokio::pin!(generate, playback);
+                        let mut result = None;
+                        loop {
+                            tokio::select! {
+                                done = &mut generate, if result.is_none() => result = Some(done),
+                                interrupted = &mut playback => {
+                                    if interrupted {
+                                        println!("Answer cancelled");
+                                    }
+                                    break result;
+                                }
+                            }
+                        }
+                    };
+                    ducker.restore();
+
+                    let Some(result) = result else {
+                        persona_switch.lock().unwrap().take();
+                        continue;
+                    };
+                    if let Ok(answer) = &result {
+                        let spoken = spoken_part(answer).unwrap_or_default();
+                        last_spoken = Some((spoken.to_string(), Instant::now()));
+                    }
 
-                    synth
-                        .synthesize_to_file(
-                            output_path,
-                            remove_emoji(remove_think_tags(&result)),
-                            None,
-                        )
-                        .expect("Failed to synthesize speech");
+                    // The LLM asked to become another persona
+                    if let Some(name) = persona_switch.lock().unwrap().take() {
+                        voice = become_persona(&mut chat, &cfg, &name);
+                    }
 
-                    // Play the generated oudio file
-                    let file = File::open(output_path).expect("Failed to open file");
-                    let source =
-                        Decoder::new(BufReader::new(file)).expect("Failed to decode audio file");
+                    if result.is_ok() {
+                        stats.record(&TurnStats {
+                            stt_ms: millis(stt_time),
+                            llm_first_token_ms: first_token.map(millis),
+                            llm_ms: millis(llm_time),
+                            tts_ms: millis(*tts_time.lock().unwrap()),
+                            prompt_tokens: chat.usage().prompt_tokens,
+                            response_tokens: chat.usage().response_tokens,
+                        });
+                    }
 
-                    sink.append(source);
-                    sink.sleep_until_end();
+                    if let Err(err) = result {
+                        println!("Error failed to get response from AI: {}", err);
+                        earcons.play(sink.as_ref(), Earcon::Error);
+                    }
                 }
             }
         }
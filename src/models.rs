@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const WHISPER_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+const PIPER_URL: &str = "https://huggingface.co/rhasspy/piper-voices/resolve/main";
+
+/**
+ * Model download related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct ModelsConfig {
+    #[serde(default = "default_models_dir")]
+    pub models_dir: String, // Where models given by name instead of path are stored
+    #[serde(default)]
+    pub model_checksums: HashMap<String, String>, // Expected sha256 of model files by file name
+}
+
+fn default_models_dir() -> String {
+    "./models".to_string()
+}
+
+fn sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/**
+ * Finds the configured models and downloads them if they are missing
+ **/
+pub struct Models<'a> {
+    cfg: &'a ModelsConfig,
+}
+
+impl<'a> Models<'a> {
+    pub fn new(cfg: &'a ModelsConfig) -> Self {
+        Self { cfg }
+    }
+
+    /**
+     * Path to the Whisper model, either a path like "./ggml-base.en.bin" or only
+     * the name like "base.en". Missing models are downloaded from Hugging Face.
+     **/
+    pub fn whisper(&self, model: &str) -> Result<String, Box<dyn Error>> {
+        let path = if is_name(model, ".bin") {
+            Path::new(&self.cfg.models_dir).join(format!("ggml-{}.bin", model))
+        } else {
+            PathBuf::from(model)
+        };
+
+        let file_name = file_name(&path);
+        if file_name.starts_with("ggml-") {
+            self.fetch(&path, &format!("{}/{}", WHISPER_URL, file_name))?;
+        }
+
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    /**
+     * Path to the config of the Piper voice, either a path like "./en_GB-jenny_dioco-medium.onnx.json"
+     * or only the name like "en_GB-jenny_dioco-medium". Missing voices are downloaded from Hugging Face.
+     **/
+    pub fn piper(&self, voice: &str) -> Result<String, Box<dyn Error>> {
+        let path = if is_name(voice, ".json") {
+            Path::new(&self.cfg.models_dir).join(format!("{}.onnx.json", voice))
+        } else {
+            PathBuf::from(voice)
+        };
+
+        // Voices are named language-name-quality and sorted into directories by these
+        let file_name = file_name(&path);
+        let voice = file_name.trim_end_matches(".onnx.json");
+        let parts: Vec<&str> = voice.split('-').collect();
+        if let [language, name, quality] = parts[..] {
+            let family = language.split('_').next().unwrap_or(language);
+            let url = format!("{}/{}/{}/{}/{}", PIPER_URL, family, language, name, quality);
+
+            self.fetch(&path, &format!("{}/{}.onnx.json", url, voice))?;
+            self.fetch(
+                &path.with_file_name(format!("{}.onnx", voice)),
+                &format!("{}/{}.onnx", url, voice),
+            )?;
+        }
+
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    /**
+     * Download the file unless it already exists with the expected checksum
+     **/
+    fn fetch(&self, path: &Path, url: &str) -> Result<(), Box<dyn Error>> {
+        let expected = self.cfg.model_checksums.get(&file_name(path));
+
+        if path.exists() {
+            match expected {
+                Some(expected) if !sha256(path)?.eq_ignore_ascii_case(expected) => {
+                    println!(
+                        "Checksum of {} is wrong, downloading it again",
+                        path.display()
+                    );
+                }
+                _ => return Ok(()),
+            }
+        }
+
+        let checksum = download(url, path)?;
+        match expected {
+            Some(expected) if !checksum.eq_ignore_ascii_case(expected) => {
+                std::fs::remove_file(path)?;
+                Err(format!("Checksum of {} doesn't match, got {}", url, checksum).into())
+            }
+            Some(_) => Ok(()),
+            None => {
+                println!("Checksum of {}: {}", path.display(), checksum);
+                Ok(())
+            }
+        }
+    }
+}
+
+/**
+ * Models can be given by name instead of path, a name has no directory and no file extension
+ **/
+fn is_name(model: &str, extension: &str) -> bool {
+    !model.contains('/') && !model.contains('\\') && !model.ends_with(extension)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/**
+ * Download the file and return its sha256. It is first written to a .part file,
+ * so a cancelled download isn't mistaken for a complete one on the next start.
+ **/
+fn download(url: &str, path: &Path) -> Result<String, Box<dyn Error>> {
+    println!("Downloading {}...", url);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let response = ureq::get(url).call()?;
+    let total: Option<u64> = response
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok());
+
+    let part = PathBuf::from(format!("{}.part", path.display()));
+    let mut file = File::create(&part)?;
+    let mut reader = response.into_reader();
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut downloaded = 0;
+    let mut reported = 0;
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        hasher.update(&buffer[..read]);
+        downloaded += read as u64;
+
+        if let Some(total) = total {
+            let percent = downloaded * 100 / total.max(1);
+            if percent >= reported + 10 {
+                println!("  {}%", percent);
+                reported = percent;
+            }
+        }
+    }
+
+    file.flush()?;
+    std::fs::rename(&part, path)?;
+    Ok(hex(&hasher.finalize()))
+}
@@ -0,0 +1,147 @@
+use ollama_rs::generation::chat::ChatMessage;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::llm::{GenerationOptions, LlmBackend};
+
+/**
+ * Moderation related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct ModerationConfig {
+    #[serde(default)]
+    pub moderation_rules: Vec<ModerationRule>, // Checked on every sentence of an answer before it is spoken
+    #[serde(default)]
+    pub moderation_model: Option<String>, // Model that reads every answer before it is spoken, the answer is only spoken once it is complete
+    #[serde(default = "default_moderation_prompt")]
+    pub moderation_prompt: String, // Instruction for the moderation model, it has to answer SAFE or UNSAFE
+    #[serde(default = "default_moderation_message")]
+    pub moderation_message: String, // Said instead of a blocked answer
+}
+
+/**
+ * A regex rule, sentences matching it are rewritten or, without a replacement, block the rest of the answer
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModerationRule {
+    pub pattern: String, // Case doesn't matter, e.g. "\\b(kill|murder)\\b"
+    #[serde(default)]
+    pub replace: Option<String>, // What the match is replaced with, $1 refers to a group
+}
+
+fn default_moderation_prompt() -> String {
+    "You check what a voice assistant for a family with children is about to say. \
+    Answer UNSAFE if it contains violence, sexual content, insults, drugs or anything else not meant for children, \
+    otherwise answer SAFE. Answer with one word only."
+        .to_string()
+}
+
+fn default_moderation_message() -> String {
+    "Sorry, I'd rather not talk about that.".to_string()
+}
+
+pub struct Moderator {
+    rules: Vec<(Regex, Option<String>)>,
+    classifier: Option<(Box<dyn LlmBackend>, String)>,
+    prompt: String,
+    pub message: String,
+}
+
+impl Moderator {
+    /**
+     * The backend is only used when a moderation model is set
+     **/
+    pub fn new(cfg: &ModerationConfig, backend: Box<dyn LlmBackend>) -> Self {
+        let rules = cfg
+            .moderation_rules
+            .iter()
+            .map(|rule| {
+                let regex = RegexBuilder::new(&rule.pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .expect("Invalid moderation rule");
+                (regex, rule.replace.clone())
+            })
+            .collect();
+
+        Self {
+            rules,
+            classifier: cfg.moderation_model.clone().map(|model| (backend, model)),
+            prompt: cfg.moderation_prompt.clone(),
+            message: cfg.moderation_message.clone(),
+        }
+    }
+
+    /**
+     * Answers have to be complete before the moderation model can read them
+     **/
+    pub fn waits_for_answer(&self) -> bool {
+        self.classifier.is_some()
+    }
+
+    /**
+     * Applies the rules to a sentence, None if it is blocked
+     **/
+    pub fn rewrite(&self, sentence: &str) -> Option<String> {
+        let mut sentence = sentence.to_string();
+        for (regex, replace) in &self.rules {
+            match replace {
+                Some(replace) => sentence = regex.replace_all(&sentence, replace).to_string(),
+                None if regex.is_match(&sentence) => {
+                    println!("Moderation: blocked \"{}\"", sentence);
+                    return None;
+                }
+                None => {}
+            }
+        }
+        Some(sentence)
+    }
+
+    /**
+     * Asks the moderation model whether the answer may be spoken, blocks it when the model fails
+     **/
+    pub async fn allowed(&self, answer: &str) -> bool {
+        let Some((backend, model)) = &self.classifier else {
+            return true;
+        };
+
+        let messages = vec![
+            ChatMessage::system(self.prompt.clone()),
+            ChatMessage::user(answer.to_string()),
+        ];
+        let options = GenerationOptions {
+            temperature: Some(0.0),
+            ..Default::default()
+        };
+
+        let mut reply = String::new();
+        let mut receiver = backend.stream_chat(model, messages, Vec::new(), &options);
+        while let Some(chunk) = receiver.recv().await {
+            match chunk {
+                Ok(delta) => {
+                    reply.push_str(&delta.content);
+                    if delta.done {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Moderation failed: {}", err);
+                    return false;
+                }
+            }
+        }
+
+        // Thinking models put their verdict after the thoughts
+        let verdict = match reply.rfind("</think>") {
+            Some(i) => &reply[i + 8..],
+            None => &reply,
+        }
+        .to_uppercase();
+
+        let safe = verdict.contains("SAFE") && !verdict.contains("UNSAFE");
+        if !safe {
+            println!("Moderation: {} blocked the answer", model);
+        }
+        safe
+    }
+}
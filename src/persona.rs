@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::tts::style::VoiceStyle;
+
+/**
+ * A character the bot can become, everything not set stays as configured
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Persona {
+    #[serde(default)]
+    pub system: Option<String>, // System prompt of this persona
+    #[serde(default)]
+    pub model: Option<String>, // Model used for this persona
+    #[serde(default)]
+    pub temperature: Option<f32>, // Higher is more creative, lower more focused
+    #[serde(default)]
+    pub voice: VoiceStyle, // How the persona sounds, e.g. {"speaker": 2, "speech_rate": 1.1}
+}
+
+/**
+ * Persona related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct PersonaConfig {
+    #[serde(default)]
+    pub personas: HashMap<String, Persona>, // Personas that can be switched to by name, e.g. "sarcastic_friend"
+    #[serde(default)]
+    pub persona: Option<String>, // Persona the bot starts as, otherwise the normal system prompt is used
+}
+
+/**
+ * Names are compared without case and punctuation, "Sarcastic Friend" is the same as "sarcastic_friend"
+ **/
+fn simplify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/**
+ * Returns the name of the persona the user wants to switch to,
+ * e.g. "Switch to the sarcastic friend" or "Become the helpful assistant"
+ **/
+pub fn persona_command<'a>(text: &str, personas: &'a HashMap<String, Persona>) -> Option<&'a str> {
+    let regex = Regex::new(r"(?i)\b(?:switch to|change to|become)\b(.*)").unwrap();
+    let wanted = format!(" {} ", simplify(&regex.captures(text)?[1]));
+
+    personas
+        .keys()
+        .filter(|name| wanted.contains(&format!(" {} ", simplify(name))))
+        .max_by_key(|name| name.len())
+        .map(String::as_str)
+}
@@ -0,0 +1,59 @@
+use regex::Regex;
+
+use crate::tts::Speech;
+use crate::tts::style::VoiceStyle;
+
+// Saying it slower uses this much of the speech rate it was said with
+const SLOWER: f32 = 0.75;
+// Longer sentences are questions about the answer, not a request to repeat it
+const MAX_WORDS: usize = 8;
+
+/**
+ * A sentence the bot said, its audio is kept to say it again without synthesizing it again
+ **/
+#[derive(Clone)]
+pub struct Said {
+    pub style: VoiceStyle,
+    pub text: String,
+    pub speech: Speech,
+}
+
+impl Said {
+    /**
+     * The style and text to synthesize the sentence again, but slower
+     **/
+    pub fn slower(&self) -> (VoiceStyle, String) {
+        let mut style = self.style.clone();
+        style.speech_rate = Some(style.speech_rate.unwrap_or(1.0) * SLOWER);
+        (style, self.text.clone())
+    }
+}
+
+pub enum Repeat {
+    Again,
+    Slower,
+}
+
+/**
+ * Whether the user asks for the last answer again, e.g. "Say that again" or "Pardon?",
+ * and whether it should be slower, e.g. "Can you repeat that slowly?"
+ **/
+pub fn repeat_command(text: &str) -> Option<Repeat> {
+    if text.split_whitespace().count() > MAX_WORDS {
+        return None;
+    }
+
+    let again = Regex::new(
+        r"(?i)\b(?:say (?:that|it|this) again|repeat (?:that|it|this|yourself|please)|what did you (?:just )?say|come again|pardon)\b",
+    )
+    .unwrap();
+    let slower = Regex::new(r"(?i)\b(?:say|repeat)\b.*\b(?:slower|slowly)\b").unwrap();
+
+    if slower.is_match(text) {
+        Some(Repeat::Slower)
+    } else if again.is_match(text) {
+        Some(Repeat::Again)
+    } else {
+        None
+    }
+}
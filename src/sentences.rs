@@ -0,0 +1,62 @@
+/**
+ * Byte index right after the first sentence, None if no sentence is complete yet.
+ * A sentence ends with ".", "!" or "?" followed by whitespace, or with a line break.
+ * Numbers like "3.5" are not split.
+ **/
+fn sentence_end(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '\n' => true,
+            '.' | '!' | '?' => chars.peek().is_some_and(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if end {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+/**
+ * Collects a response that arrives piece by piece and hands out every sentence as soon as
+ * it is complete, so the first one can already be spoken while the rest is still being
+ * generated. "Wait." might still become "Wait...", so a sentence is only complete once the
+ * whitespace after it arrived.
+ **/
+#[derive(Default)]
+pub struct SentenceStream {
+    buffer: String,
+}
+
+impl SentenceStream {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        self.buffer.push_str(text);
+
+        let mut sentences = Vec::new();
+        while let Some(end) = sentence_end(&self.buffer) {
+            let sentence: String = self.buffer.drain(..end).collect();
+            let sentence = sentence.trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+        }
+        sentences
+    }
+
+    /**
+     * The rest of the response once it is complete
+     **/
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = rest.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
@@ -0,0 +1,33 @@
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * Settings changed by voice while the bot runs. The config is compiled into the program,
+ * so they are saved to their own file, which is read at startup and overrides the config.
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speech_rate: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tts_volume: Option<f32>,
+}
+
+impl Settings {
+    /**
+     * Returns no changes if the file doesn't exist yet
+     **/
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+/**
+ * Groups speaker embeddings into speakers while the assistant is running.
+ * Each speaker is represented by the sum of all its embeddings, a new
+ * embedding belongs to the most similar speaker if it is similar enough.
+ **/
+pub struct SpeakerClusters {
+    speakers: Vec<Vec<f32>>, // Sum of the normalized embeddings of every speaker
+    threshold: f32,
+    max_speakers: usize,
+}
+
+fn normalize(embedding: &[f32]) -> Vec<f32> {
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return embedding.to_vec();
+    }
+    embedding.iter().map(|x| x / norm).collect()
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+impl SpeakerClusters {
+    pub fn new(threshold: f32, max_speakers: usize) -> Self {
+        Self {
+            speakers: Vec::new(),
+            threshold,
+            max_speakers: max_speakers.max(1),
+        }
+    }
+
+    /**
+     * The number of the speaker this embedding belongs to, starting at 1
+     **/
+    pub fn assign(&mut self, embedding: &[f32]) -> usize {
+        let embedding = normalize(embedding);
+
+        let best = self
+            .speakers
+            .iter()
+            .map(|sum| cosine_similarity(sum, &embedding))
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let index = match best {
+            Some((index, similarity)) if similarity >= self.threshold => index,
+            // Nobody sounds like this yet, unless there are already too many speakers
+            Some((index, _)) if self.speakers.len() >= self.max_speakers => index,
+            _ => {
+                self.speakers.push(vec![0.0; embedding.len()]);
+                self.speakers.len() - 1
+            }
+        };
+
+        let sum = &mut self.speakers[index];
+        sum.iter_mut().zip(&embedding).for_each(|(s, x)| *s += x);
+
+        index + 1
+    }
+}
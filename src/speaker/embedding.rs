@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use ort::session::Session;
+use ort::value::Tensor;
+
+use crate::speaker::Diarizer;
+use crate::speaker::cluster::SpeakerClusters;
+use crate::speaker::fbank::{MEL_BINS, fbank};
+
+/**
+ * Computes a speaker embedding with a WeSpeaker model, voices of the same person
+ * end up close to each other. Exported models can be downloaded from
+ * https://github.com/wenet-e2e/wespeaker/blob/master/docs/pretrained.md
+ **/
+pub struct SpeakerEmbedder {
+    session: Session,
+}
+
+impl SpeakerEmbedder {
+    pub fn new(model: &Path) -> Self {
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_file(model))
+            .expect("Failed to load speaker embedding model");
+
+        Self { session }
+    }
+
+    /**
+     * The embedding of 16kHz audio, None if it is too short
+     **/
+    pub fn embed(&mut self, samples: &[f32]) -> ort::Result<Option<Vec<f32>>> {
+        let features = fbank(samples);
+        if features.is_empty() {
+            return Ok(None);
+        }
+
+        let frames = features.len();
+        let inputs = ort::inputs![
+            "feats" => Tensor::from_array(([1, frames, MEL_BINS], features.concat()))?,
+        ]?;
+
+        let outputs = self.session.run(inputs)?;
+        let (_, embedding) = outputs["embs"].try_extract_raw_tensor::<f32>()?;
+
+        Ok(Some(embedding.to_vec()))
+    }
+}
+
+/**
+ * Tells speakers apart by clustering the embedding of every utterance
+ **/
+pub struct EmbeddingDiarizer {
+    embedder: SpeakerEmbedder,
+    clusters: SpeakerClusters,
+}
+
+impl EmbeddingDiarizer {
+    pub fn new(model: &Path, threshold: f32, max_speakers: usize) -> Self {
+        Self {
+            embedder: SpeakerEmbedder::new(model),
+            clusters: SpeakerClusters::new(threshold, max_speakers),
+        }
+    }
+}
+
+impl Diarizer for EmbeddingDiarizer {
+    fn identify(&mut self, samples: &[f32]) -> Option<usize> {
+        match self.embedder.embed(samples) {
+            Ok(embedding) => embedding.map(|embedding| self.clusters.assign(&embedding)),
+            Err(err) => {
+                eprintln!("Speaker embedding failed: {}", err);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::f32::consts::PI;
+
+/**
+ * Kaldi style filterbank features at 16kHz, which is what most speaker embedding
+ * models (e.g. WeSpeaker) are trained on: 25ms frames every 10ms, 80 mel bins
+ **/
+const FRAME_LEN: usize = 400;
+const FRAME_SHIFT: usize = 160;
+const FFT_LEN: usize = 512;
+pub const MEL_BINS: usize = 80;
+
+const SAMPLE_RATE: f32 = 16_000.0;
+const LOW_FREQ: f32 = 20.0;
+const PREEMPHASIS: f32 = 0.97;
+
+fn mel(freq: f32) -> f32 {
+    1127.0 * (1.0 + freq / 700.0).ln()
+}
+
+/**
+ * Triangular filters that are evenly spaced on the mel scale,
+ * each one is a weight for every bin of the power spectrum
+ **/
+fn mel_filters() -> Vec<Vec<f32>> {
+    let low = mel(LOW_FREQ);
+    let high = mel(SAMPLE_RATE / 2.0);
+    let delta = (high - low) / (MEL_BINS + 1) as f32;
+
+    (0..MEL_BINS)
+        .map(|bin| {
+            let left = low + bin as f32 * delta;
+            let center = left + delta;
+            let right = center + delta;
+
+            (0..FFT_LEN / 2 + 1)
+                .map(|i| {
+                    let m = mel(i as f32 * SAMPLE_RATE / FFT_LEN as f32);
+                    if m > left && m <= center {
+                        (m - left) / (center - left)
+                    } else if m > center && m < right {
+                        (right - m) / (right - center)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/**
+ * In place radix 2 FFT, the length has to be a power of two
+ **/
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    // Bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/**
+ * Log mel filterbank energies of 16kHz audio, one row of MEL_BINS values per frame.
+ * The mean of every bin is subtracted, so the loudness and the microphone matter less.
+ **/
+pub fn fbank(samples: &[f32]) -> Vec<Vec<f32>> {
+    if samples.len() < FRAME_LEN {
+        return Vec::new();
+    }
+
+    let filters = mel_filters();
+    let window: Vec<f32> = (0..FRAME_LEN)
+        .map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / (FRAME_LEN - 1) as f32).cos()).powf(0.85))
+        .collect();
+
+    let frames = 1 + (samples.len() - FRAME_LEN) / FRAME_SHIFT;
+    let mut features: Vec<Vec<f32>> = Vec::with_capacity(frames);
+
+    for frame in 0..frames {
+        // The models expect 16 bit integer amplitudes
+        let start = frame * FRAME_SHIFT;
+        let mut re: Vec<f32> = samples[start..start + FRAME_LEN]
+            .iter()
+            .map(|sample| sample * 32768.0)
+            .collect();
+
+        let mean = re.iter().sum::<f32>() / FRAME_LEN as f32;
+        re.iter_mut().for_each(|sample| *sample -= mean);
+
+        for i in (1..FRAME_LEN).rev() {
+            re[i] -= PREEMPHASIS * re[i - 1];
+        }
+        re[0] -= PREEMPHASIS * re[0];
+
+        re.iter_mut()
+            .zip(&window)
+            .for_each(|(sample, w)| *sample *= w);
+        re.resize(FFT_LEN, 0.0);
+        let mut im = vec![0.0; FFT_LEN];
+        fft(&mut re, &mut im);
+
+        let power: Vec<f32> = (0..FFT_LEN / 2 + 1)
+            .map(|i| re[i] * re[i] + im[i] * im[i])
+            .collect();
+
+        features.push(
+            filters
+                .iter()
+                .map(|filter| {
+                    let energy: f32 = filter.iter().zip(&power).map(|(w, p)| w * p).sum();
+                    energy.max(f32::EPSILON).ln()
+                })
+                .collect(),
+        );
+    }
+
+    for bin in 0..MEL_BINS {
+        let mean = features.iter().map(|row| row[bin]).sum::<f32>() / frames as f32;
+        features.iter_mut().for_each(|row| row[bin] -= mean);
+    }
+
+    features
+}
@@ -0,0 +1,68 @@
+#[cfg(feature = "diarization")]
+pub mod cluster;
+#[cfg(feature = "diarization")]
+pub mod embedding;
+#[cfg(feature = "diarization")]
+pub mod fbank;
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * A diarizer finds out who is talking, so the LLM can tell people in the room apart.
+ * The samples are always mono f32 at 16kHz.
+ **/
+pub trait Diarizer: Send {
+    /**
+     * The number of the speaker of this utterance starting at 1, None if unknown
+     **/
+    fn identify(&mut self, samples: &[f32]) -> Option<usize>;
+}
+
+/**
+ * Speaker diarization related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct SpeakerConfig {
+    #[serde(default)]
+    pub diarization: bool, // Prefix transcriptions with "Speaker 1:", "Speaker 2:" and so on
+    #[serde(default)]
+    pub speaker_model: Option<String>, // Path to the WeSpeaker onnx model
+    #[serde(default = "default_speaker_threshold")]
+    pub speaker_threshold: f32, // How similar two voices have to be to count as the same speaker, from 0 to 1
+    #[serde(default = "default_max_speakers")]
+    pub max_speakers: usize, // Once there are this many speakers, new voices are given to the most similar one
+}
+
+fn default_speaker_threshold() -> f32 {
+    0.5
+}
+
+fn default_max_speakers() -> usize {
+    4
+}
+
+pub fn create_diarizer(cfg: &SpeakerConfig) -> Option<Box<dyn Diarizer>> {
+    if cfg.diarization {
+        Some(create_backend(cfg))
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "diarization")]
+fn create_backend(cfg: &SpeakerConfig) -> Box<dyn Diarizer> {
+    let model = cfg
+        .speaker_model
+        .as_deref()
+        .expect("speaker_model must be set to use diarization");
+    Box::new(embedding::EmbeddingDiarizer::new(
+        std::path::Path::new(model),
+        cfg.speaker_threshold,
+        cfg.max_speakers,
+    ))
+}
+
+#[cfg(not(feature = "diarization"))]
+fn create_backend(_cfg: &SpeakerConfig) -> Box<dyn Diarizer> {
+    panic!("Diarization requires building with --features diarization")
+}
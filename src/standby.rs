@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hotword::contains_hotword;
+
+/**
+ * Standby related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StandbyConfig {
+    #[serde(default)]
+    pub standby_after: Option<u64>, // Minutes without talking until the bot goes to standby, never by default
+    #[serde(default = "default_standby_words")]
+    pub standby_words: Vec<String>, // Saying one of them sends the bot to standby right away
+    #[serde(default = "default_wake_words")]
+    pub wake_words: Vec<String>, // Saying one of them in standby wakes the bot up again
+}
+
+impl Default for StandbyConfig {
+    fn default() -> Self {
+        Self {
+            standby_after: None,
+            standby_words: default_standby_words(),
+            wake_words: default_wake_words(),
+        }
+    }
+}
+
+fn default_standby_words() -> Vec<String> {
+    vec!["go to standby".to_string()]
+}
+
+fn default_wake_words() -> Vec<String> {
+    vec!["wake up".to_string()]
+}
+
+/**
+ * In standby Whisper and the voice are unloaded to free memory and only the voice
+ * activity detection runs. Whisper is only loaded for a moment to check what was said
+ * for a wake word.
+ **/
+pub struct Standby {
+    cfg: StandbyConfig,
+    hotword_distance: Option<usize>,
+    last_activity: Instant,
+    active: bool,
+}
+
+impl Standby {
+    pub fn new(cfg: &StandbyConfig, hotword_distance: Option<usize>) -> Self {
+        Self {
+            cfg: cfg.clone(),
+            hotword_distance,
+            last_activity: Instant::now(),
+            active: false,
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /**
+     * Something was said or announced, so the bot is still needed
+     **/
+    pub fn activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /**
+     * Whether the bot wasn't needed for long enough to go to standby
+     **/
+    pub fn due(&self) -> bool {
+        !self.active
+            && self.cfg.standby_after.is_some_and(|minutes| {
+                self.last_activity.elapsed() > Duration::from_secs(minutes * 60)
+            })
+    }
+
+    pub fn requested(&self, prompt: &str) -> bool {
+        self.contains_any(prompt, &self.cfg.standby_words)
+    }
+
+    pub fn wakes(&self, prompt: &str) -> bool {
+        self.contains_any(prompt, &self.cfg.wake_words)
+    }
+
+    pub fn enter(&mut self) {
+        println!("Standby, say {:?} to wake me up", self.cfg.wake_words);
+        self.active = true;
+    }
+
+    pub fn leave(&mut self) {
+        println!("Woke up from standby");
+        self.active = false;
+        self.activity();
+    }
+
+    fn contains_any(&self, prompt: &str, words: &[String]) -> bool {
+        words
+            .iter()
+            .any(|word| contains_hotword(prompt, word, self.hotword_distance))
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::Duration;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/**
+ * Statistics related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct StatsConfig {
+    #[serde(default)]
+    pub stats: bool, // Print how long every step took and how many tokens were used after every answer
+    #[serde(default)]
+    pub stats_file: Option<String>, // Append the statistics of every answer as a JSON line to this file, e.g. "stats.jsonl"
+}
+
+/**
+ * Where the time of one answer went and how much of the context it used
+ **/
+#[derive(Serialize, Default)]
+pub struct TurnStats {
+    pub stt_ms: u64,                     // Transcribing what was said
+    pub llm_first_token_ms: Option<u64>, // Until the model started answering
+    pub llm_ms: u64,                     // Until the model finished answering, including tools
+    pub tts_ms: u64,                     // Synthesizing all sentences
+    pub prompt_tokens: u64,              // Size of the conversation the model read
+    pub response_tokens: u64,
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+pub struct Stats {
+    print: bool,
+    file: Option<String>,
+    turns: u64,
+    total: TurnStats,
+}
+
+impl Stats {
+    pub fn new(cfg: &StatsConfig) -> Self {
+        Self {
+            print: cfg.stats,
+            file: cfg.stats_file.clone(),
+            turns: 0,
+            total: TurnStats::default(),
+        }
+    }
+
+    /**
+     * Print the statistics of the answer together with the averages so far and write them to the file
+     **/
+    pub fn record(&mut self, turn: &TurnStats) {
+        self.turns += 1;
+        self.total.stt_ms += turn.stt_ms;
+        self.total.llm_ms += turn.llm_ms;
+        self.total.tts_ms += turn.tts_ms;
+        self.total.response_tokens += turn.response_tokens;
+
+        if self.print {
+            let first_token = turn
+                .llm_first_token_ms
+                .map(|ms| format!(" (first token {}ms)", ms))
+                .unwrap_or_default();
+            println!(
+                "Stats: stt {}ms, llm {}ms{}, tts {}ms, {} prompt tokens, {} response tokens",
+                turn.stt_ms,
+                turn.llm_ms,
+                first_token,
+                turn.tts_ms,
+                turn.prompt_tokens,
+                turn.response_tokens
+            );
+            println!(
+                "Average of {} answers: stt {}ms, llm {}ms, tts {}ms, {} response tokens",
+                self.turns,
+                self.total.stt_ms / self.turns,
+                self.total.llm_ms / self.turns,
+                self.total.tts_ms / self.turns,
+                self.total.response_tokens / self.turns
+            );
+        }
+
+        if let Some(path) = &self.file
+            && let Err(err) = append_line(path, turn)
+        {
+            eprintln!("Failed to write statistics to {}: {}", path, err);
+        }
+    }
+}
+
+fn append_line(path: &str, turn: &TurnStats) -> Result<(), Box<dyn std::error::Error>> {
+    let mut line = serde_json::to_value(turn)?;
+    line["time"] = Local::now().to_rfc3339().into();
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+pub mod whisper;
+
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stt::whisper::{WhisperConfig, WhisperStt};
+
+/**
+ * Sample rate the audio passed to the engines has
+ **/
+pub const SAMPLE_RATE: usize = 16_000;
+
+/**
+ * A single word and when it was said, times are in milliseconds from the start of the audio
+ **/
+pub struct Word {
+    pub text: String,
+    pub start: u64,
+    pub end: u64,
+    pub probability: f32, // From 0 to 1, how sure the engine is about the least certain part of the word
+}
+
+/**
+ * A part of the transcription, usually a sentence, together with when it was said
+ **/
+pub struct Segment {
+    pub text: String,
+    pub start: u64,
+    pub end: u64,
+    pub words: Vec<Word>, // Empty if the engine doesn't know when each word was said
+}
+
+/**
+ * What the speech to text engine understood
+ **/
+pub struct Transcription {
+    pub text: String,
+    pub confidence: f32, // From 0 to 1, how sure the engine is about the text
+    pub language: Option<String>,
+    pub segments: Vec<Segment>, // Empty if the engine doesn't provide timestamps
+}
+
+fn srt_time(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+impl Transcription {
+    /**
+     * The segments as SRT subtitles
+     **/
+    pub fn to_srt(&self) -> String {
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                format!(
+                    "{}\n{} --> {}\n{}\n\n",
+                    index + 1,
+                    srt_time(segment.start),
+                    srt_time(segment.end),
+                    segment.text
+                )
+            })
+            .collect()
+    }
+}
+
+/**
+ * A speech to text engine turns recorded speech into text.
+ * The samples are always mono f32 at SAMPLE_RATE.
+ **/
+pub trait SttEngine: Send {
+    fn transcribe(
+        &mut self,
+        samples: &[f32],
+    ) -> Result<Transcription, Box<dyn Error + Send + Sync>>;
+
+    /**
+     * Transcribe again more carefully after the user said they were misunderstood,
+     * the language is a hint which language was spoken
+     **/
+    fn retranscribe(
+        &mut self,
+        samples: &[f32],
+        _language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error + Send + Sync>> {
+        self.transcribe(samples)
+    }
+
+    /**
+     * Other guesses of what was said, used when the engine wasn't sure. The language is a
+     * hint which language was spoken. Engines that only have one guess return none.
+     **/
+    fn alternatives(
+        &mut self,
+        _samples: &[f32],
+        _count: usize,
+        _language: Option<&str>,
+    ) -> Result<Vec<Transcription>, Box<dyn Error + Send + Sync>> {
+        Ok(Vec::new())
+    }
+}
+
+/**
+ * The available speech to text engines, selected with `stt` in the config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SttKind {
+    #[default]
+    Whisper,
+}
+
+/**
+ * Speech to text related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SttConfig {
+    #[serde(default)]
+    pub stt: SttKind, // Which speech to text engine to use, currently only "whisper"
+    #[serde(default)]
+    pub hotwords: Vec<String>, // Words the engine should be more likely to understand, e.g. the name of the bot
+
+    #[serde(flatten)]
+    pub whisper: WhisperConfig,
+}
+
+/**
+ * Create the configured engine, the hotwords are added to the ones from the config
+ **/
+pub fn create_stt(cfg: &SttConfig, hotwords: &[String]) -> Box<dyn SttEngine> {
+    let hotwords = [cfg.hotwords.as_slice(), hotwords].concat();
+
+    match cfg.stt {
+        SttKind::Whisper => Box::new(WhisperStt::new(cfg.whisper.clone(), &hotwords)),
+    }
+}
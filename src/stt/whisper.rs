@@ -0,0 +1,441 @@
+use std::error::Error;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+    WhisperToken,
+};
+
+use crate::stt::{Segment, SttEngine, Transcription, Word};
+
+/**
+ * Whisper related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WhisperConfig {
+    pub whisper: String, // Path to the Whisper model
+
+    #[serde(default = "default_language")]
+    pub language: String, // Language spoken to Whisper, e.g. "en", "de" or "auto" to detect it
+    #[serde(default)]
+    pub whisper_translate: bool, // Let Whisper translate what was said to English
+    #[serde(default = "default_true")]
+    pub whisper_gpu: bool, // Use the GPU for Whisper, needs one of the cuda, metal, vulkan or hipblas features
+    #[serde(default)]
+    pub whisper_gpu_device: i32, // Which GPU to use if there are multiple
+    #[serde(default)]
+    pub initial_prompt: Option<String>, // Text Whisper sees before the audio, helps with names and rare words
+    #[serde(default = "default_no_speech_threshold")]
+    pub no_speech_threshold: f32, // Segments Whisper thinks are silence with more than this probability are dropped, if it was unsure about them too
+    #[serde(default = "default_no_speech_logprob")]
+    pub no_speech_logprob: f32, // Unsure means an average log probability of the words below this, higher drops more
+    #[serde(default)]
+    pub n_threads: Option<i32>, // CPU threads used by Whisper, uses up to 4 by default
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_no_speech_threshold() -> f32 {
+    0.6
+}
+
+fn default_no_speech_logprob() -> f32 {
+    -1.0
+}
+
+/**
+ * Whisper transcribes the audio in windows of 30 seconds, in units of 10 milliseconds
+ **/
+const WINDOW: i64 = 3000;
+
+/**
+ * The text of the given segments
+ **/
+fn full_text(state: &WhisperState, kept: &[i32]) -> String {
+    kept.iter()
+        .filter_map(|&segment| state.full_get_segment_text(segment).ok())
+        .collect()
+}
+
+/**
+ * Whisper timestamps are in units of 10 milliseconds
+ **/
+fn to_ms(timestamp: i64) -> u64 {
+    timestamp.max(0) as u64 * 10
+}
+
+/**
+ * The segments Whisper found together with the words in them.
+ * Whisper works with tokens which are often only parts of a word,
+ * a token starting with a space starts a new word.
+ **/
+fn segments(ctx: &WhisperContext, state: &WhisperState, kept: &[i32]) -> Vec<Segment> {
+    let eot = ctx.token_eot();
+
+    kept.iter()
+        .map(|&segment| {
+            let mut words: Vec<Word> = Vec::new();
+            for token in 0..state.full_n_tokens(segment).unwrap_or(0) {
+                let Ok(data) = state.full_get_token_data(segment, token) else {
+                    continue;
+                };
+                // Special tokens like timestamps come after the end of text token
+                if data.id >= eot {
+                    continue;
+                }
+                let text = state
+                    .full_get_token_text_lossy(segment, token)
+                    .unwrap_or_default();
+
+                match words.last_mut() {
+                    Some(word) if !text.starts_with(' ') => {
+                        word.text.push_str(&text);
+                        word.end = to_ms(data.t1);
+                        word.probability = word.probability.min(data.p);
+                    }
+                    _ => words.push(Word {
+                        text: text.trim_start().to_string(),
+                        start: to_ms(data.t0),
+                        end: to_ms(data.t1),
+                        probability: data.p,
+                    }),
+                }
+            }
+
+            Segment {
+                text: state
+                    .full_get_segment_text_lossy(segment)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+                start: to_ms(state.full_get_segment_t0(segment).unwrap_or(0)),
+                end: to_ms(state.full_get_segment_t1(segment).unwrap_or(0)),
+                words,
+            }
+        })
+        .collect()
+}
+
+/**
+ * Average probability of all text tokens, a low value means Whisper was just guessing
+ **/
+fn confidence(ctx: &WhisperContext, state: &WhisperState, kept: &[i32]) -> f32 {
+    let eot = ctx.token_eot();
+    let mut sum = 0.0;
+    let mut count = 0;
+
+    for &segment in kept {
+        for token in 0..state.full_n_tokens(segment).unwrap_or(0) {
+            // Special tokens like timestamps come after the end of text token
+            if state
+                .full_get_token_id(segment, token)
+                .is_ok_and(|id| id < eot)
+            {
+                sum += state.full_get_token_prob(segment, token).unwrap_or(0.0);
+                count += 1;
+            }
+        }
+    }
+
+    if count == 0 { 0.0 } else { sum / count as f32 }
+}
+
+/**
+ * Average log probability of the text tokens of a segment
+ **/
+fn average_logprob(ctx: &WhisperContext, state: &WhisperState, segment: i32) -> f32 {
+    let eot = ctx.token_eot();
+    let logprobs: Vec<f32> = (0..state.full_n_tokens(segment).unwrap_or(0))
+        .filter_map(|token| state.full_get_token_data(segment, token).ok())
+        .filter(|data| data.id < eot)
+        .map(|data| data.plog)
+        .collect();
+
+    if logprobs.is_empty() {
+        0.0
+    } else {
+        logprobs.iter().sum::<f32>() / logprobs.len() as f32
+    }
+}
+
+/**
+ * Probability of the given token from the logits of the model
+ **/
+fn probability(logits: &[f32], token: WhisperToken) -> f32 {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = logits.iter().map(|logit| (logit - max).exp()).sum();
+    (logits[token as usize] - max).exp() / sum
+}
+
+/**
+ * Remove annotations like [Music] or (laughing) that Whisper adds for sounds
+ **/
+fn remove_annotations(text: &str) -> String {
+    let regex = Regex::new(r"\[[^\]]*\]|\([^)]*\)").unwrap();
+    regex.replace_all(text, "").trim().to_string()
+}
+
+/**
+ * Speech to text using whisper.cpp.
+ * The state holds large buffers, so it is only created once and reused for every utterance.
+ **/
+pub struct WhisperStt {
+    ctx: WhisperContext,
+    state: WhisperState,
+    cfg: WhisperConfig,
+    prompt: Option<String>,
+}
+
+impl WhisperStt {
+    /**
+     * Whisper is more likely to understand the hotwords if they are part of the initial prompt
+     **/
+    pub fn new(cfg: WhisperConfig, hotwords: &[String]) -> Self {
+        let mut params = WhisperContextParameters::default();
+        params
+            .use_gpu(cfg.whisper_gpu)
+            .gpu_device(cfg.whisper_gpu_device);
+
+        let ctx =
+            WhisperContext::new_with_params(&cfg.whisper, params).expect("failed to load model");
+        let state = ctx.create_state().expect("failed to create Whisper state");
+
+        let mut prompt = cfg.initial_prompt.clone().unwrap_or_default();
+        if !hotwords.is_empty() {
+            prompt = format!("{} {}.", prompt, hotwords.join(", "));
+        }
+        let prompt = Some(prompt.trim().to_string()).filter(|prompt| !prompt.is_empty());
+
+        Self {
+            ctx,
+            state,
+            cfg,
+            prompt,
+        }
+    }
+}
+
+impl WhisperStt {
+    fn run(
+        &mut self,
+        samples: &[f32],
+        strategy: SamplingStrategy,
+        language: &str,
+        temperature: f32,
+    ) -> Result<Transcription, Box<dyn Error + Send + Sync>> {
+        let mut params = FullParams::new(strategy);
+        params.set_temperature(temperature);
+        // Don't let the text of the previous utterance leak into this one
+        params.set_no_context(true);
+        params.set_language(Some(language));
+        params.set_translate(self.cfg.whisper_translate);
+        // whisper.cpp drops a whole window as silence only if both thresholds are crossed,
+        // single segments are checked the same way afterwards
+        params.set_no_speech_thold(self.cfg.no_speech_threshold);
+        params.set_logprob_thold(self.cfg.no_speech_logprob);
+        params.set_token_timestamps(true);
+        if let Some(prompt) = &self.prompt {
+            params.set_initial_prompt(prompt);
+        }
+        params.set_n_threads(self.threads());
+
+        // Results of the previous run are cleared by whisper.cpp
+        self.state.full(params, samples)?;
+
+        let lang_id = if language == "auto" {
+            self.state.full_lang_id_from_state().ok()
+        } else {
+            whisper_rs::get_lang_id(language)
+        };
+        let kept = self.speech_segments(lang_id)?;
+
+        let language = if language == "auto" {
+            lang_id.and_then(whisper_rs::get_lang_str).map(String::from)
+        } else {
+            Some(language.to_string())
+        };
+
+        Ok(Transcription {
+            text: remove_annotations(&full_text(&self.state, &kept)),
+            confidence: confidence(&self.ctx, &self.state, &kept),
+            language,
+            segments: segments(&self.ctx, &self.state, &kept),
+        })
+    }
+
+    /**
+     * Whisper uses up to 4 threads by default
+     **/
+    fn threads(&self) -> i32 {
+        self.cfg.n_threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |n| n.get().min(4) as i32)
+        })
+    }
+
+    /**
+     * Probability that the 30 seconds starting at offset are silence, which whisper.cpp
+     * doesn't keep. The audio is encoded again and the start of a transcription decoded,
+     * one token at a time since only the logits of the last token are kept.
+     **/
+    fn no_speech_probability(
+        &mut self,
+        offset: i64,
+        lang_id: Option<i32>,
+    ) -> Result<f32, Box<dyn Error + Send + Sync>> {
+        let threads = self.threads() as usize;
+        self.state.encode(offset.max(0) as usize, threads)?;
+
+        let mut prompt = vec![self.ctx.token_sot()];
+        if self.ctx.is_multilingual() {
+            prompt.push(self.ctx.token_lang(lang_id.unwrap_or(0)));
+            prompt.push(if self.cfg.whisper_translate {
+                self.ctx.token_translate()
+            } else {
+                self.ctx.token_transcribe()
+            });
+        }
+        for (n_past, token) in prompt.iter().enumerate() {
+            self.state.decode(&[*token], n_past, threads)?;
+        }
+
+        Ok(probability(self.state.get_logits()?, self.ctx.token_nosp()))
+    }
+
+    /**
+     * The segments that are not dropped as silence. Segments starting in the window of an
+     * earlier segment share its probability, so usually the audio is only encoded once more.
+     **/
+    fn speech_segments(
+        &mut self,
+        lang_id: Option<i32>,
+    ) -> Result<Vec<i32>, Box<dyn Error + Send + Sync>> {
+        let segments = self.state.full_n_segments()?;
+        if self.cfg.no_speech_threshold >= 1.0 {
+            return Ok((0..segments).collect());
+        }
+
+        let mut kept = Vec::new();
+        let mut window: Option<(i64, f32)> = None;
+        for segment in 0..segments {
+            let start = self.state.full_get_segment_t0(segment)?;
+            let no_speech = match window {
+                Some((window_start, no_speech)) if start < window_start + WINDOW => no_speech,
+                _ => {
+                    let no_speech = self.no_speech_probability(start, lang_id)?;
+                    window = Some((start, no_speech));
+                    no_speech
+                }
+            };
+
+            if no_speech > self.cfg.no_speech_threshold
+                && average_logprob(&self.ctx, &self.state, segment) < self.cfg.no_speech_logprob
+            {
+                println!(
+                    "Dropped \"{}\" as silence",
+                    self.state
+                        .full_get_segment_text_lossy(segment)
+                        .unwrap_or_default()
+                        .trim()
+                );
+            } else {
+                kept.push(segment);
+            }
+        }
+        Ok(kept)
+    }
+}
+
+impl SttEngine for WhisperStt {
+    fn transcribe(
+        &mut self,
+        samples: &[f32],
+    ) -> Result<Transcription, Box<dyn Error + Send + Sync>> {
+        let language = self.cfg.language.clone();
+        self.run(
+            samples,
+            SamplingStrategy::Greedy { best_of: 3 },
+            &language,
+            0.0,
+        )
+    }
+
+    /**
+     * Beam search is slower but makes fewer mistakes, and detecting the wrong
+     * language is a common reason for nonsense, so the hint is used if there is one
+     **/
+    fn retranscribe(
+        &mut self,
+        samples: &[f32],
+        language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error + Send + Sync>> {
+        let language = language.unwrap_or(&self.cfg.language).to_string();
+        let strategy = SamplingStrategy::BeamSearch {
+            beam_size: 5,
+            patience: -1.0,
+        };
+        self.run(samples, strategy, &language, 0.0)
+    }
+
+    /**
+     * The first alternative comes from beam search, the others are sampled at
+     * higher temperatures, so Whisper picks less likely words too
+     **/
+    fn alternatives(
+        &mut self,
+        samples: &[f32],
+        count: usize,
+        language: Option<&str>,
+    ) -> Result<Vec<Transcription>, Box<dyn Error + Send + Sync>> {
+        let mut alternatives = Vec::new();
+        if count == 0 {
+            return Ok(alternatives);
+        }
+        alternatives.push(self.retranscribe(samples, language)?);
+
+        let language = language.unwrap_or(&self.cfg.language).to_string();
+        for index in 1..count {
+            let temperature = (0.2 + 0.2 * index as f32).min(1.0);
+            let strategy = SamplingStrategy::Greedy { best_of: 1 };
+            alternatives.push(self.run(samples, strategy, &language, temperature)?);
+        }
+        Ok(alternatives)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_annotations() {
+        assert_eq!(remove_annotations("[Music]"), "");
+        assert_eq!(
+            remove_annotations(" Hello (laughing) there."),
+            "Hello  there."
+        );
+        assert_eq!(
+            remove_annotations("[BLANK_AUDIO] What time is it? [Music]"),
+            "What time is it?"
+        );
+        assert_eq!(
+            remove_annotations("No annotations here"),
+            "No annotations here"
+        );
+    }
+
+    #[test]
+    fn probability_is_softmax() {
+        let logits = [1.0, 2.0, 3.0];
+        let sum: f32 = (0..3).map(|token| probability(&logits, token)).sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        assert!((probability(&logits, 2) - 0.66524).abs() < 1e-4);
+        assert_eq!(probability(&[f32::NEG_INFINITY, 0.0], 0), 0.0);
+    }
+}
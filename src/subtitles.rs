@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/**
+ * Subtitle related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct SubtitleConfig {
+    #[serde(default)]
+    pub subtitles: bool, // Print every sentence of the response when it is spoken
+    #[serde(default)]
+    pub subtitles_file: Option<String>, // File that always contains the sentence being spoken, e.g. for an OBS text source
+}
+
+/**
+ * Shows what the bot is saying while it says it
+ **/
+pub struct Subtitles {
+    print: bool,
+    file: Option<PathBuf>,
+}
+
+impl Subtitles {
+    pub fn new(cfg: &SubtitleConfig) -> Self {
+        let subtitles = Self {
+            print: cfg.subtitles,
+            file: cfg.subtitles_file.as_ref().map(PathBuf::from),
+        };
+        subtitles.clear();
+        subtitles
+    }
+
+    pub fn show(&self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.print {
+            println!("Subtitle: {}", text);
+        }
+        self.write(text);
+    }
+
+    /**
+     * Empty the file once the bot stopped talking
+     **/
+    pub fn clear(&self) {
+        self.write("");
+    }
+
+    fn write(&self, text: &str) {
+        if let Some(file) = &self.file
+            && let Err(err) = std::fs::write(file, text)
+        {
+            eprintln!("Failed to write subtitles to {}: {}", file.display(), err);
+        }
+    }
+}
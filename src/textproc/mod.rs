@@ -0,0 +1,319 @@
+mod numbers;
+
+use std::collections::HashSet;
+
+use regex::{Captures, Regex};
+
+use numbers::{number, ordinal, year};
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/**
+ * Abbreviations and what is said instead
+ **/
+const ABBREVIATIONS: [(&str, &str); 12] = [
+    (r"\bDr\.", "Doctor"),
+    (r"\bMr\.", "Mister"),
+    (r"\bMrs\.", "Missus"),
+    (r"\bMs\.", "Miss"),
+    (r"\bProf\.", "Professor"),
+    (r"\bSt\.", "Saint"),
+    (r"\bJr\.", "Junior"),
+    (r"\bvs\.", "versus"),
+    (r"\be\.g\.", "for example"),
+    (r"\bi\.e\.", "that is"),
+    (r"\betc\.", "et cetera."),
+    (r"\bapprox\.", "approximately"),
+];
+
+/**
+ * Units that follow a number, with how they are said for one and for many
+ **/
+const UNITS: [(&str, &str, &str); 20] = [
+    ("km/h", "kilometer per hour", "kilometers per hour"),
+    ("mph", "mile per hour", "miles per hour"),
+    ("m/s", "meter per second", "meters per second"),
+    ("°C", "degree Celsius", "degrees Celsius"),
+    ("°F", "degree Fahrenheit", "degrees Fahrenheit"),
+    ("°", "degree", "degrees"),
+    ("%", "percent", "percent"),
+    ("km", "kilometer", "kilometers"),
+    ("cm", "centimeter", "centimeters"),
+    ("mm", "millimeter", "millimeters"),
+    ("m", "meter", "meters"),
+    ("kg", "kilogram", "kilograms"),
+    ("g", "gram", "grams"),
+    ("ml", "milliliter", "milliliters"),
+    ("l", "liter", "liters"),
+    ("min", "minute", "minutes"),
+    ("h", "hour", "hours"),
+    ("s", "second", "seconds"),
+    ("GB", "gigabyte", "gigabytes"),
+    ("MB", "megabyte", "megabytes"),
+];
+
+const CURRENCIES: [(char, &str, &str, &str); 3] = [
+    ('$', "dollar", "dollars", "cents"),
+    ('€', "euro", "euros", "cents"),
+    ('£', "pound", "pounds", "pence"),
+];
+
+/**
+ * A number like "1,234,567" or "3.5"
+ **/
+const NUMBER: &str = r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?";
+
+fn plural<'a>(amount: &str, one: &'a str, many: &'a str) -> &'a str {
+    if amount == "1" { one } else { many }
+}
+
+/**
+ * Only the domain of a link is read, e.g. "https://www.example.com/page" is "example dot com"
+ **/
+fn urls(text: &str) -> String {
+    let regex =
+        Regex::new(r"\b(?:https?://|www\.)(?:www\.)?([\w-]+(?:\.[\w-]+)+)(?:/\S*[^\s.,!?;:)])?/?")
+            .unwrap();
+    regex
+        .replace_all(text, |caps: &Captures| caps[1].replace('.', " dot "))
+        .to_string()
+}
+
+fn abbreviations(text: &str) -> String {
+    let mut text = text.to_string();
+    for (abbreviation, words) in ABBREVIATIONS {
+        let regex = Regex::new(abbreviation).unwrap();
+        text = regex.replace_all(&text, words).to_string();
+    }
+    text
+}
+
+/**
+ * Dates like "2024-05-03" are read as "May third twenty twenty-four"
+ **/
+fn dates(text: &str) -> String {
+    let regex = Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b").unwrap();
+    regex
+        .replace_all(text, |caps: &Captures| {
+            let year_number: u64 = caps[1].parse().unwrap();
+            let month: usize = caps[2].parse().unwrap();
+            let day: u64 = caps[3].parse().unwrap();
+            match MONTHS.get(month.wrapping_sub(1)) {
+                Some(month) if (1..=31).contains(&day) => {
+                    format!("{} {} {}", month, ordinal(day), year(year_number))
+                }
+                _ => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/**
+ * Times like "14:30" are read as "fourteen thirty", "9:05" as "nine oh five" and "10:00" as "ten o'clock"
+ **/
+fn times(text: &str) -> String {
+    let regex = Regex::new(r"\b(\d{1,2}):(\d{2})\b").unwrap();
+    regex
+        .replace_all(text, |caps: &Captures| {
+            let hours = number(&caps[1]);
+            match &caps[2] {
+                "00" => format!("{} o'clock", hours),
+                minutes if minutes.starts_with('0') => {
+                    format!("{} oh {}", hours, number(&minutes[1..]))
+                }
+                minutes => format!("{} {}", hours, number(minutes)),
+            }
+        })
+        .to_string()
+}
+
+/**
+ * Amounts like "$3.50" are read as "3 dollars 50"
+ **/
+fn currencies(text: &str) -> String {
+    let mut text = text.to_string();
+    for (symbol, one, many, cents) in CURRENCIES {
+        let regex = Regex::new(&format!(
+            r"{}\s?({})",
+            regex::escape(&symbol.to_string()),
+            NUMBER
+        ))
+        .unwrap();
+        text = regex
+            .replace_all(&text, |caps: &Captures| {
+                let amount = &caps[1];
+                match amount.split_once('.') {
+                    Some((whole, "00")) => format!("{} {}", whole, plural(whole, one, many)),
+                    Some((whole, fraction)) if fraction.len() == 2 => {
+                        let fraction = fraction.trim_start_matches('0');
+                        format!(
+                            "{} {} {} {}",
+                            whole,
+                            plural(whole, one, many),
+                            fraction,
+                            cents
+                        )
+                    }
+                    _ => format!("{} {}", amount, plural(amount, one, many)),
+                }
+            })
+            .to_string();
+    }
+    text
+}
+
+/**
+ * Units after a number are spelled out, e.g. "5 km/h" is "5 kilometers per hour"
+ **/
+fn units(text: &str) -> String {
+    let symbols: Vec<String> = UNITS
+        .iter()
+        .map(|(symbol, _, _)| regex::escape(symbol))
+        .collect();
+    let regex = Regex::new(&format!(r"({})\s?({})(\W|$)", NUMBER, symbols.join("|"))).unwrap();
+
+    regex
+        .replace_all(text, |caps: &Captures| {
+            let (_, one, many) = UNITS
+                .iter()
+                .find(|(symbol, _, _)| *symbol == &caps[2])
+                .unwrap();
+            format!("{} {}{}", &caps[1], plural(&caps[1], one, many), &caps[3])
+        })
+        .to_string()
+}
+
+/**
+ * Numbers are spelled out, "1st" is "first", "-3" is "minus three" and
+ * numbers that look like a year, e.g. "1999", are read like one
+ **/
+fn numbers(text: &str) -> String {
+    let ordinals = Regex::new(r"\b(\d+)(?:st|nd|rd|th)\b").unwrap();
+    let text = ordinals.replace_all(text, |caps: &Captures| match caps[1].parse() {
+        Ok(n) => ordinal(n),
+        Err(_) => caps[0].to_string(),
+    });
+
+    let negatives = Regex::new(r"(^|[\s(])-(\d)").unwrap();
+    let text = negatives.replace_all(&text, "${1}minus $2");
+
+    let numbers = Regex::new(&format!(r"\b(?:{})\b", NUMBER)).unwrap();
+    numbers
+        .replace_all(&text, |caps: &Captures| {
+            let n = &caps[0];
+            match n.parse() {
+                Ok(n) if (1100..2100).contains(&n) => year(n),
+                _ => number(n),
+            }
+        })
+        .to_string()
+}
+
+/**
+ * Rewrite a response the way it should be said, so Piper doesn't have to guess
+ * how to read numbers, dates, units, abbreviations and links
+ **/
+pub fn normalize(text: &str) -> String {
+    let text = urls(text);
+    let text = abbreviations(&text);
+    let text = dates(&text);
+    let text = times(&text);
+    let text = currencies(&text);
+    let text = units(&text);
+    let text = numbers(&text);
+    text.replace('&', " and ").replace("  ", " ")
+}
+
+/**
+ * The lowercase words of a text that are long enough to mean something
+ **/
+pub fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(cases: &[(&str, &str)]) {
+        for (text, said) in cases {
+            assert_eq!(normalize(text), *said, "normalizing {:?}", text);
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        check(&[
+            ("I have 3 apples", "I have three apples"),
+            ("It is 0 now", "It is zero now"),
+            ("42", "forty-two"),
+            (
+                "1,234,567",
+                "one million two hundred thirty-four thousand five hundred sixty-seven",
+            ),
+            ("-3 degrees", "minus three degrees"),
+            ("1999", "nineteen ninety-nine"),
+            ("2024", "twenty twenty-four"),
+            ("He came 1st and 22nd", "He came first and twenty-second"),
+        ]);
+    }
+
+    #[test]
+    fn decimals() {
+        check(&[
+            ("3.5", "three point five"),
+            ("0.25", "zero point two five"),
+            ("pi is 3.14", "pi is three point one four"),
+            ("€3.50", "three euros fifty cents"),
+        ]);
+    }
+
+    #[test]
+    fn units() {
+        check(&[
+            ("It's 5 km/h", "It's five kilometers per hour"),
+            ("1 km", "one kilometer"),
+            ("20°C", "twenty degrees Celsius"),
+            ("50%", "fifty percent"),
+            ("2.5 kg", "two point five kilograms"),
+            ("1 h", "one hour"),
+            ("100 MB", "one hundred megabytes"),
+        ]);
+    }
+
+    #[test]
+    fn abbreviations() {
+        check(&[
+            ("Dr. Smith", "Doctor Smith"),
+            ("Mr. Jones and Mrs. Jones", "Mister Jones and Missus Jones"),
+            ("Prof. X", "Professor X"),
+            ("e.g. this", "for example this"),
+        ]);
+    }
+
+    #[test]
+    fn words_are_lowercase_and_long_enough() {
+        let found = words("The Dentist, at 10 o'clock!");
+        let expected: HashSet<String> = ["the", "dentist", "clock"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,144 @@
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+fn below_hundred(n: u64) -> String {
+    match n {
+        0..20 => ONES[n as usize].to_string(),
+        _ if n.is_multiple_of(10) => TENS[(n / 10) as usize].to_string(),
+        _ => format!("{}-{}", TENS[(n / 10) as usize], ONES[(n % 10) as usize]),
+    }
+}
+
+fn below_thousand(n: u64) -> String {
+    match (n / 100, n % 100) {
+        (0, rest) => below_hundred(rest),
+        (hundreds, 0) => format!("{} hundred", ONES[hundreds as usize]),
+        (hundreds, rest) => format!(
+            "{} hundred {}",
+            ONES[hundreds as usize],
+            below_hundred(rest)
+        ),
+    }
+}
+
+/**
+ * Spell out a number, e.g. 1234 is "one thousand two hundred thirty-four"
+ **/
+pub fn cardinal(mut n: u64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut words = Vec::new();
+    for (scale, name) in SCALES {
+        if n >= scale {
+            words.push(format!("{} {}", below_thousand(n / scale), name));
+            n %= scale;
+        }
+    }
+    if n > 0 {
+        words.push(below_thousand(n));
+    }
+    words.join(" ")
+}
+
+/**
+ * Spell out the position, e.g. 21 is "twenty-first"
+ **/
+pub fn ordinal(n: u64) -> String {
+    let words = cardinal(n);
+    let split = words.rfind([' ', '-']).map(|i| i + 1).unwrap_or(0);
+    let (head, last) = words.split_at(split);
+
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        last if last.ends_with('y') => format!("{}ieth", &last[..last.len() - 1]),
+        last => format!("{}th", last),
+    };
+    format!("{}{}", head, last)
+}
+
+/**
+ * Spell out a year the way it is spoken, e.g. 1999 is "nineteen ninety-nine"
+ * and 2005 is "two thousand five"
+ **/
+pub fn year(n: u64) -> String {
+    let (century, rest) = (n / 100, n % 100);
+    match rest {
+        _ if !(1100..10_000).contains(&n) || (2000..2010).contains(&n) => cardinal(n),
+        0 => format!("{} hundred", below_hundred(century)),
+        1..10 => format!("{} oh {}", below_hundred(century), ONES[rest as usize]),
+        _ => format!("{} {}", below_hundred(century), below_hundred(rest)),
+    }
+}
+
+/**
+ * Spell out every digit on its own, e.g. "05" is "zero five"
+ **/
+pub fn digits(s: &str) -> String {
+    s.chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|digit| ONES[digit as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/**
+ * Spell out a number like "1,234" or "3.5", numbers too big to be said are read digit by digit
+ **/
+pub fn number(s: &str) -> String {
+    let (whole, fraction) = match s.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (s, None),
+    };
+
+    let whole = whole.replace(',', "");
+    let mut words = match whole.parse() {
+        Ok(n) => cardinal(n),
+        Err(_) => digits(&whole),
+    };
+
+    if let Some(fraction) = fraction {
+        words = format!("{} point {}", words, digits(fraction));
+    }
+    words
+}
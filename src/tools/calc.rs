@@ -0,0 +1,283 @@
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/**
+ * The AI can use this tool to calculate things, small models are bad at arithmetic.
+ * The expression is parsed by hand, so nothing but math can be run.
+ **/
+pub struct CalcTool {}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "The expression to calculate, e.g. \"18% of 243\" or \"sqrt(2) * (3 + 4)^2\". Supports + - * / ^ %, parentheses, pi, e and the functions sqrt, abs, round, floor, ceil, ln, log, sin, cos, tan."
+    )]
+    expression: String,
+}
+
+impl Tool for CalcTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "calculator"
+    }
+
+    fn description() -> &'static str {
+        "Calculates the result of a math expression, always use it instead of calculating yourself."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let result = evaluate(&parameters.expression)?;
+        println!("CalcTool: {} = {}", parameters.expression, result);
+        Ok(format!("{} = {}", parameters.expression, result))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                number.push(c);
+                chars.next();
+            }
+            let number = number
+                .parse()
+                .map_err(|_| format!("{} is not a number", number))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric()) {
+                name.push(c);
+                chars.next();
+            }
+            // "18% of 243" and "3 x 4" are common ways to say multiply
+            match name.to_lowercase().as_str() {
+                "of" | "x" | "times" => tokens.push(Token::Op('*')),
+                name => tokens.push(Token::Name(name.to_string())),
+            }
+        } else {
+            let op = match c {
+                '×' | '·' => '*',
+                '÷' | ':' => '/',
+                '−' => '-',
+                '+' | '-' | '*' | '/' | '^' | '%' | '(' | ')' => c,
+                _ => return Err(format!("Unknown symbol {}", c)),
+            };
+            tokens.push(Token::Op(op));
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+/**
+ * A recursive descent parser that calculates while parsing
+ **/
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    // expression = term (("+" | "-") term)*
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') {
+                value -= self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // term = unary (("*" | "/") unary)*
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                value /= self.unary()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // unary = "-" unary | power
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    // power = percent ("^" unary)?
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.percent()?;
+        if self.eat('^') {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    // percent = primary "%"*
+    fn percent(&mut self) -> Result<f64, String> {
+        let mut value = self.primary()?;
+        while self.eat('%') {
+            value /= 100.0;
+        }
+        Ok(value)
+    }
+
+    // primary = number | "(" expression ")" | constant | function "(" expression ")"
+    fn primary(&mut self) -> Result<f64, String> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(number)) => Ok(number),
+            Some(Token::Op('(')) => {
+                let value = self.expression()?;
+                if !self.eat(')') {
+                    return Err("Missing )".to_string());
+                }
+                Ok(value)
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "pi" => Ok(std::f64::consts::PI),
+                "e" => Ok(std::f64::consts::E),
+                _ => {
+                    let function: fn(f64) -> f64 = match name.as_str() {
+                        "sqrt" => f64::sqrt,
+                        "abs" => f64::abs,
+                        "round" => f64::round,
+                        "floor" => f64::floor,
+                        "ceil" => f64::ceil,
+                        "ln" => f64::ln,
+                        "log" => f64::log10,
+                        "sin" => f64::sin,
+                        "cos" => f64::cos,
+                        "tan" => f64::tan,
+                        _ => return Err(format!("Unknown function {}", name)),
+                    };
+                    Ok(function(self.primary()?))
+                }
+            },
+            Some(Token::Op(op)) => Err(format!("Unexpected {}", op)),
+            None => Err("The expression ended too early".to_string()),
+        }
+    }
+}
+
+pub fn evaluate(expression: &str) -> Result<String, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        pos: 0,
+    };
+    let value = parser.expression()?;
+    if let Some(token) = parser.peek() {
+        let token = match token {
+            Token::Number(number) => number.to_string(),
+            Token::Name(name) => name.clone(),
+            Token::Op(op) => op.to_string(),
+        };
+        return Err(format!(
+            "Unexpected {} after the end of the expression",
+            token
+        ));
+    }
+    if !value.is_finite() {
+        return Err("The result is not a number, e.g. because of a division by zero".to_string());
+    }
+
+    // Hide floating point noise like 0.30000000000000004
+    if value.abs() < 1e12 {
+        return Ok(((value * 1e10).round() / 1e10).to_string());
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("2 + 3 * 4").unwrap(), "14");
+        assert_eq!(evaluate("(2 + 3) * 4").unwrap(), "20");
+        assert_eq!(evaluate("10 - 4 - 3").unwrap(), "3");
+        assert_eq!(evaluate("2 * 3 ^ 2").unwrap(), "18");
+        assert_eq!(evaluate("2 ^ 3 ^ 2").unwrap(), "512");
+        assert_eq!(evaluate("18% of 243").unwrap(), "43.74");
+        assert_eq!(evaluate("0.1 + 0.2").unwrap(), "0.3");
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(evaluate("-3 + 5").unwrap(), "2");
+        assert_eq!(evaluate("2 * -3").unwrap(), "-6");
+        assert_eq!(evaluate("--3").unwrap(), "3");
+        assert_eq!(evaluate("-2 ^ 2").unwrap(), "-4");
+        assert_eq!(evaluate("2 ^ -1").unwrap(), "0.5");
+        assert_eq!(evaluate("-(1 + 2)").unwrap(), "-3");
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("0 / 0").is_err());
+        assert!(evaluate("5 / (2 - 2)").is_err());
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(
+            evaluate("2 +").unwrap_err(),
+            "The expression ended too early"
+        );
+        assert_eq!(evaluate("(2 + 3").unwrap_err(), "Missing )");
+        assert_eq!(evaluate("").unwrap_err(), "The expression ended too early");
+        assert_eq!(evaluate("1.2.3").unwrap_err(), "1.2.3 is not a number");
+        assert_eq!(evaluate("2 $ 3").unwrap_err(), "Unknown symbol $");
+        assert_eq!(evaluate("foo(2)").unwrap_err(), "Unknown function foo");
+        assert_eq!(evaluate("* 2").unwrap_err(), "Unexpected *");
+        assert_eq!(
+            evaluate("2 3").unwrap_err(),
+            "Unexpected 3 after the end of the expression"
+        );
+    }
+}
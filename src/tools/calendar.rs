@@ -0,0 +1,388 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use ollama_rs::generation::tools::Tool;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+// How often the calendar is fetched again to warn about upcoming events
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/**
+ * Calendar related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CalendarConfig {
+    #[serde(default)]
+    pub calendar: Option<String>, // Enables the calendar tool, an ICS file, a link to one or a CalDAV calendar
+    #[serde(default)]
+    pub calendar_user: Option<String>, // User name for the link or CalDAV server
+    #[serde(default)]
+    pub calendar_password: Option<String>, // Password for the link or CalDAV server, falls back to the CALENDAR_PASSWORD environment variable
+    #[serde(default = "default_calendar_warning")]
+    pub calendar_warning: Option<u64>, // Say this many minutes before an event starts that it is coming up, null to turn off
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            calendar: None,
+            calendar_user: None,
+            calendar_password: None,
+            calendar_warning: default_calendar_warning(),
+        }
+    }
+}
+
+fn default_calendar_warning() -> Option<u64> {
+    Some(15)
+}
+
+impl CalendarConfig {
+    /**
+     * Minutes before an event it is announced, None without a calendar
+     **/
+    pub fn warnings(&self) -> Option<u64> {
+        self.calendar.as_ref().and(self.calendar_warning)
+    }
+}
+
+/**
+ * One event of the calendar, recurring events are repeated by their rule
+ **/
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub summary: String,
+    pub location: Option<String>,
+    pub start: NaiveDateTime, // Local time
+    pub duration: TimeDelta,
+    pub all_day: bool,
+    rule: Option<String>,
+}
+
+impl Event {
+    fn describe(&self, start: NaiveDateTime) -> String {
+        let time = if self.all_day {
+            format!("{} all day", start.format("%A %Y-%m-%d"))
+        } else if self.duration.is_zero() {
+            start.format("%A %Y-%m-%d %H:%M").to_string()
+        } else {
+            format!(
+                "{} to {}",
+                start.format("%A %Y-%m-%d %H:%M"),
+                (start + self.duration).format("%H:%M")
+            )
+        };
+        match &self.location {
+            Some(location) => format!("{}: {} at {}", time, self.summary, location),
+            None => format!("{}: {}", time, self.summary),
+        }
+    }
+
+    /**
+     * What is said when the event is about to start
+     **/
+    pub fn announcement(&self) -> String {
+        let minutes = (self.start - Local::now().naive_local()).num_minutes();
+        if minutes <= 0 {
+            format!("{} is starting now.", self.summary)
+        } else {
+            format!("{} starts in {} minutes.", self.summary, minutes)
+        }
+    }
+
+    /**
+     * The starts of all occurrences that overlap with the given time span
+     **/
+    fn occurrences(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let overlaps =
+            |start: NaiveDateTime| start < to && (start >= from || start + self.duration > from);
+
+        let Some(rule) = &self.rule else {
+            return [self.start].into_iter().filter(|s| overlaps(*s)).collect();
+        };
+
+        let part = |name: &str| {
+            rule.split(';')
+                .find_map(|part| part.strip_prefix(name)?.strip_prefix('='))
+        };
+        let interval: u32 = part("INTERVAL").and_then(|i| i.parse().ok()).unwrap_or(1);
+        let count: Option<usize> = part("COUNT").and_then(|c| c.parse().ok());
+        let until = part("UNTIL").and_then(|until| parse_date_time(until, "").map(|(u, _)| u));
+        let next = |start: NaiveDateTime| match part("FREQ") {
+            Some("DAILY") => start.checked_add_signed(TimeDelta::days(interval as i64)),
+            Some("WEEKLY") => start.checked_add_signed(TimeDelta::weeks(interval as i64)),
+            Some("MONTHLY") => start.checked_add_months(Months::new(interval)),
+            Some("YEARLY") => start.checked_add_months(Months::new(12 * interval)),
+            _ => None,
+        };
+
+        let mut starts = Vec::new();
+        let mut start = Some(self.start);
+        let mut n = 0;
+        while let Some(current) = start {
+            if current >= to
+                || count.is_some_and(|count| n >= count)
+                || until.is_some_and(|until| current > until)
+            {
+                break;
+            }
+            if overlaps(current) {
+                starts.push(current);
+            }
+            n += 1;
+            start = next(current);
+        }
+        starts
+    }
+}
+
+/**
+ * Dates are all day events, times in UTC end with a Z and everything else is taken as local time
+ **/
+fn parse_date_time(value: &str, params: &str) -> Option<(NaiveDateTime, bool)> {
+    if (params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME")) || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((date.and_time(NaiveTime::MIN), true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        let local = Utc.from_utc_datetime(&utc).with_timezone(&Local);
+        return Some((local.naive_local(), false));
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((local, false))
+}
+
+/**
+ * Reads the events out of an iCalendar file, long lines are folded onto lines starting with a space
+ **/
+pub fn parse_ics(ics: &str) -> Vec<Event> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    let mut end = None;
+    let mut nested = 0; // Inside an alarm or similar of the event
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+        let value = value
+            .replace("\\n", " ")
+            .replace("\\,", ",")
+            .replace("\\;", ";");
+
+        match (name, event.as_mut()) {
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            (_, Some(_)) if nested > 0 => {}
+            ("BEGIN", None) if value == "VEVENT" => {
+                event = Some(Event {
+                    summary: String::new(),
+                    location: None,
+                    start: NaiveDateTime::MIN,
+                    duration: TimeDelta::zero(),
+                    all_day: false,
+                    rule: None,
+                });
+                end = None;
+            }
+            ("END", Some(current)) if value == "VEVENT" => {
+                if let Some(end) = end {
+                    current.duration = end - current.start;
+                } else if current.all_day {
+                    current.duration = TimeDelta::days(1);
+                }
+                if current.start != NaiveDateTime::MIN {
+                    events.push(event.take().unwrap());
+                }
+                event = None;
+            }
+            ("SUMMARY", Some(current)) => current.summary = value,
+            ("LOCATION", Some(current)) if !value.is_empty() => current.location = Some(value),
+            ("RRULE", Some(current)) => current.rule = Some(value),
+            ("DTSTART", Some(current)) => {
+                if let Some((start, all_day)) = parse_date_time(&value, params) {
+                    current.start = start;
+                    current.all_day = all_day;
+                }
+            }
+            ("DTEND", Some(_)) => end = parse_date_time(&value, params).map(|(end, _)| end),
+            _ => {}
+        }
+    }
+    events
+}
+
+/**
+ * Links that don't return an iCalendar file are asked for their events the CalDAV way
+ **/
+fn fetch(cfg: &CalendarConfig) -> Result<Vec<Event>, Box<dyn std::error::Error + Sync + Send>> {
+    let source = cfg.calendar.as_deref().ok_or("No calendar configured")?;
+    if !source.starts_with("http://") && !source.starts_with("https://") {
+        return Ok(parse_ics(&std::fs::read_to_string(source)?));
+    }
+
+    let password = cfg
+        .calendar_password
+        .clone()
+        .or_else(|| std::env::var("CALENDAR_PASSWORD").ok());
+    let authorize = |request: ureq::Request| match (&cfg.calendar_user, &password) {
+        (Some(user), Some(password)) => {
+            let credentials = STANDARD.encode(format!("{}:{}", user, password));
+            request.set("Authorization", &format!("Basic {}", credentials))
+        }
+        _ => request,
+    };
+
+    let ics = authorize(ureq::get(source)).call()?.into_string()?;
+    if ics.trim_start().starts_with("BEGIN:VCALENDAR") {
+        return Ok(parse_ics(&ics));
+    }
+
+    let query = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+    let response = authorize(ureq::request("REPORT", source))
+        .set("Depth", "1")
+        .set("Content-Type", "application/xml; charset=utf-8")
+        .send_string(query)?
+        .into_string()?;
+
+    let data = Regex::new(r"(?s)<[^>]*calendar-data[^>]*>(.*?)</[^>]*calendar-data>").unwrap();
+    let events = data
+        .captures_iter(&response)
+        .flat_map(|captures| {
+            let ics = captures[1]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&#13;", "")
+                .replace("&amp;", "&");
+            parse_ics(&ics)
+        })
+        .collect();
+    Ok(events)
+}
+
+/**
+ * The AI can use this tool to look at the calendar of the user
+ **/
+pub struct CalendarTool {
+    pub cfg: CalendarConfig,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "The first day to look at as \"YYYY-MM-DD\", today if empty.")]
+    date: Option<String>,
+    #[schemars(description = "How many days to look at, 1 for only that day.")]
+    days: Option<u32>,
+}
+
+impl Tool for CalendarTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "calendar"
+    }
+
+    fn description() -> &'static str {
+        "Returns the events in the calendar of the user, e.g. to answer what is planned for today."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let date = match parameters.date.filter(|date| !date.trim().is_empty()) {
+            Some(date) => NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("{} is not a date like 2025-01-31", date))?,
+            None => Local::now().date_naive(),
+        };
+        let days = parameters.days.unwrap_or(1).clamp(1, 366);
+        println!("CalendarTool: {} for {} days", date, days);
+
+        let cfg = self.cfg.clone();
+        let events = tokio::task::spawn_blocking(move || fetch(&cfg)).await??;
+
+        let from = date.and_time(NaiveTime::MIN);
+        let to = from + TimeDelta::days(days as i64);
+        let mut found: Vec<(NaiveDateTime, String)> = events
+            .iter()
+            .flat_map(|event| {
+                event
+                    .occurrences(from, to)
+                    .into_iter()
+                    .map(|start| (start, event.describe(start)))
+            })
+            .collect();
+        found.sort();
+
+        if found.is_empty() {
+            return Ok("There are no events in the calendar then".to_string());
+        }
+        let found: Vec<String> = found.into_iter().map(|(_, text)| text).collect();
+        Ok(found.join("\n"))
+    }
+}
+
+/**
+ * Check the calendar in the background and send the events that start within the warning time,
+ * every occurrence is only sent once
+ **/
+pub fn spawn_warnings(cfg: CalendarConfig, minutes: u64) -> UnboundedReceiver<Event> {
+    let (sender, receiver) = unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut events = Vec::new();
+        let mut fetched: Option<std::time::Instant> = None;
+        let mut warned = HashSet::new();
+
+        loop {
+            if fetched.is_none_or(|fetched| fetched.elapsed() > REFRESH_INTERVAL) {
+                let cfg = cfg.clone();
+                match tokio::task::spawn_blocking(move || fetch(&cfg)).await {
+                    Ok(Ok(fresh)) => events = fresh,
+                    Ok(Err(err)) => eprintln!("Failed to read the calendar: {}", err),
+                    Err(err) => eprintln!("Failed to read the calendar: {}", err),
+                }
+                fetched = Some(std::time::Instant::now());
+            }
+
+            let now = Local::now().naive_local();
+            let soon = now + TimeDelta::minutes(minutes as i64);
+            warned.retain(|(_, start)| *start >= now);
+            for event in events.iter().filter(|event| !event.all_day) {
+                for start in event.occurrences(now, soon) {
+                    if start >= now && warned.insert((event.summary.clone(), start)) {
+                        let mut occurrence = event.clone();
+                        occurrence.start = start;
+                        if sender.send(occurrence).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        }
+    });
+
+    receiver
+}
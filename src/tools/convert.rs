@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+// The European Central Bank only updates its rates once a day
+const RATES_MAX_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    Length,
+    Mass,
+    Volume,
+    Area,
+    Speed,
+    Temperature,
+}
+
+/**
+ * Every unit with its names and how much of the base unit of its kind it is
+ **/
+const UNITS: &[(Kind, f64, &[&str])] = &[
+    (Kind::Length, 1.0, &["m", "meter", "metre"]),
+    (Kind::Length, 1000.0, &["km", "kilometer", "kilometre"]),
+    (Kind::Length, 0.01, &["cm", "centimeter", "centimetre"]),
+    (Kind::Length, 0.001, &["mm", "millimeter", "millimetre"]),
+    (Kind::Length, 1609.344, &["mi", "mile"]),
+    (Kind::Length, 0.9144, &["yd", "yard"]),
+    (Kind::Length, 0.3048, &["ft", "foot", "feet"]),
+    (Kind::Length, 0.0254, &["in", "inch", "inches"]),
+    (Kind::Length, 1852.0, &["nmi", "nautical mile"]),
+    (Kind::Mass, 1.0, &["kg", "kilogram", "kilo"]),
+    (Kind::Mass, 0.001, &["g", "gram", "gramme"]),
+    (Kind::Mass, 1000.0, &["t", "ton", "tonne", "metric ton"]),
+    (Kind::Mass, 0.45359237, &["lb", "lbs", "pound"]),
+    (Kind::Mass, 0.028349523125, &["oz", "ounce"]),
+    (Kind::Mass, 6.35029318, &["st", "stone"]),
+    (Kind::Volume, 1.0, &["l", "liter", "litre"]),
+    (Kind::Volume, 0.001, &["ml", "milliliter", "millilitre"]),
+    (Kind::Volume, 0.1, &["dl", "deciliter", "decilitre"]),
+    (Kind::Volume, 1000.0, &["m3", "cubic meter", "cubic metre"]),
+    (Kind::Volume, 3.785411784, &["gal", "gallon", "us gallon"]),
+    (Kind::Volume, 4.54609, &["imperial gallon", "uk gallon"]),
+    (Kind::Volume, 0.946352946, &["qt", "quart"]),
+    (Kind::Volume, 0.473176473, &["pt", "pint"]),
+    (Kind::Volume, 0.2365882365, &["cup"]),
+    (Kind::Volume, 0.0295735295625, &["fl oz", "fluid ounce"]),
+    (Kind::Volume, 0.01478676478125, &["tbsp", "tablespoon"]),
+    (Kind::Volume, 0.00492892159375, &["tsp", "teaspoon"]),
+    (
+        Kind::Area,
+        1.0,
+        &["m2", "square meter", "square metre", "sqm"],
+    ),
+    (
+        Kind::Area,
+        1_000_000.0,
+        &["km2", "square kilometer", "square kilometre"],
+    ),
+    (Kind::Area, 10_000.0, &["ha", "hectare"]),
+    (Kind::Area, 4046.8564224, &["acre"]),
+    (
+        Kind::Area,
+        0.09290304,
+        &["ft2", "sq ft", "square foot", "square feet"],
+    ),
+    (
+        Kind::Area,
+        2_589_988.110336,
+        &["mi2", "sq mi", "square mile"],
+    ),
+    (
+        Kind::Speed,
+        1.0,
+        &["m/s", "meters per second", "metres per second"],
+    ),
+    (
+        Kind::Speed,
+        1.0 / 3.6,
+        &[
+            "km/h",
+            "kmh",
+            "kph",
+            "kilometers per hour",
+            "kilometres per hour",
+        ],
+    ),
+    (Kind::Speed, 0.44704, &["mph", "miles per hour"]),
+    (Kind::Speed, 0.514444, &["kn", "knot"]),
+    (Kind::Temperature, 1.0, &["c", "celsius", "centigrade"]),
+    (Kind::Temperature, 1.0, &["f", "fahrenheit"]),
+    (Kind::Temperature, 1.0, &["k", "kelvin"]),
+];
+
+/**
+ * Currency names people say instead of the code
+ **/
+const CURRENCIES: &[(&str, &[&str])] = &[
+    ("USD", &["dollar", "us dollar", "buck"]),
+    ("EUR", &["euro"]),
+    ("GBP", &["pound", "pound sterling", "british pound", "quid"]),
+    ("JPY", &["yen"]),
+    ("CHF", &["franc", "swiss franc"]),
+    ("CNY", &["yuan", "renminbi"]),
+    ("INR", &["rupee", "indian rupee"]),
+    ("CAD", &["canadian dollar"]),
+    ("AUD", &["australian dollar"]),
+    ("SEK", &["swedish krona", "swedish crown"]),
+    ("NOK", &["norwegian krone", "norwegian crown"]),
+    ("DKK", &["danish krone", "danish crown"]),
+    ("PLN", &["zloty"]),
+    ("CZK", &["czech koruna", "czech crown"]),
+];
+
+/**
+ * The AI can use this tool to convert units and currencies, the exchange rates come from
+ * the European Central Bank through frankfurter.app and are cached for a few hours
+ **/
+#[derive(Default)]
+pub struct ConvertTool {
+    rates: Option<(Instant, HashMap<String, f64>)>, // Euros are worth this much of every currency
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "The amount to convert.")]
+    value: f64,
+    #[schemars(
+        description = "The unit or currency to convert from, e.g. \"miles\", \"lb\", \"fahrenheit\", \"EUR\" or \"dollars\"."
+    )]
+    from: String,
+    #[schemars(description = "The unit or currency to convert to.")]
+    to: String,
+}
+
+impl Tool for ConvertTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "convert"
+    }
+
+    fn description() -> &'static str {
+        "Converts between units of length, weight, volume, area, speed and temperature, and between currencies."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        println!(
+            "ConvertTool: {} {} to {}",
+            parameters.value, parameters.from, parameters.to
+        );
+
+        let result = match (unit(&parameters.from), unit(&parameters.to)) {
+            (Some(from), Some(to)) if from.0 == to.0 => convert_unit(parameters.value, from, to),
+            _ => {
+                let (Some(from), Some(to)) = (currency(&parameters.from), currency(&parameters.to))
+                else {
+                    return Err(
+                        format!("Can't convert {} to {}", parameters.from, parameters.to).into(),
+                    );
+                };
+                let rates = self.rates().await?;
+                let rate = |code: &str| {
+                    rates
+                        .get(code)
+                        .copied()
+                        .ok_or_else(|| format!("There is no exchange rate for {}", code))
+                };
+                parameters.value / rate(&from)? * rate(&to)?
+            }
+        };
+
+        Ok(format!(
+            "{} {} = {} {}",
+            parameters.value,
+            parameters.from,
+            round(result),
+            parameters.to
+        ))
+    }
+}
+
+impl ConvertTool {
+    async fn rates(
+        &mut self,
+    ) -> Result<&HashMap<String, f64>, Box<dyn std::error::Error + Sync + Send>> {
+        let outdated = self
+            .rates
+            .as_ref()
+            .is_none_or(|(fetched, _)| fetched.elapsed() > RATES_MAX_AGE);
+        if outdated {
+            let rates = tokio::task::spawn_blocking(fetch_rates).await??;
+            self.rates = Some((Instant::now(), rates));
+        }
+        Ok(&self.rates.as_ref().unwrap().1)
+    }
+}
+
+fn fetch_rates() -> Result<HashMap<String, f64>, Box<dyn std::error::Error + Sync + Send>> {
+    let response = ureq::get("https://api.frankfurter.app/latest").call()?;
+    let response: Value = serde_json::from_reader(response.into_reader())?;
+
+    let mut rates: HashMap<String, f64> = response["rates"]
+        .as_object()
+        .ok_or("No exchange rates received")?
+        .iter()
+        .filter_map(|(code, rate)| Some((code.clone(), rate.as_f64()?)))
+        .collect();
+    rates.insert("EUR".to_string(), 1.0);
+    Ok(rates)
+}
+
+/**
+ * Lowercase without degree signs and plurals, so "Degrees Fahrenheit" and "miles" are found
+ **/
+fn names(name: &str) -> Vec<String> {
+    let name = name
+        .to_lowercase()
+        .replace("degrees", "")
+        .replace("degree", "")
+        .replace('°', "")
+        .replace("²", "2")
+        .replace("³", "3")
+        .trim()
+        .to_string();
+
+    let mut names = vec![name.clone()];
+    if let Some(singular) = name.strip_suffix("es") {
+        names.push(singular.to_string());
+    }
+    if let Some(singular) = name.strip_suffix('s') {
+        names.push(singular.to_string());
+    }
+    names
+}
+
+fn unit(name: &str) -> Option<(Kind, f64, &'static str)> {
+    names(name).iter().find_map(|name| {
+        UNITS
+            .iter()
+            .find(|(_, _, names)| names.contains(&name.as_str()))
+            .map(|(kind, factor, names)| (*kind, *factor, names[0]))
+    })
+}
+
+fn currency(name: &str) -> Option<String> {
+    let trimmed = name.trim();
+    if trimmed.len() == 3 && trimmed.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Some(trimmed.to_uppercase());
+    }
+
+    names(name).iter().find_map(|name| {
+        CURRENCIES
+            .iter()
+            .find(|(_, names)| names.contains(&name.as_str()))
+            .map(|(code, _)| code.to_string())
+    })
+}
+
+fn convert_unit(value: f64, from: (Kind, f64, &str), to: (Kind, f64, &str)) -> f64 {
+    if from.0 != Kind::Temperature {
+        return value * from.1 / to.1;
+    }
+
+    let celsius = match from.2 {
+        "f" => (value - 32.0) * 5.0 / 9.0,
+        "k" => value - 273.15,
+        _ => value,
+    };
+    match to.2 {
+        "f" => celsius * 9.0 / 5.0 + 32.0,
+        "k" => celsius + 273.15,
+        _ => celsius,
+    }
+}
+
+/**
+ * Five significant digits are more than enough to be spoken
+ **/
+fn round(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let decimals = (4 - value.abs().log10().floor() as i32).clamp(0, 10) as usize;
+    let text = format!("{:.*}", decimals, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/**
+ * Document related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DocumentsConfig {
+    #[serde(default)]
+    pub documents_dir: Option<String>, // Enables the document tool, only files in this directory can be read
+    #[serde(default = "default_documents_chunk_chars")]
+    pub documents_chunk_chars: usize, // How many characters the model gets per part of a document
+}
+
+impl Default for DocumentsConfig {
+    fn default() -> Self {
+        Self {
+            documents_dir: None,
+            documents_chunk_chars: default_documents_chunk_chars(),
+        }
+    }
+}
+
+fn default_documents_chunk_chars() -> usize {
+    3000
+}
+
+/**
+ * Split the text into parts of at most max characters, at the end of a paragraph or sentence if possible
+ **/
+pub fn chunks(text: &str, max: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        let Some((end, _)) = rest.char_indices().nth(max) else {
+            chunks.push(rest.to_string());
+            break;
+        };
+
+        // Cutting in the first half would make the parts too short
+        let part = &rest[..end];
+        let late = |cut: usize| (cut > end / 2).then_some(cut);
+        let cut = (part.rfind("\n\n").and_then(late))
+            .or_else(|| part.rfind(". ").and_then(|i| late(i + 1)))
+            .or_else(|| part.rfind(char::is_whitespace).and_then(late))
+            .unwrap_or(end);
+        chunks.push(rest[..cut].trim().to_string());
+        rest = rest[cut..].trim_start();
+    }
+
+    chunks
+}
+
+/**
+ * The files in the directory and the ones below it. Symlinks are skipped, one pointing
+ * to a directory above it would never let this end.
+ **/
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = std::fs::read_dir(&current).map_err(|err| err.to_string())?;
+        for entry in entries.flatten() {
+            let file_type = entry.file_type().map_err(|err| err.to_string())?;
+            if file_type.is_symlink() {
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/**
+ * The text of a text file or PDF, PDFs are read with pdftotext
+ **/
+pub fn read_text(path: &Path) -> Result<String, String> {
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    let pdf = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+    if pdf {
+        let output = Command::new("pdftotext")
+            .args(["-layout".as_ref(), path.as_os_str(), "-".as_ref()])
+            .output()
+            .map_err(|err| format!("Failed to run pdftotext, is it installed? {}", err))?;
+        if !output.status.success() {
+            return Err(format!("Failed to read the PDF {}", file));
+        }
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    String::from_utf8(bytes).map_err(|_| format!("{} is not a text file", file))
+}
+
+/**
+ * The AI can use this tool to list and read documents from a directory,
+ * long ones are given part by part
+ **/
+pub struct DocumentsTool {
+    pub cfg: DocumentsConfig,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "Either \"list\" to see the documents or \"read\" to read one.")]
+    action: String,
+    #[schemars(description = "The file name of the document to read, as listed.")]
+    file: Option<String>,
+    #[schemars(description = "Which part of a long document to read, starting at 1.")]
+    part: Option<usize>,
+}
+
+impl DocumentsTool {
+    fn dir(&self) -> Result<PathBuf, String> {
+        let dir = (self.cfg.documents_dir.as_deref()).ok_or("documents_dir is not set")?;
+        Path::new(dir)
+            .canonicalize()
+            .map_err(|err| format!("Failed to open {}: {}", dir, err))
+    }
+
+    /**
+     * The files in the directory and the ones below it, relative to it
+     **/
+    fn list(&self) -> Result<Vec<String>, String> {
+        let dir = self.dir()?;
+        let files = files(&dir)?;
+        Ok(files
+            .iter()
+            .filter_map(|path| path.strip_prefix(&dir).ok())
+            .map(|relative| relative.to_string_lossy().to_string())
+            .collect())
+    }
+
+    /**
+     * The text of a document, files outside of the directory can't be read
+     **/
+    fn text(&self, file: &str) -> Result<String, String> {
+        let dir = self.dir()?;
+        let path = (dir.join(file).canonicalize())
+            .map_err(|_| format!("There is no document called {}", file))?;
+        if !path.starts_with(&dir) {
+            return Err(format!("{} is not in the documents directory", file));
+        }
+        read_text(&path)
+    }
+}
+
+impl Tool for DocumentsTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "documents"
+    }
+
+    fn description() -> &'static str {
+        "Lists the user's documents or reads one of them, text files and PDFs, to summarize it or read it out loud."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let file = parameters.file.unwrap_or_default();
+        println!("DocumentsTool: {} {}", parameters.action, file);
+
+        let tool = DocumentsTool {
+            cfg: self.cfg.clone(),
+        };
+        match parameters.action.to_lowercase().as_str() {
+            "list" => {
+                let files = tokio::task::spawn_blocking(move || tool.list()).await??;
+                if files.is_empty() {
+                    return Ok("There are no documents".to_string());
+                }
+                Ok(files.join("\n"))
+            }
+            "read" => {
+                let name = file.trim().to_string();
+                let text = tokio::task::spawn_blocking(move || tool.text(&name)).await??;
+                let chunks = chunks(&text, self.cfg.documents_chunk_chars.max(100));
+                if chunks.is_empty() {
+                    return Ok(format!("{} is empty", file));
+                }
+
+                let part = parameters.part.unwrap_or(1).clamp(1, chunks.len());
+                if chunks.len() == 1 {
+                    return Ok(chunks[0].clone());
+                }
+                Ok(format!(
+                    "Part {} of {} of {}:\n{}",
+                    part,
+                    chunks.len(),
+                    file,
+                    chunks[part - 1]
+                ))
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
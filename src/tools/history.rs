@@ -0,0 +1,132 @@
+use chrono::{DateTime, Local, NaiveDate};
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::llm::history::{LoggedTurn, SavedHistory};
+use crate::textproc::words;
+
+const MAX_RESULTS: usize = 5;
+// Long answers are cut, the model only needs to recognize what it was about
+const MAX_SNIPPET: usize = 300;
+
+fn snippet(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(MAX_SNIPPET) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn parse_date(date: Option<String>) -> Result<Option<NaiveDate>, String> {
+    match date.filter(|date| !date.trim().is_empty()) {
+        Some(date) => NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| format!("{} is not a date like 2025-01-31", date)),
+        None => Ok(None),
+    }
+}
+
+/**
+ * The turns of the log between the dates that share the most words with the query, in the
+ * order they were said. Without a query the last turns between the dates are returned.
+ **/
+fn search(
+    log: Vec<LoggedTurn>,
+    query: &str,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Vec<(DateTime<Local>, LoggedTurn)> {
+    let query = words(query);
+    let turns = log
+        .into_iter()
+        .filter_map(|turn| {
+            let time = DateTime::parse_from_rfc3339(&turn.time).ok()?;
+            Some((time.with_timezone(&Local), turn))
+        })
+        .filter(|(time, _)| from.is_none_or(|from| time.date_naive() >= from))
+        .filter(|(time, _)| to.is_none_or(|to| time.date_naive() <= to));
+
+    let mut found: Vec<(usize, (DateTime<Local>, LoggedTurn))> = turns
+        .map(|(time, turn)| {
+            let said = words(&format!("{} {}", turn.user, turn.assistant));
+            (said.intersection(&query).count(), (time, turn))
+        })
+        .filter(|(score, _)| query.is_empty() || *score > 0)
+        .collect();
+
+    // The newest first when turns fit equally well
+    found.reverse();
+    found.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    let mut found: Vec<_> = found
+        .into_iter()
+        .take(MAX_RESULTS)
+        .map(|(_, turn)| turn)
+        .collect();
+    found.sort_by_key(|(time, _)| *time);
+    found
+}
+
+/**
+ * The AI can use this tool to look up what was said in earlier conversations, even the ones
+ * it has forgotten, e.g. "What did I say about the dentist appointment last week?".
+ * It searches the log in the history file.
+ **/
+pub struct HistoryTool {
+    pub path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "Words to search for, e.g. \"dentist appointment\", empty for everything that was said."
+    )]
+    query: Option<String>,
+    #[schemars(description = "The first day to search as \"YYYY-MM-DD\", the beginning if empty.")]
+    from: Option<String>,
+    #[schemars(description = "The last day to search as \"YYYY-MM-DD\", today if empty.")]
+    to: Option<String>,
+}
+
+impl Tool for HistoryTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "history"
+    }
+
+    fn description() -> &'static str {
+        "Searches everything the user and you said in earlier conversations. Use it when the user asks about something they told you before that you don't remember."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let query = parameters.query.unwrap_or_default();
+        let from = parse_date(parameters.from)?;
+        let to = parse_date(parameters.to)?;
+        println!("HistoryTool: {:?} from {:?} to {:?}", query, from, to);
+
+        let path = self.path.clone();
+        let saved = tokio::task::spawn_blocking(move || SavedHistory::load(&path)).await??;
+
+        let found = search(saved.log, &query, from, to);
+        if found.is_empty() {
+            return Ok("Nothing like that was said then".to_string());
+        }
+
+        let found: Vec<String> = found
+            .into_iter()
+            .map(|(time, turn)| {
+                format!(
+                    "{}\nUser: {}\nYou: {}",
+                    time.format("%A, %Y-%m-%d %H:%M"),
+                    snippet(&turn.user),
+                    snippet(&turn.assistant)
+                )
+            })
+            .collect();
+        Ok(found.join("\n\n"))
+    }
+}
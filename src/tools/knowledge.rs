@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::llm::ollama::OllamaConfig;
+use crate::tools::documents::{chunks, files, read_text};
+
+// How many passages are sent to Ollama at once
+const BATCH_SIZE: usize = 16;
+
+/**
+ * Knowledge base related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KnowledgeConfig {
+    #[serde(default)]
+    pub knowledge_dir: Option<String>, // Enables the knowledge tool, the text files and PDFs in this directory are indexed
+    #[serde(default = "default_knowledge_index")]
+    pub knowledge_index: String, // Where the index is kept, it is updated when files change
+    #[serde(default = "default_knowledge_model")]
+    pub knowledge_model: String, // The Ollama model that turns text into embeddings
+    #[serde(default = "default_knowledge_chunk_chars")]
+    pub knowledge_chunk_chars: usize, // How long the indexed passages are
+    #[serde(default = "default_knowledge_results")]
+    pub knowledge_results: usize, // How many passages the model gets per search
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        Self {
+            knowledge_dir: None,
+            knowledge_index: default_knowledge_index(),
+            knowledge_model: default_knowledge_model(),
+            knowledge_chunk_chars: default_knowledge_chunk_chars(),
+            knowledge_results: default_knowledge_results(),
+        }
+    }
+}
+
+fn default_knowledge_index() -> String {
+    "knowledge.json".to_string()
+}
+
+fn default_knowledge_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_knowledge_chunk_chars() -> usize {
+    1000
+}
+
+fn default_knowledge_results() -> usize {
+    3
+}
+
+#[derive(Serialize, Deserialize)]
+struct Passage {
+    text: String,
+    embedding: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    modified: u64, // Seconds since 1970, the file is indexed again when it changes
+    passages: Vec<Passage>,
+}
+
+/**
+ * The passages of every file and their embeddings, by the path relative to the directory
+ **/
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    model: String,
+    files: BTreeMap<String, IndexedFile>,
+}
+
+/**
+ * How similar two embeddings are, 1 is the same direction
+ **/
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let length_a = a.iter().map(|a| a * a).sum::<f32>().sqrt();
+    let length_b = b.iter().map(|b| b * b).sum::<f32>().sqrt();
+    if length_a == 0.0 || length_b == 0.0 {
+        return 0.0;
+    }
+    dot / (length_a * length_b)
+}
+
+/**
+ * The user's notes and manuals, indexed by their meaning with embeddings from Ollama
+ **/
+#[derive(Clone)]
+pub struct Knowledge {
+    cfg: KnowledgeConfig,
+    url: String,
+    index: Arc<Mutex<Index>>,
+}
+
+impl Knowledge {
+    pub fn new(cfg: &KnowledgeConfig, ollama: &OllamaConfig) -> Self {
+        // The index can always be built again, so a broken one is not a reason to stop
+        let index = match std::fs::read_to_string(&cfg.knowledge_index) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+                eprintln!(
+                    "Failed to parse {}, indexing again: {}",
+                    cfg.knowledge_index, err
+                );
+                Index::default()
+            }),
+            Err(_) => Index::default(),
+        };
+
+        Self {
+            cfg: cfg.clone(),
+            url: ollama.url(),
+            index: Arc::new(Mutex::new(index)),
+        }
+    }
+
+    /**
+     * Index new and changed files in the background, searching works with the old index meanwhile
+     **/
+    pub fn spawn_indexing(&self) {
+        let knowledge = self.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = knowledge.update() {
+                eprintln!("Failed to index the knowledge base: {}", err);
+            }
+        });
+    }
+
+    fn update(&self) -> Result<(), String> {
+        let dir = (self.cfg.knowledge_dir.as_deref()).ok_or("knowledge_dir is not set")?;
+        let dir = Path::new(dir)
+            .canonicalize()
+            .map_err(|err| format!("Failed to open {}: {}", dir, err))?;
+
+        // Embeddings of different models can't be compared
+        {
+            let mut index = self.index.lock().unwrap();
+            if index.model != self.cfg.knowledge_model {
+                index.model = self.cfg.knowledge_model.clone();
+                index.files.clear();
+            }
+        }
+
+        let mut found = HashSet::new();
+        let mut indexed = 0;
+        for path in files(&dir)? {
+            let Ok(relative) = path.strip_prefix(&dir) else {
+                continue;
+            };
+            let name = relative.to_string_lossy().to_string();
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map(|time| {
+                    time.duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                })
+                .unwrap_or_default();
+            found.insert(name.clone());
+
+            let unchanged = (self.index.lock().unwrap().files.get(&name))
+                .is_some_and(|file| file.modified == modified);
+            if unchanged {
+                continue;
+            }
+
+            // Files that aren't text are skipped
+            let Ok(text) = read_text(&path) else {
+                continue;
+            };
+            let texts = chunks(&text, self.cfg.knowledge_chunk_chars.max(100));
+            let mut passages = Vec::new();
+            for batch in texts.chunks(BATCH_SIZE) {
+                let embeddings = self.embed(batch)?;
+                passages.extend(
+                    batch
+                        .iter()
+                        .zip(embeddings)
+                        .map(|(text, embedding)| Passage {
+                            text: text.clone(),
+                            embedding,
+                        }),
+                );
+            }
+
+            let file = IndexedFile { modified, passages };
+            self.index.lock().unwrap().files.insert(name.clone(), file);
+            self.save();
+            println!("Indexed {} for the knowledge base", name);
+            indexed += 1;
+        }
+
+        // Forget the files that were deleted
+        let removed = {
+            let mut index = self.index.lock().unwrap();
+            let before = index.files.len();
+            index.files.retain(|name, _| found.contains(name));
+            before - index.files.len()
+        };
+        if removed > 0 {
+            self.save();
+        }
+
+        if indexed > 0 || removed > 0 {
+            println!(
+                "Knowledge base: {} files indexed, {} removed",
+                indexed, removed
+            );
+        }
+        Ok(())
+    }
+
+    fn save(&self) {
+        let path = &self.cfg.knowledge_index;
+        let result = serde_json::to_string(&*self.index.lock().unwrap())
+            .map_err(|err| err.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            eprintln!("Failed to save the knowledge base to {}: {}", path, err);
+        }
+    }
+
+    /**
+     * Turn the texts into embeddings with Ollama
+     **/
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let request = json!({ "model": self.cfg.knowledge_model, "input": texts });
+        let response = ureq::post(&format!("{}/api/embed", self.url))
+            .timeout(Duration::from_secs(120))
+            .set("Content-Type", "application/json")
+            .send_string(&request.to_string())
+            .map_err(|err| format!("Failed to get embeddings from Ollama: {}", err))?;
+
+        #[derive(Deserialize)]
+        struct EmbedResponse {
+            embeddings: Vec<Vec<f32>>,
+        }
+        let response: EmbedResponse = serde_json::from_reader(response.into_reader())
+            .map_err(|err| format!("Failed to read the embeddings: {}", err))?;
+        if response.embeddings.len() != texts.len() {
+            return Err("Ollama returned the wrong number of embeddings".to_string());
+        }
+        Ok(response.embeddings)
+    }
+
+    /**
+     * The passages that are closest in meaning to the query, with the file they are from
+     **/
+    fn search(&self, query: &str) -> Result<Vec<(String, String)>, String> {
+        if self.index.lock().unwrap().files.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = self.embed(&[query.to_string()])?.remove(0);
+
+        let index = self.index.lock().unwrap();
+        let mut scored: Vec<(f32, &String, &Passage)> = (index.files.iter())
+            .flat_map(|(name, file)| file.passages.iter().map(move |passage| (name, passage)))
+            .map(|(name, passage)| (cosine_similarity(&query, &passage.embedding), name, passage))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(scored
+            .into_iter()
+            .take(self.cfg.knowledge_results.max(1))
+            .map(|(_, name, passage)| (name.clone(), passage.text.clone()))
+            .collect())
+    }
+}
+
+/**
+ * The AI can use this tool to look things up in the user's own notes and manuals
+ **/
+pub struct KnowledgeTool {
+    pub knowledge: Knowledge,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "What to look for, as a question or keywords, e.g. \"how to descale the coffee machine\"."
+    )]
+    query: String,
+}
+
+impl Tool for KnowledgeTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "knowledge"
+    }
+
+    fn description() -> &'static str {
+        "Searches the user's own notes and manuals for the passages that fit a question best. Use it for questions about the user's things, plans or notes."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        println!("KnowledgeTool: {}", parameters.query);
+
+        let knowledge = self.knowledge.clone();
+        let query = parameters.query;
+        let passages = tokio::task::spawn_blocking(move || knowledge.search(&query)).await??;
+        if passages.is_empty() {
+            return Ok("Nothing has been indexed yet".to_string());
+        }
+
+        let passages: Vec<String> = passages
+            .iter()
+            .map(|(file, text)| format!("From {}:\n{}", file, text))
+            .collect();
+        Ok(passages.join("\n\n"))
+    }
+}
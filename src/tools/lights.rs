@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::tools::mqtt::Mqtt;
+
+const COLORS: &[(&str, [u8; 3])] = &[
+    ("red", [255, 0, 0]),
+    ("orange", [255, 120, 0]),
+    ("yellow", [255, 220, 0]),
+    ("green", [0, 255, 0]),
+    ("cyan", [0, 255, 255]),
+    ("turquoise", [64, 224, 208]),
+    ("blue", [0, 0, 255]),
+    ("purple", [128, 0, 255]),
+    ("violet", [143, 0, 255]),
+    ("magenta", [255, 0, 255]),
+    ("pink", [255, 105, 180]),
+    ("white", [255, 255, 255]),
+];
+
+/**
+ * Shades of white, from the light of a candle to daylight
+ **/
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum White {
+    Warm,
+    Neutral,
+    Cool,
+}
+
+impl White {
+    /**
+     * The color temperature in mireds, which Hue and zigbee2mqtt use
+     **/
+    fn mireds(self) -> u32 {
+        match self {
+            White::Warm => 370,    // 2700K
+            White::Neutral => 250, // 4000K
+            White::Cool => 167,    // 6000K
+        }
+    }
+
+    /**
+     * For lights that only know colors
+     **/
+    fn rgb(self) -> [u8; 3] {
+        match self {
+            White::Warm => [255, 166, 87],
+            White::Neutral => [255, 209, 163],
+            White::Cool => [255, 243, 239],
+        }
+    }
+}
+
+/**
+ * How the lights should be, everything not set stays as it is
+ **/
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct LightState {
+    #[serde(default)]
+    pub on: Option<bool>,
+    #[serde(default)]
+    pub brightness: Option<u8>, // In percent
+    #[serde(default)]
+    pub color: Option<String>, // A name like "red" or "#ff8800"
+    #[serde(default)]
+    pub white: Option<White>,
+}
+
+impl LightState {
+    /**
+     * The settings of the other state replace the ones of this one
+     **/
+    fn merge(self, other: LightState) -> LightState {
+        LightState {
+            on: other.on.or(self.on),
+            brightness: other.brightness.or(self.brightness),
+            color: other.color.or(self.color),
+            white: other.white.or(self.white),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKind {
+    Hue,         // id is the number of the light on the bridge, or "groups/<number>" for a room
+    Wled,        // id is the address of the WLED controller, e.g. "192.168.1.30"
+    Zigbee2mqtt, // id is the friendly name of the device, needs mqtt_host
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Light {
+    pub name: String, // What the user calls it, e.g. "living room"
+    pub kind: LightKind,
+    pub id: String,
+}
+
+/**
+ * Light related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LightsConfig {
+    #[serde(default)]
+    pub lights: Vec<Light>, // Enables the light tool, e.g. [{"name": "desk", "kind": "wled", "id": "192.168.1.30"}]
+    #[serde(default)]
+    pub hue_bridge: Option<String>, // Address of the Philips Hue bridge, e.g. "192.168.1.20"
+    #[serde(default)]
+    pub hue_user: Option<String>, // The user created on the bridge, falls back to the HUE_USER environment variable
+    #[serde(default = "default_zigbee2mqtt_topic")]
+    pub zigbee2mqtt_topic: String,
+    #[serde(default = "default_light_scenes")]
+    pub light_scenes: HashMap<String, LightState>, // Scenes by name, e.g. {"movie": {"brightness": 10, "white": "warm"}}
+}
+
+impl Default for LightsConfig {
+    fn default() -> Self {
+        Self {
+            lights: Vec::new(),
+            hue_bridge: None,
+            hue_user: None,
+            zigbee2mqtt_topic: default_zigbee2mqtt_topic(),
+            light_scenes: default_light_scenes(),
+        }
+    }
+}
+
+fn default_zigbee2mqtt_topic() -> String {
+    "zigbee2mqtt".to_string()
+}
+
+fn default_light_scenes() -> HashMap<String, LightState> {
+    let scene = |brightness, white| LightState {
+        on: Some(true),
+        brightness: Some(brightness),
+        color: None,
+        white: Some(white),
+    };
+    HashMap::from([
+        ("bright".to_string(), scene(100, White::Neutral)),
+        ("relax".to_string(), scene(40, White::Warm)),
+        ("reading".to_string(), scene(80, White::Neutral)),
+        ("concentrate".to_string(), scene(100, White::Cool)),
+        ("movie".to_string(), scene(10, White::Warm)),
+        ("night".to_string(), scene(3, White::Warm)),
+    ])
+}
+
+/**
+ * A color name or "#rrggbb" as red, green and blue
+ **/
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let color = color.trim().to_lowercase();
+    if let Some(hex) = color.strip_prefix('#')
+        && hex.len() == 6
+    {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some([channel(0)?, channel(2)?, channel(4)?]);
+    }
+    COLORS
+        .iter()
+        .find(|(name, _)| color == *name || color.strip_prefix("light ") == Some(name))
+        .map(|(_, rgb)| *rgb)
+}
+
+/**
+ * The CIE xy coordinates of a color, which is how Hue lights are given colors
+ **/
+fn xy(rgb: [u8; 3]) -> [f32; 2] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c > 0.04045 {
+            ((c + 0.055) / 1.055).powf(2.4)
+        } else {
+            c / 12.92
+        }
+    };
+    let (r, g, b) = (linear(rgb[0]), linear(rgb[1]), linear(rgb[2]));
+    let x = r * 0.4124 + g * 0.3576 + b * 0.1805;
+    let y = r * 0.2126 + g * 0.7152 + b * 0.0722;
+    let z = r * 0.0193 + g * 0.1192 + b * 0.9505;
+    let sum = x + y + z;
+    if sum == 0.0 {
+        return [0.3227, 0.329];
+    }
+    [x / sum, y / sum]
+}
+
+/**
+ * What is sent to the lights, with the color already understood
+ **/
+#[derive(Clone, Copy)]
+struct Command {
+    on: Option<bool>,
+    brightness: Option<u8>,
+    rgb: Option<[u8; 3]>,
+    white: Option<White>,
+}
+
+impl Command {
+    fn new(state: &LightState) -> Result<Self, String> {
+        let rgb = match &state.color {
+            Some(color) => Some(parse_color(color).ok_or_else(|| {
+                let names: Vec<&str> = COLORS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "{} is not a color, the colors are {} or #rrggbb",
+                    color,
+                    names.join(", ")
+                )
+            })?),
+            None => None,
+        };
+        let brightness = state.brightness.map(|brightness| brightness.min(100));
+
+        // Changing how a light looks turns it on, a brightness of 0 turns it off
+        let on = match (state.on, brightness) {
+            (_, Some(0)) => Some(false),
+            (Some(on), _) => Some(on),
+            (None, _) if brightness.is_some() || rgb.is_some() || state.white.is_some() => {
+                Some(true)
+            }
+            (None, _) => None,
+        };
+
+        Ok(Self {
+            on,
+            brightness: brightness.filter(|&brightness| brightness > 0),
+            rgb,
+            white: state.white,
+        })
+    }
+
+    /**
+     * The brightness scaled to 1 to max
+     **/
+    fn scaled(&self, max: u32) -> Option<u32> {
+        self.brightness
+            .map(|percent| (percent as u32 * max).div_ceil(100).max(1))
+    }
+
+    fn hue(&self) -> Value {
+        let mut body = json!({});
+        if let Some(on) = self.on {
+            body["on"] = on.into();
+        }
+        if let Some(bri) = self.scaled(254) {
+            body["bri"] = bri.into();
+        }
+        if let Some(rgb) = self.rgb {
+            body["xy"] = json!(xy(rgb));
+        } else if let Some(white) = self.white {
+            body["ct"] = white.mireds().into();
+        }
+        body
+    }
+
+    fn wled(&self) -> Value {
+        let mut body = json!({});
+        if let Some(on) = self.on {
+            body["on"] = on.into();
+        }
+        if let Some(bri) = self.scaled(255) {
+            body["bri"] = bri.into();
+        }
+        if let Some(rgb) = self.rgb.or(self.white.map(White::rgb)) {
+            body["seg"] = json!([{ "col": [rgb] }]);
+        }
+        body
+    }
+
+    fn zigbee2mqtt(&self) -> Value {
+        let mut body = json!({});
+        if let Some(on) = self.on {
+            body["state"] = if on { "ON" } else { "OFF" }.into();
+        }
+        if let Some(brightness) = self.scaled(254) {
+            body["brightness"] = brightness.into();
+        }
+        if let Some([r, g, b]) = self.rgb {
+            body["color"] = json!({ "hex": format!("#{:02x}{:02x}{:02x}", r, g, b) });
+        } else if let Some(white) = self.white {
+            body["color_temp"] = white.mireds().into();
+        }
+        body
+    }
+}
+
+/**
+ * The AI can use this tool to switch lights, dim them and change their color,
+ * with settings instead of raw payloads so small models get it right
+ **/
+pub struct LightsTool {
+    cfg: LightsConfig,
+    mqtt: Option<Mqtt>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "The name of the light, leave it empty for all of them.")]
+    light: Option<String>,
+    #[schemars(description = "true to turn the light on, false to turn it off.")]
+    on: Option<bool>,
+    #[schemars(description = "The brightness in percent, from 1 to 100.")]
+    brightness: Option<u8>,
+    #[schemars(description = "A color like \"red\", \"blue\" or \"#ff8800\".")]
+    color: Option<String>,
+    #[schemars(description = "A shade of white instead of a color.")]
+    white: Option<White>,
+    #[schemars(description = "A scene like \"relax\", \"movie\" or \"night\".")]
+    scene: Option<String>,
+}
+
+impl LightsTool {
+    /**
+     * The MQTT connection is only needed for zigbee2mqtt lights
+     **/
+    pub fn new(cfg: &LightsConfig, mqtt: Option<Mqtt>) -> Self {
+        let zigbee2mqtt = (cfg.lights.iter()).any(|light| light.kind == LightKind::Zigbee2mqtt);
+        assert!(
+            !zigbee2mqtt || mqtt.is_some(),
+            "zigbee2mqtt lights need mqtt_host to be set"
+        );
+        let hue = cfg.lights.iter().any(|light| light.kind == LightKind::Hue);
+        assert!(
+            !hue || cfg.hue_bridge.is_some(),
+            "Hue lights need hue_bridge to be set"
+        );
+
+        Self {
+            cfg: cfg.clone(),
+            mqtt,
+        }
+    }
+
+    /**
+     * Send the command to a light, the blocking HTTP requests run on their own thread
+     **/
+    async fn apply(&self, light: &Light, command: Command) -> Result<(), String> {
+        match light.kind {
+            LightKind::Hue => {
+                let bridge = self.cfg.hue_bridge.clone().unwrap_or_default();
+                let user = (self.cfg.hue_user.clone())
+                    .or_else(|| std::env::var("HUE_USER").ok())
+                    .ok_or("hue_user is not set")?;
+                let path = if light.id.contains('/') {
+                    format!("{}/action", light.id)
+                } else {
+                    format!("lights/{}/state", light.id)
+                };
+                let url = format!("http://{}/api/{}/{}", bridge, user, path);
+                let body = command.hue();
+                let answer = tokio::task::spawn_blocking(move || send("PUT", &url, &body))
+                    .await
+                    .map_err(|err| err.to_string())??;
+
+                // The bridge answers errors with 200 and a list of them
+                let error = (answer.as_array().into_iter().flatten())
+                    .find_map(|item| item["error"]["description"].as_str());
+                match error {
+                    Some(error) => Err(error.to_string()),
+                    None => Ok(()),
+                }
+            }
+            LightKind::Wled => {
+                let url = format!("http://{}/json/state", light.id);
+                let body = command.wled();
+                tokio::task::spawn_blocking(move || send("POST", &url, &body))
+                    .await
+                    .map_err(|err| err.to_string())??;
+                Ok(())
+            }
+            LightKind::Zigbee2mqtt => {
+                let mqtt = self.mqtt.as_ref().ok_or("mqtt_host is not set")?;
+                let topic = format!("{}/{}/set", self.cfg.zigbee2mqtt_topic, light.id);
+                let payload = command.zigbee2mqtt().to_string();
+                mqtt.publish(&topic, payload)
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+fn send(method: &str, url: &str, body: &Value) -> Result<Value, String> {
+    let response = ureq::request(method, url)
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .map_err(|err| format!("Failed to reach {}: {}", url, err))?;
+    Ok(serde_json::from_reader(response.into_reader()).unwrap_or_default())
+}
+
+impl Tool for LightsTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "lights"
+    }
+
+    fn description() -> &'static str {
+        "Turns lights on or off, dims them and changes their color or shade of white, or sets a scene."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let name = parameters.light.unwrap_or_default().trim().to_lowercase();
+        println!("LightsTool: {}", name);
+
+        let mut state = LightState::default();
+        if let Some(scene) = &parameters.scene {
+            let scene = scene.trim().to_lowercase();
+            state = self.cfg.light_scenes.get(&scene).cloned().ok_or_else(|| {
+                let mut names: Vec<&str> =
+                    self.cfg.light_scenes.keys().map(String::as_str).collect();
+                names.sort();
+                format!(
+                    "There is no scene {}, the scenes are {}",
+                    scene,
+                    names.join(", ")
+                )
+            })?;
+        }
+        let state = state.merge(LightState {
+            on: parameters.on,
+            brightness: parameters.brightness,
+            color: parameters.color,
+            white: parameters.white,
+        });
+        let command = Command::new(&state)?;
+
+        let all = name.is_empty() || name == "all";
+        let lights: Vec<&Light> = (self.cfg.lights.iter())
+            .filter(|light| all || light.name.to_lowercase().contains(&name))
+            .collect();
+        if lights.is_empty() {
+            let names: Vec<&str> = self
+                .cfg
+                .lights
+                .iter()
+                .map(|light| light.name.as_str())
+                .collect();
+            return Err(format!(
+                "There is no light {}, the lights are {}",
+                name,
+                names.join(", ")
+            )
+            .into());
+        }
+
+        let mut changed = Vec::new();
+        let mut failed = Vec::new();
+        for light in lights {
+            match self.apply(light, command).await {
+                Ok(()) => changed.push(light.name.as_str()),
+                Err(err) => failed.push(format!("{}: {}", light.name, err)),
+            }
+        }
+
+        if changed.is_empty() {
+            return Err(failed.join("\n").into());
+        }
+        let mut answer = format!("Changed {}", changed.join(", "));
+        if !failed.is_empty() {
+            answer.push_str(&format!(", but these failed:\n{}", failed.join("\n")));
+        }
+        Ok(answer)
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::tools::store;
+
+/**
+ * List related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ListsConfig {
+    #[serde(default)]
+    pub lists_file: Option<String>, // Enables the list tool, the lists are kept in this file, e.g. "lists.json"
+}
+
+/**
+ * Named lists like "shopping" or "to-do", saved to the file after every change
+ **/
+pub struct Lists {
+    path: String,
+    lists: BTreeMap<String, Vec<String>>,
+}
+
+impl Lists {
+    pub fn load(path: &str) -> Self {
+        let lists = store::load(path).unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to load the lists from {}, starting without them: {}",
+                path, err
+            );
+            BTreeMap::new()
+        });
+
+        Self {
+            path: path.to_string(),
+            lists,
+        }
+    }
+
+    fn save(&self) {
+        if let Err(err) = store::save(&self.path, &self.lists) {
+            eprintln!("Failed to save the lists to {}: {}", self.path, err);
+        }
+    }
+}
+
+/**
+ * "Shopping list", "the shopping list" and "Shopping" are all the same list
+ **/
+fn list_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_prefix("the ").unwrap_or(&name);
+    let name = name.strip_prefix("my ").unwrap_or(name);
+    let name = name.strip_suffix(" list").unwrap_or(name);
+    name.trim().to_string()
+}
+
+/**
+ * The AI can use this tool to keep lists like the shopping list,
+ * they are kept in a file and work without internet
+ **/
+pub struct ListsTool {
+    pub lists: Lists,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "Either \"add\", \"remove\", \"read\", \"clear\" to empty a list or \"lists\" to see which lists there are."
+    )]
+    action: String,
+    #[schemars(description = "The name of the list, e.g. \"shopping\" or \"to-do\".")]
+    list: Option<String>,
+    #[schemars(description = "The items to add or remove, e.g. [\"milk\", \"eggs\"].")]
+    items: Option<Vec<String>>,
+}
+
+impl Tool for ListsTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "lists"
+    }
+
+    fn description() -> &'static str {
+        "Adds items to lists like the shopping or to-do list, removes them, reads a list out or clears it."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let name = list_name(&parameters.list.unwrap_or_default());
+        let items: Vec<String> = (parameters.items.unwrap_or_default().into_iter())
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+        println!("ListsTool: {} {} {:?}", parameters.action, name, items);
+
+        let action = parameters.action.to_lowercase();
+        if action != "lists" && name.is_empty() {
+            return Err("The name of the list is missing".into());
+        }
+
+        let lists = &mut self.lists;
+        match action.as_str() {
+            "add" => {
+                let list = lists.lists.entry(name.clone()).or_default();
+                let mut added = Vec::new();
+                for item in items {
+                    if !list.iter().any(|old| old.eq_ignore_ascii_case(&item)) {
+                        list.push(item.clone());
+                        added.push(item);
+                    }
+                }
+                lists.save();
+                if added.is_empty() {
+                    return Ok(format!("Nothing new was added to the {} list", name));
+                }
+                Ok(format!("Added {} to the {} list", added.join(", "), name))
+            }
+            "remove" => {
+                let Some(list) = lists.lists.get_mut(&name) else {
+                    return Ok(format!("There is no {} list", name));
+                };
+                let before = list.len();
+                list.retain(|old| !items.iter().any(|item| old.eq_ignore_ascii_case(item)));
+                let removed = before - list.len();
+                if list.is_empty() {
+                    lists.lists.remove(&name);
+                }
+                lists.save();
+                Ok(format!("Removed {} items from the {} list", removed, name))
+            }
+            "read" => match lists.lists.get(&name) {
+                Some(list) => Ok(format!("The {} list: {}", name, list.join(", "))),
+                None => Ok(format!("The {} list is empty", name)),
+            },
+            "clear" => {
+                lists.lists.remove(&name);
+                lists.save();
+                Ok(format!("Cleared the {} list", name))
+            }
+            "lists" => {
+                if lists.lists.is_empty() {
+                    return Ok("There are no lists".to_string());
+                }
+                let names: Vec<&str> = lists.lists.keys().map(String::as_str).collect();
+                Ok(format!("The lists are: {}", names.join(", ")))
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::textproc::words;
+use crate::tools::store;
+
+/**
+ * Memory related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MemoryConfig {
+    #[serde(default)]
+    pub memory_file: Option<String>, // Enables the long-term memory, facts about the user are kept in this file, e.g. "memory.json"
+    #[serde(default = "default_memory_facts")]
+    pub memory_facts: usize, // How many facts the model is reminded of with every message, the ones that fit best
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            memory_file: None,
+            memory_facts: default_memory_facts(),
+        }
+    }
+}
+
+fn default_memory_facts() -> usize {
+    10
+}
+
+/**
+ * Facts about the user by topic, saved to the file after every change
+ **/
+pub struct Memory {
+    path: String,
+    facts: BTreeMap<String, String>,
+}
+
+impl Memory {
+    pub fn load(path: &str) -> Self {
+        let facts = store::load(path).unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to load the memory from {}, starting without it: {}",
+                path, err
+            );
+            BTreeMap::new()
+        });
+
+        Self {
+            path: path.to_string(),
+            facts,
+        }
+    }
+
+    fn save(&self) {
+        if let Err(err) = store::save(&self.path, &self.facts) {
+            eprintln!("Failed to save the memory to {}: {}", self.path, err);
+        }
+    }
+
+    /**
+     * At most limit facts that share words with the text, the ones sharing the most first
+     **/
+    fn relevant(&self, text: &str, limit: usize) -> Vec<(&String, &String)> {
+        let text = words(text);
+        let mut scored: Vec<(usize, (&String, &String))> = self
+            .facts
+            .iter()
+            .map(|(topic, fact)| {
+                let fact_words = words(&format!("{} {}", topic, fact));
+                (fact_words.intersection(&text).count(), (topic, fact))
+            })
+            .filter(|(score, _)| *score > 0)
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, fact)| fact)
+            .collect()
+    }
+
+    /**
+     * What the model should know about the user to answer the text, None if nothing fits.
+     * If there aren't more facts than the limit, all of them are given.
+     **/
+    pub fn context(&self, text: &str, limit: usize) -> Option<String> {
+        let facts = if self.facts.len() <= limit {
+            self.facts.iter().collect()
+        } else {
+            self.relevant(text, limit)
+        };
+        if facts.is_empty() {
+            return None;
+        }
+
+        let facts: Vec<String> = facts
+            .into_iter()
+            .map(|(topic, fact)| format!("- {}: {}", topic, fact))
+            .collect();
+        Some(format!(
+            "What you remember about the user:\n{}",
+            facts.join("\n")
+        ))
+    }
+}
+
+/**
+ * The AI can use this tool to remember facts about the user, like their birthday or what they like,
+ * they are kept in a file and the model is reminded of the ones that fit what was said
+ **/
+pub struct MemoryTool {
+    pub memory: Arc<Mutex<Memory>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "Either \"remember\", \"forget\" or \"recall\".")]
+    action: String,
+    #[schemars(
+        description = "What the fact is about, e.g. \"birthday\" or \"favorite food\". Remembering replaces the old fact about it. For recall, words to search for, leave it empty for everything."
+    )]
+    topic: Option<String>,
+    #[schemars(description = "The fact to remember, e.g. \"3rd of May\".")]
+    fact: Option<String>,
+}
+
+impl Tool for MemoryTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "memory"
+    }
+
+    fn description() -> &'static str {
+        "Remembers facts about the user for a long time, forgets them or recalls them. Use it whenever the user tells you something about themselves."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let topic = parameters.topic.unwrap_or_default().trim().to_lowercase();
+        println!("MemoryTool: {} {}", parameters.action, topic);
+
+        let mut memory = self.memory.lock().unwrap();
+        match parameters.action.to_lowercase().as_str() {
+            "remember" => {
+                let fact = parameters.fact.ok_or("The fact to remember is missing")?;
+                if topic.is_empty() {
+                    return Err("The topic of the fact is missing".into());
+                }
+                let answer = format!("Remembered {}: {}", topic, fact);
+                memory.facts.insert(topic, fact);
+                memory.save();
+                Ok(answer)
+            }
+            "forget" => match memory.facts.remove(&topic) {
+                Some(fact) => {
+                    memory.save();
+                    Ok(format!("Forgot {}: {}", topic, fact))
+                }
+                None => Ok(format!("Nothing about {} was remembered", topic)),
+            },
+            "recall" => {
+                let facts = if topic.is_empty() {
+                    memory.facts.iter().collect()
+                } else {
+                    memory.relevant(&topic, usize::MAX)
+                };
+                if facts.is_empty() {
+                    return Ok("Nothing about that was remembered".to_string());
+                }
+                let facts: Vec<String> = facts
+                    .into_iter()
+                    .map(|(topic, fact)| format!("{}: {}", topic, fact))
+                    .collect();
+                Ok(facts.join("\n"))
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
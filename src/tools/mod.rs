@@ -1,2 +1,112 @@
+pub mod calc;
+pub mod calendar;
+pub mod convert;
+pub mod documents;
+pub mod fetch;
+pub mod history;
+pub mod knowledge;
+pub mod lights;
+pub mod lists;
+pub mod memory;
+pub mod mqtt;
+pub mod music;
+pub mod persona;
+pub mod plugin;
+pub mod random;
+pub mod reminder;
+pub mod spotify;
+pub mod store;
 pub mod timeout;
 pub mod time;
+pub mod timer;
+pub mod translate;
+pub mod weather;
+pub mod webcam;
+pub mod wikipedia;
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tools::calendar::CalendarConfig;
+use crate::tools::documents::DocumentsConfig;
+use crate::tools::fetch::FetchConfig;
+use crate::tools::knowledge::KnowledgeConfig;
+use crate::tools::lights::LightsConfig;
+use crate::tools::lists::ListsConfig;
+use crate::tools::memory::MemoryConfig;
+use crate::tools::mqtt::MqttConfig;
+use crate::tools::music::MusicConfig;
+use crate::tools::plugin::PluginsConfig;
+use crate::tools::reminder::ReminderConfig;
+use crate::tools::timeout::TimeoutConfig;
+use crate::tools::timer::TimerConfig;
+use crate::tools::translate::TranslateConfig;
+use crate::tools::weather::WeatherConfig;
+use crate::tools::webcam::WebcamConfig;
+use crate::tools::wikipedia::WikipediaConfig;
+
+/**
+ * Settings of the tools, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct ToolsConfig {
+    #[serde(flatten)]
+    pub calendar: CalendarConfig,
+    #[serde(flatten)]
+    pub documents: DocumentsConfig,
+    #[serde(flatten)]
+    pub fetch: FetchConfig,
+    #[serde(flatten)]
+    pub knowledge: KnowledgeConfig,
+    #[serde(flatten)]
+    pub lights: LightsConfig,
+    #[serde(flatten)]
+    pub lists: ListsConfig,
+    #[serde(flatten)]
+    pub memory: MemoryConfig,
+    #[serde(flatten)]
+    pub mqtt: MqttConfig,
+    #[serde(flatten)]
+    pub music: MusicConfig,
+    #[serde(flatten)]
+    pub plugins: PluginsConfig,
+    #[serde(flatten)]
+    pub reminders: ReminderConfig,
+    #[serde(flatten)]
+    pub timeout: TimeoutConfig,
+    #[serde(flatten)]
+    pub timer: TimerConfig,
+    #[serde(flatten)]
+    pub translate: TranslateConfig,
+    #[serde(flatten)]
+    pub weather: WeatherConfig,
+    #[serde(flatten)]
+    pub webcam: WebcamConfig,
+    #[serde(flatten)]
+    pub wikipedia: WikipediaConfig,
+}
+
+/**
+ * Which tools the model may use, either all or none of them, or each one by its name,
+ * e.g. {"timeout": false}. Tools that are not named are allowed.
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum UseTools {
+    All(bool),
+    Each(HashMap<String, bool>),
+}
+
+impl UseTools {
+    pub fn any(&self) -> bool {
+        !matches!(self, UseTools::All(false))
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        match self {
+            UseTools::All(all) => *all,
+            UseTools::Each(tools) => tools.get(name).copied().unwrap_or(true),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/**
+ * MQTT related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MqttConfig {
+    #[serde(default)]
+    pub mqtt_host: Option<String>, // Enables the MQTT tool, the broker to connect to, e.g. "localhost"
+    #[serde(default = "default_mqtt_port")]
+    pub mqtt_port: u16,
+    #[serde(default)]
+    pub mqtt_user: Option<String>,
+    #[serde(default)]
+    pub mqtt_password: Option<String>, // Falls back to the MQTT_PASSWORD environment variable
+    #[serde(default)]
+    pub mqtt_publish: HashMap<String, String>, // Topics the model may publish to and what they do, e.g. {"home/fan/set": "Turns the fan ON or OFF"}
+    #[serde(default)]
+    pub mqtt_subscribe: HashMap<String, String>, // Topics whose last value the model can read and what they are, e.g. {"home/temperature": "Living room temperature in °C"}
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            mqtt_host: None,
+            mqtt_port: default_mqtt_port(),
+            mqtt_user: None,
+            mqtt_password: None,
+            mqtt_publish: HashMap::new(),
+            mqtt_subscribe: HashMap::new(),
+        }
+    }
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+/**
+ * The connection to the broker, shared by every tool that uses MQTT
+ **/
+#[derive(Clone)]
+pub struct Mqtt {
+    values: Arc<Mutex<HashMap<String, String>>>, // Last payload received on every subscribed topic
+    #[cfg(feature = "mqtt")]
+    client: rumqttc::AsyncClient,
+}
+
+impl Mqtt {
+    /**
+     * Connects to the broker in the background, it reconnects by itself when the connection is lost
+     **/
+    #[cfg(feature = "mqtt")]
+    pub fn connect(cfg: &MqttConfig) -> Self {
+        use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+        use std::time::Duration;
+
+        let host = cfg.mqtt_host.clone().expect("mqtt_host must be set");
+        let mut options = MqttOptions::new("my_ai_friend", host, cfg.mqtt_port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let password = (cfg.mqtt_password.clone()).or_else(|| std::env::var("MQTT_PASSWORD").ok());
+        if let (Some(user), Some(password)) = (&cfg.mqtt_user, password) {
+            options.set_credentials(user, password);
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
+        let values = Arc::new(Mutex::new(HashMap::new()));
+
+        let topics: Vec<String> = cfg.mqtt_subscribe.keys().cloned().collect();
+        let subscriber = client.clone();
+        let received = values.clone();
+        tokio::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    // The subscriptions are gone after reconnecting
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        for topic in &topics {
+                            if let Err(err) = subscriber.try_subscribe(topic, QoS::AtMostOnce) {
+                                eprintln!("Failed to subscribe to {}: {}", topic, err);
+                            }
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                        received.lock().unwrap().insert(publish.topic, payload);
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        eprintln!("MQTT connection failed: {}", err);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        Self { values, client }
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    pub fn connect(_cfg: &MqttConfig) -> Self {
+        panic!("MQTT requires building with --features mqtt")
+    }
+
+    #[cfg(feature = "mqtt")]
+    pub async fn publish(
+        &self,
+        topic: &str,
+        payload: String,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let qos = rumqttc::QoS::AtLeastOnce;
+        self.client.publish(topic, qos, false, payload).await?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    pub async fn publish(
+        &self,
+        _topic: &str,
+        _payload: String,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        Err("MQTT requires building with --features mqtt".into())
+    }
+
+    /**
+     * The last payload received on a subscribed topic
+     **/
+    pub fn value(&self, topic: &str) -> Option<String> {
+        self.values.lock().unwrap().get(topic).cloned()
+    }
+}
+
+/**
+ * The AI can use this tool to publish to MQTT topics and read the last values of subscribed ones,
+ * e.g. to control home automation
+ **/
+pub struct MqttTool {
+    mqtt: Mqtt,
+    publish: HashMap<String, String>,
+    subscribe: HashMap<String, String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "Either \"list\" to see the topics, \"publish\" to send a payload to a topic or \"read\" to get the last value of a topic."
+    )]
+    action: String,
+    #[schemars(description = "The topic to publish to or read from.")]
+    topic: Option<String>,
+    #[schemars(description = "What to publish, e.g. \"ON\".")]
+    payload: Option<String>,
+}
+
+impl MqttTool {
+    pub fn new(cfg: &MqttConfig, mqtt: Mqtt) -> Self {
+        Self {
+            mqtt,
+            publish: cfg.mqtt_publish.clone(),
+            subscribe: cfg.mqtt_subscribe.clone(),
+        }
+    }
+
+    fn list(&self) -> String {
+        let publish = self
+            .publish
+            .iter()
+            .map(|(topic, description)| format!("publish {}: {}", topic, description));
+        let subscribe = self
+            .subscribe
+            .iter()
+            .map(|(topic, description)| format!("read {}: {}", topic, description));
+        publish.chain(subscribe).collect::<Vec<_>>().join("\n")
+    }
+}
+
+impl Tool for MqttTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "mqtt"
+    }
+
+    fn description() -> &'static str {
+        "Controls devices and reads sensors of the home over MQTT, list the topics first to know which ones there are."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let topic = parameters.topic.unwrap_or_default();
+        println!("MqttTool: {} {}", parameters.action, topic);
+
+        match parameters.action.to_lowercase().as_str() {
+            "list" => Ok(self.list()),
+            "publish" => {
+                if !self.publish.contains_key(&topic) {
+                    return Ok(format!(
+                        "Publishing to {} isn't allowed, the topics are:\n{}",
+                        topic,
+                        self.list()
+                    ));
+                }
+                let payload = parameters.payload.unwrap_or_default();
+                self.mqtt.publish(&topic, payload.clone()).await?;
+                Ok(format!("Published {} to {}", payload, topic))
+            }
+            "read" => {
+                if !self.subscribe.contains_key(&topic) {
+                    return Ok(format!(
+                        "Reading {} isn't allowed, the topics are:\n{}",
+                        topic,
+                        self.list()
+                    ));
+                }
+                match self.mqtt.value(&topic) {
+                    Some(value) => Ok(format!("{} is {}", topic, value)),
+                    None => Ok(format!("Nothing was received on {} yet", topic)),
+                }
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::time::Duration;
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::tools::spotify::{Spotify, SpotifyConfig};
+
+/**
+ * Which kind of music player the music tool controls
+ **/
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MusicKind {
+    Mpd,     // The Music Player Daemon, it can search the music library
+    Mpris,   // Any player playerctl can control, like VLC, it can't search
+    Spotify, // Spotify Connect through the Web API, it can search
+}
+
+/**
+ * Music related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MusicConfig {
+    #[serde(default)]
+    pub music: Option<MusicKind>, // Enables the music tool: "mpd", "mpris" or "spotify"
+    #[serde(default = "default_mpd_host")]
+    pub mpd_host: String,
+    #[serde(default = "default_mpd_port")]
+    pub mpd_port: u16,
+    #[serde(default)]
+    pub mpd_password: Option<String>,
+    #[serde(default)]
+    pub music_player: Option<String>, // The MPRIS player to control, e.g. "vlc", the first one playerctl finds by default
+    #[serde(flatten)]
+    pub spotify: SpotifyConfig,
+}
+
+impl Default for MusicConfig {
+    fn default() -> Self {
+        Self {
+            music: None,
+            mpd_host: default_mpd_host(),
+            mpd_port: default_mpd_port(),
+            mpd_password: None,
+            music_player: None,
+            spotify: SpotifyConfig::default(),
+        }
+    }
+}
+
+fn default_mpd_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_mpd_port() -> u16 {
+    6600
+}
+
+/**
+ * Put an argument in quotes for MPD
+ **/
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Clone)]
+pub struct Mpd {
+    address: String,
+    password: Option<String>,
+}
+
+impl Mpd {
+    /**
+     * Run the commands one after another and return the "key: value" lines they answered with
+     **/
+    fn run(&self, commands: &[String]) -> Result<Vec<(String, String)>, String> {
+        let address = (self.address.to_socket_addrs().ok())
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("Failed to resolve {}", self.address))?;
+        let stream = TcpStream::connect_timeout(&address, Duration::from_secs(2))
+            .map_err(|err| format!("Failed to connect to MPD at {}: {}", self.address, err))?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .map_err(|err| err.to_string())?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+        let mut writer = stream;
+
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|err| err.to_string())?;
+        if !line.starts_with("OK MPD") {
+            return Err(format!("{} is not MPD", self.address));
+        }
+
+        let password = self
+            .password
+            .iter()
+            .map(|password| format!("password {}", quote(password)));
+        let mut answer = Vec::new();
+        for command in password.chain(commands.iter().cloned()) {
+            writeln!(writer, "{}", command).map_err(|err| err.to_string())?;
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+                    return Err("MPD closed the connection".to_string());
+                }
+                let line = line.trim_end();
+                if line == "OK" {
+                    break;
+                }
+                if let Some(error) = line.strip_prefix("ACK ") {
+                    return Err(format!("MPD failed: {}", error));
+                }
+                if let Some((key, value)) = line.split_once(": ") {
+                    answer.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        Ok(answer)
+    }
+
+    /**
+     * The value of a key in the status, e.g. "state" or "volume"
+     **/
+    fn status(&self, key: &str) -> Result<Option<String>, String> {
+        let status = self.run(&["status".to_string()])?;
+        Ok(status
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value))
+    }
+
+    fn queue_length(&self) -> Result<usize, String> {
+        let length = self.status("playlistlength")?;
+        Ok(length.and_then(|length| length.parse().ok()).unwrap_or(0))
+    }
+}
+
+/**
+ * A music player the bot can control, also used to make the music quieter while the bot talks
+ **/
+#[derive(Clone)]
+pub enum Player {
+    Mpd(Mpd),
+    Mpris(Option<String>),
+    Spotify(Spotify),
+}
+
+impl Player {
+    pub fn new(cfg: &MusicConfig) -> Option<Self> {
+        match cfg.music? {
+            MusicKind::Mpd => Some(Player::Mpd(Mpd {
+                address: format!("{}:{}", cfg.mpd_host, cfg.mpd_port),
+                password: cfg.mpd_password.clone(),
+            })),
+            MusicKind::Mpris => Some(Player::Mpris(cfg.music_player.clone())),
+            MusicKind::Spotify => Some(Player::Spotify(Spotify::new(&cfg.spotify))),
+        }
+    }
+
+    /**
+     * Run playerctl for the configured player and return what it printed
+     **/
+    fn playerctl(player: &Option<String>, args: &[&str]) -> Result<String, String> {
+        let mut command = Command::new("playerctl");
+        if let Some(player) = player {
+            command.args(["--player", player]);
+        }
+        let output = command
+            .args(args)
+            .output()
+            .map_err(|err| format!("Failed to run playerctl, is it installed? {}", err))?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("playerctl failed: {}", error.trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /**
+     * What is playing right now
+     **/
+    pub fn current(&self) -> Result<String, String> {
+        match self {
+            Player::Mpd(mpd) => {
+                let state = mpd.status("state")?.unwrap_or_default();
+                let song = mpd.run(&["currentsong".to_string()])?;
+                let tag = |key: &str| song.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+                let title = match (tag("Artist"), tag("Title")) {
+                    (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+                    (None, Some(title)) => title,
+                    _ => tag("file").unwrap_or_else(|| "nothing".to_string()),
+                };
+                Ok(match state.as_str() {
+                    "play" => format!("Playing {}", title),
+                    "pause" => format!("Paused {}", title),
+                    _ => "Stopped".to_string(),
+                })
+            }
+            Player::Mpris(player) => Self::playerctl(
+                player,
+                &["metadata", "--format", "{{status}} {{artist}} - {{title}}"],
+            ),
+            Player::Spotify(spotify) => spotify.current(),
+        }
+    }
+
+    pub fn playing(&self) -> bool {
+        match self {
+            Player::Mpd(mpd) => mpd
+                .status("state")
+                .is_ok_and(|state| state.as_deref() == Some("play")),
+            Player::Mpris(player) => {
+                Self::playerctl(player, &["status"]).is_ok_and(|status| status == "Playing")
+            }
+            Player::Spotify(spotify) => spotify.playing(),
+        }
+    }
+
+    /**
+     * Play the music matching the query in random order, or continue what was playing without one
+     **/
+    pub fn play(&self, query: Option<&str>) -> Result<String, String> {
+        match self {
+            Player::Mpd(mpd) => {
+                if let Some(query) = query {
+                    mpd.run(&[
+                        "clear".to_string(),
+                        format!("searchadd any {}", quote(query)),
+                    ])?;
+                    if mpd.queue_length()? == 0 {
+                        return Ok(format!("Found no music for {}", query));
+                    }
+                    mpd.run(&["shuffle".to_string()])?;
+                }
+                mpd.run(&["play".to_string()])?;
+                self.current()
+            }
+            Player::Mpris(player) => {
+                Self::playerctl(player, &["play"])?;
+                let current = self.current()?;
+                Ok(match query {
+                    Some(_) => format!(
+                        "This player can't search for music, continued instead. {}",
+                        current
+                    ),
+                    None => current,
+                })
+            }
+            Player::Spotify(spotify) => spotify.play(query),
+        }
+    }
+
+    /**
+     * Add the music matching the query to the end of the queue
+     **/
+    pub fn queue(&self, query: &str) -> Result<String, String> {
+        match self {
+            Player::Mpd(mpd) => {
+                let before = mpd.queue_length()?;
+                mpd.run(&[format!("searchadd any {}", quote(query))])?;
+                let added = mpd.queue_length()?.saturating_sub(before);
+                Ok(format!("Added {} songs for {} to the queue", added, query))
+            }
+            Player::Mpris(_) => Err("This player can't search for music".to_string()),
+            Player::Spotify(spotify) => spotify.queue(query),
+        }
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        match self {
+            Player::Mpd(mpd) => mpd.run(&["pause 1".to_string()]).map(|_| ()),
+            Player::Mpris(player) => Self::playerctl(player, &["pause"]).map(|_| ()),
+            Player::Spotify(spotify) => spotify.pause(),
+        }
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        match self {
+            Player::Mpd(mpd) => mpd.run(&["pause 0".to_string()]).map(|_| ()),
+            Player::Mpris(player) => Self::playerctl(player, &["play"]).map(|_| ()),
+            Player::Spotify(spotify) => spotify.resume(),
+        }
+    }
+
+    pub fn next(&self) -> Result<String, String> {
+        match self {
+            Player::Mpd(mpd) => mpd.run(&["next".to_string()]).map(|_| ())?,
+            Player::Mpris(player) => Self::playerctl(player, &["next"]).map(|_| ())?,
+            Player::Spotify(spotify) => spotify.next()?,
+        }
+        self.current()
+    }
+
+    pub fn previous(&self) -> Result<String, String> {
+        match self {
+            Player::Mpd(mpd) => mpd.run(&["previous".to_string()]).map(|_| ())?,
+            Player::Mpris(player) => Self::playerctl(player, &["previous"]).map(|_| ())?,
+            Player::Spotify(spotify) => spotify.previous()?,
+        }
+        self.current()
+    }
+
+    /**
+     * The volume of the player from 0 to 1, None if it can't be changed
+     **/
+    pub fn volume(&self) -> Option<f32> {
+        match self {
+            Player::Mpd(mpd) => {
+                let volume: i32 = mpd.status("volume").ok()??.parse().ok()?;
+                (volume >= 0).then(|| volume as f32 / 100.0)
+            }
+            Player::Mpris(player) => Self::playerctl(player, &["volume"]).ok()?.parse().ok(),
+            Player::Spotify(spotify) => spotify.volume(),
+        }
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        let result = match self {
+            Player::Mpd(mpd) => mpd
+                .run(&[format!("setvol {}", (volume * 100.0).round() as u32)])
+                .map(|_| ()),
+            Player::Mpris(player) => {
+                Self::playerctl(player, &["volume", &format!("{:.2}", volume)]).map(|_| ())
+            }
+            Player::Spotify(spotify) => spotify.set_volume(volume),
+        };
+        if let Err(err) = result {
+            eprintln!("Failed to change the music volume: {}", err);
+        }
+    }
+}
+
+/**
+ * The AI can use this tool to play, pause, skip and queue music
+ **/
+pub struct MusicTool {
+    pub player: Player,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "Either \"play\", \"pause\", \"next\", \"previous\", \"queue\" to add music after the current one or \"status\" to know what is playing."
+    )]
+    action: String,
+    #[schemars(
+        description = "For play and queue, an artist, album, song or genre to search for, e.g. \"jazz\". Play without it continues the music."
+    )]
+    query: Option<String>,
+}
+
+impl Tool for MusicTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "music"
+    }
+
+    fn description() -> &'static str {
+        "Plays music by artist, album, song or genre, pauses it, skips songs, queues more or tells what is playing."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let query = parameters.query.filter(|query| !query.trim().is_empty());
+        println!(
+            "MusicTool: {} {}",
+            parameters.action,
+            query.as_deref().unwrap_or_default()
+        );
+
+        // Talking to the player blocks
+        let player = self.player.clone();
+        let action = parameters.action.to_lowercase();
+        let answer = tokio::task::spawn_blocking(move || match action.as_str() {
+            "play" => player.play(query.as_deref()),
+            "pause" | "stop" => player.pause().map(|_| "Paused the music".to_string()),
+            "next" | "skip" => player.next(),
+            "previous" => player.previous(),
+            "queue" => match query {
+                Some(query) => player.queue(&query),
+                None => Err("What to queue is missing".to_string()),
+            },
+            "status" => player.current(),
+            action => Err(format!("Unknown action {}", action)),
+        })
+        .await?;
+
+        Ok(answer?)
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::{Arc, Mutex};
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/**
+ * The AI can use this tool to become another persona, the switch happens after it answered
+ **/
+pub struct PersonaTool {
+    pub personas: Vec<String>,
+    pub switch: Arc<Mutex<Option<String>>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "The name of the persona to become.")]
+    name: String,
+}
+
+impl Tool for PersonaTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "persona"
+    }
+
+    fn description() -> &'static str {
+        "Switches to another persona with a different personality and voice, use it when the user asks for it."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let Some(name) = self
+            .personas
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(&parameters.name))
+        else {
+            return Ok(format!(
+                "There is no persona called {}, the personas are: {}",
+                parameters.name,
+                self.personas.join(", ")
+            ));
+        };
+
+        println!("PersonaTool: {}", name);
+        *self.switch.lock().unwrap() = Some(name.clone());
+        Ok(format!("You will be {} from your next answer on", name))
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ollama_rs::generation::tools::{ToolFunctionInfo, ToolInfo, ToolType};
+use schemars::schema::RootSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/**
+ * A tool that is a program of its own, declared in the config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PluginConfig {
+    pub name: String,
+    pub description: String, // Tells the model what the tool does and when to use it
+    pub command: String,     // The program to run, e.g. "python3"
+    #[serde(default)]
+    pub args: Vec<String>, // e.g. ["plugins/stocks.py"]
+    #[serde(default = "default_parameters")]
+    pub parameters: Value, // JSON schema of the parameters the program gets
+    #[serde(default = "default_plugin_timeout")]
+    pub timeout: u64, // Seconds until the program is stopped
+    #[serde(default)]
+    pub confirm: bool, // Ask the user before running the program, for things that can't be undone
+}
+
+fn default_parameters() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_plugin_timeout() -> u64 {
+    30
+}
+
+/**
+ * Plugin related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default)]
+pub struct PluginsConfig {
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>, // Tools that are external programs, see the README
+}
+
+/**
+ * Runs the program with the parameters as JSON on stdin and gives what it prints to the model.
+ * It can also print {"result": "..."} or {"error": "..."}.
+ **/
+#[derive(Clone)]
+pub struct Plugin {
+    cfg: PluginConfig,
+    parameters: RootSchema,
+}
+
+impl Plugin {
+    pub fn new(cfg: &PluginConfig) -> Self {
+        let parameters = serde_json::from_value(cfg.parameters.clone())
+            .unwrap_or_else(|err| panic!("Invalid parameters of the plugin {}: {}", cfg.name, err));
+
+        Self {
+            cfg: cfg.clone(),
+            parameters,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.cfg.name
+    }
+
+    pub fn confirm(&self) -> bool {
+        self.cfg.confirm
+    }
+
+    /**
+     * Describes the plugin and its parameters to the LLM
+     **/
+    pub fn info(&self) -> ToolInfo {
+        ToolInfo {
+            tool_type: ToolType::Function,
+            function: ToolFunctionInfo {
+                name: self.cfg.name.clone(),
+                description: self.cfg.description.clone(),
+                parameters: self.parameters.clone(),
+            },
+        }
+    }
+
+    /**
+     * Run the program and wait for it to finish, it is killed once the timeout is over
+     **/
+    fn run(&self, arguments: Value) -> Result<String, String> {
+        let mut child = Command::new(&self.cfg.command)
+            .args(&self.cfg.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Failed to run {}: {}", self.cfg.command, err))?;
+
+        // Read the output in the background, so a program that prints a lot does not block
+        let read = |pipe: Option<Box<dyn Read + Send>>| {
+            thread::spawn(move || {
+                let mut text = String::new();
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_string(&mut text);
+                }
+                text
+            })
+        };
+        let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+        let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{}", arguments);
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(self.cfg.timeout);
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+                break status;
+            }
+            if Instant::now() > deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} took longer than {} seconds",
+                    self.cfg.name, self.cfg.timeout
+                ));
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        if !status.success() {
+            return Err(format!("{} failed: {}", self.cfg.name, stderr.trim()));
+        }
+        Ok(stdout.trim().to_string())
+    }
+
+    pub async fn call(
+        &self,
+        arguments: Value,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        println!("Plugin {}: {}", self.cfg.name, arguments);
+
+        let plugin = self.clone();
+        let stdout = tokio::task::spawn_blocking(move || plugin.run(arguments)).await??;
+
+        match serde_json::from_str::<Value>(&stdout) {
+            Ok(json) if json["error"].is_string() => Err(json["error"].as_str().unwrap().into()),
+            Ok(json) if json["result"].is_string() => {
+                Ok(json["result"].as_str().unwrap().to_string())
+            }
+            _ => Ok(stdout),
+        }
+    }
+}
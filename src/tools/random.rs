@@ -0,0 +1,96 @@
+use ollama_rs::generation::tools::Tool;
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+const MAX_DICE: u32 = 100;
+// Keeps the total of all dice far from overflowing
+const MAX_SIDES: u32 = 1000;
+
+/**
+ * The AI can use this tool to roll dice, flip coins, pick numbers and choose between things,
+ * since models are bad at being random
+ **/
+pub struct RandomTool {}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "Either \"dice\", \"coin\", \"number\" for a number between min and max or \"pick\" to choose from the options."
+    )]
+    action: String,
+    #[schemars(description = "How many dice to roll or coins to flip, 1 by default.")]
+    count: Option<u32>,
+    #[schemars(description = "How many sides the dice have, 6 by default and at most 1000.")]
+    sides: Option<u32>,
+    #[schemars(description = "The smallest number, 1 by default.")]
+    min: Option<i64>,
+    #[schemars(description = "The largest number, 100 by default.")]
+    max: Option<i64>,
+    #[schemars(description = "What to choose from, e.g. [\"pizza\", \"sushi\", \"pasta\"].")]
+    options: Option<Vec<String>>,
+}
+
+impl Tool for RandomTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "random"
+    }
+
+    fn description() -> &'static str {
+        "Rolls dice, flips coins, picks a random number or chooses randomly from options. Use it whenever something should be left to chance."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        println!("RandomTool: {}", parameters.action);
+
+        let mut rng = rand::rng();
+        let count = parameters.count.unwrap_or(1).clamp(1, MAX_DICE);
+        match parameters.action.to_lowercase().as_str() {
+            "dice" | "die" => {
+                let sides = parameters.sides.unwrap_or(6).clamp(2, MAX_SIDES);
+                let rolls: Vec<u32> = (0..count).map(|_| rng.random_range(1..=sides)).collect();
+                if rolls.len() == 1 {
+                    return Ok(format!("Rolled a {}", rolls[0]));
+                }
+                let rolls_text: Vec<String> = rolls.iter().map(u32::to_string).collect();
+                Ok(format!(
+                    "Rolled {}, that is {} in total",
+                    rolls_text.join(", "),
+                    rolls.iter().sum::<u32>()
+                ))
+            }
+            "coin" => {
+                let flips: Vec<&str> = (0..count)
+                    .map(|_| {
+                        if rng.random_bool(0.5) {
+                            "heads"
+                        } else {
+                            "tails"
+                        }
+                    })
+                    .collect();
+                Ok(flips.join(", "))
+            }
+            "number" => {
+                let min = parameters.min.unwrap_or(1);
+                let max = parameters.max.unwrap_or(100);
+                let (min, max) = (min.min(max), min.max(max));
+                Ok(rng.random_range(min..=max).to_string())
+            }
+            "pick" => {
+                let options = parameters.options.unwrap_or_default();
+                let choice = options
+                    .choose(&mut rng)
+                    .ok_or("There are no options to choose from")?;
+                Ok(format!("Picked {}", choice))
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta};
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+use crate::tools::store;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/**
+ * Reminder related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ReminderConfig {
+    #[serde(default)]
+    pub reminders_file: Option<String>, // Enables the reminder tool, reminders are kept in this file, e.g. "reminders.json"
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reminder {
+    pub text: String,
+    pub due: String, // Local time like "2025-01-31 18:00"
+}
+
+impl Reminder {
+    fn due(&self) -> Option<DateTime<Local>> {
+        NaiveDateTime::parse_from_str(&self.due, TIME_FORMAT)
+            .ok()?
+            .and_local_timezone(Local)
+            .earliest()
+    }
+
+    /**
+     * What is said when the reminder is due, reminders missed while the bot wasn't running say when they were due
+     **/
+    pub fn announcement(&self) -> String {
+        let late = self
+            .due()
+            .is_some_and(|due| Local::now() - due > TimeDelta::minutes(5));
+        if late {
+            format!("You wanted me to remind you at {}: {}", self.due, self.text)
+        } else {
+            format!("Reminder: {}", self.text)
+        }
+    }
+}
+
+/**
+ * The reminders together with the file they are saved in after every change
+ **/
+pub struct Reminders {
+    path: String,
+    list: Vec<Reminder>,
+}
+
+impl Reminders {
+    /**
+     * Starts without reminders if the file can't be read and skips reminders without a valid time
+     **/
+    pub fn load(path: &str) -> Self {
+        let mut list: Vec<Reminder> = store::load(path).unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to load the reminders from {}, starting without them: {}",
+                path, err
+            );
+            Vec::new()
+        });
+        list.retain(|reminder| {
+            let valid = reminder.due().is_some();
+            if !valid {
+                eprintln!(
+                    "Skipping the reminder {:?}, {:?} is not a time like 2025-01-31 18:00",
+                    reminder.text, reminder.due
+                );
+            }
+            valid
+        });
+
+        Self {
+            path: path.to_string(),
+            list,
+        }
+    }
+
+    fn save(&self) {
+        if let Err(err) = store::save(&self.path, &self.list) {
+            eprintln!("Failed to save the reminders to {}: {}", self.path, err);
+        }
+    }
+
+    /**
+     * Remove and return the reminders that are due
+     **/
+    fn take_due(&mut self) -> Vec<Reminder> {
+        let now = Local::now();
+        let (due, pending): (Vec<Reminder>, Vec<Reminder>) = std::mem::take(&mut self.list)
+            .into_iter()
+            .partition(|reminder| reminder.due().is_some_and(|due| due <= now));
+        self.list = pending;
+        if !due.is_empty() {
+            self.save();
+        }
+        due
+    }
+}
+
+/**
+ * The AI can use this tool to remember things for the user and remind them at the right time,
+ * the reminders survive restarts
+ **/
+pub struct ReminderTool {
+    pub reminders: Arc<Mutex<Reminders>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "Either \"add\", \"delete\" or \"list\".")]
+    action: String,
+    #[schemars(
+        description = "What to remind the user of, e.g. \"call mom\". Deleting deletes the reminders containing it, all of them without a text."
+    )]
+    text: Option<String>,
+    #[schemars(
+        description = "When to remind the user, as \"YYYY-MM-DD HH:MM\" or \"HH:MM\" for the next time it is that late."
+    )]
+    time: Option<String>,
+}
+
+impl Tool for ReminderTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "reminder"
+    }
+
+    fn description() -> &'static str {
+        "Adds a reminder that is said out loud at the given time, deletes reminders or lists them."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let text = parameters.text.unwrap_or_default().trim().to_string();
+        println!("ReminderTool: {} {}", parameters.action, text);
+
+        let mut reminders = self.reminders.lock().unwrap();
+        match parameters.action.to_lowercase().as_str() {
+            "add" => {
+                if text.is_empty() {
+                    return Err("The text of the reminder is missing".into());
+                }
+                let time = parameters
+                    .time
+                    .ok_or("The time of the reminder is missing")?;
+                let due = parse_time(time.trim())
+                    .ok_or_else(|| format!("{} is not a time like 2025-01-31 18:00", time))?;
+
+                let reminder = Reminder {
+                    text,
+                    due: due.format(TIME_FORMAT).to_string(),
+                };
+                let answer = format!("I will remind you at {}: {}", reminder.due, reminder.text);
+                reminders.list.push(reminder);
+                reminders.save();
+                Ok(answer)
+            }
+            "delete" => {
+                let before = reminders.list.len();
+                let text = text.to_lowercase();
+                reminders
+                    .list
+                    .retain(|reminder| !reminder.text.to_lowercase().contains(&text));
+                reminders.save();
+                Ok(format!(
+                    "Deleted {} reminders",
+                    before - reminders.list.len()
+                ))
+            }
+            "list" => {
+                if reminders.list.is_empty() {
+                    return Ok("There are no reminders".to_string());
+                }
+                let list: Vec<String> = reminders
+                    .list
+                    .iter()
+                    .map(|reminder| format!("{}: {}", reminder.due, reminder.text))
+                    .collect();
+                Ok(list.join("\n"))
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
+
+/**
+ * A full date and time, or only a time which means the next time it is that late
+ **/
+fn parse_time(time: &str) -> Option<DateTime<Local>> {
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(time, TIME_FORMAT) {
+        return date_time.and_local_timezone(Local).earliest();
+    }
+
+    let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+    let now = Local::now();
+    let due = now.with_time(time).earliest()?;
+    Some(if due <= now {
+        due + TimeDelta::days(1)
+    } else {
+        due
+    })
+}
+
+/**
+ * Check the reminders in the background and send the ones that are due,
+ * the ones that were missed while the bot wasn't running are sent right away
+ **/
+pub fn spawn_reminders(reminders: Arc<Mutex<Reminders>>) -> UnboundedReceiver<Reminder> {
+    let (sender, receiver) = unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let due = reminders.lock().unwrap().take_due();
+            for reminder in due {
+                if sender.send(reminder).is_err() {
+                    return;
+                }
+            }
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    receiver
+}
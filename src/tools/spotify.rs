@@ -0,0 +1,304 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const API_URL: &str = "https://api.spotify.com/v1";
+
+/**
+ * Spotify related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct SpotifyConfig {
+    #[serde(default)]
+    pub spotify_client_id: Option<String>,
+    #[serde(default)]
+    pub spotify_client_secret: Option<String>, // Falls back to the SPOTIFY_CLIENT_SECRET environment variable
+    #[serde(default)]
+    pub spotify_refresh_token: Option<String>, // Falls back to the SPOTIFY_REFRESH_TOKEN environment variable
+    #[serde(default)]
+    pub spotify_device: Option<String>, // Name (or part of it) of the device to play on, the active one by default
+}
+
+/**
+ * The message of an error Spotify answered with
+ **/
+fn error(err: ureq::Error) -> String {
+    match err {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            let json: Value = serde_json::from_str(&body).unwrap_or_default();
+            let message = (json["error"]["message"].as_str())
+                .or(json["error_description"].as_str())
+                .unwrap_or(&body);
+            format!("Spotify answered {}: {}", code, message)
+        }
+        err => format!("Failed to reach Spotify: {}", err),
+    }
+}
+
+/**
+ * Controls Spotify Connect through the Web API, which needs Spotify Premium
+ **/
+#[derive(Clone)]
+pub struct Spotify {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    device: Option<String>,
+    token: Arc<Mutex<Option<(String, Instant)>>>, // Access token and when it expires
+}
+
+impl Spotify {
+    pub fn new(cfg: &SpotifyConfig) -> Self {
+        let secret = (cfg.spotify_client_secret.clone())
+            .or_else(|| std::env::var("SPOTIFY_CLIENT_SECRET").ok());
+        let refresh_token = (cfg.spotify_refresh_token.clone())
+            .or_else(|| std::env::var("SPOTIFY_REFRESH_TOKEN").ok());
+
+        Self {
+            client_id: (cfg.spotify_client_id.clone()).expect("spotify_client_id must be set"),
+            client_secret: secret.expect("spotify_client_secret must be set"),
+            refresh_token: refresh_token.expect("spotify_refresh_token must be set"),
+            device: cfg.spotify_device.clone(),
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /**
+     * An access token, a new one is requested with the refresh token once the old one expired
+     **/
+    fn token(&self) -> Result<String, String> {
+        let mut token = self.token.lock().unwrap();
+        if let Some((access, expires)) = &*token
+            && Instant::now() < *expires
+        {
+            return Ok(access.clone());
+        }
+
+        let credentials = STANDARD.encode(format!("{}:{}", self.client_id, self.client_secret));
+        let response = ureq::post(TOKEN_URL)
+            .set("Authorization", &format!("Basic {}", credentials))
+            .send_form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &self.refresh_token),
+            ])
+            .map_err(error)?;
+        let json: Value = serde_json::from_reader(response.into_reader())
+            .map_err(|err| format!("Invalid answer from Spotify: {}", err))?;
+
+        let access = json["access_token"]
+            .as_str()
+            .ok_or("Spotify sent no access token")?
+            .to_string();
+        let lifetime = json["expires_in"]
+            .as_u64()
+            .unwrap_or(3600)
+            .saturating_sub(60);
+        *token = Some((
+            access.clone(),
+            Instant::now() + Duration::from_secs(lifetime),
+        ));
+        Ok(access)
+    }
+
+    /**
+     * Call the Web API, returns None if it answered without content
+     **/
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> Result<Option<Value>, String> {
+        let mut request = ureq::request(method, &format!("{}{}", API_URL, path))
+            .set("Authorization", &format!("Bearer {}", self.token()?));
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+
+        let response = match (method, body) {
+            ("GET", _) => request.call(),
+            (_, Some(body)) => request
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string()),
+            (_, None) => request.send_string(""),
+        }
+        .map_err(error)?;
+
+        let body = response.into_string().map_err(|err| err.to_string())?;
+        Ok(serde_json::from_str(&body).ok())
+    }
+
+    /**
+     * The id of the configured device, None to use the active one
+     **/
+    fn device_id(&self) -> Result<Option<String>, String> {
+        let Some(name) = &self.device else {
+            return Ok(None);
+        };
+        let devices = self.request("GET", "/me/player/devices", &[], None)?;
+        let devices = devices.unwrap_or_default();
+        let device = devices["devices"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|device| {
+                (device["name"].as_str())
+                    .is_some_and(|device| device.to_lowercase().contains(&name.to_lowercase()))
+            })
+            .ok_or_else(|| format!("The Spotify device {} is not online", name))?;
+        Ok(device["id"].as_str().map(str::to_string))
+    }
+
+    /**
+     * Send a playback command to the configured device
+     **/
+    fn command(&self, method: &str, path: &str, body: Option<Value>) -> Result<(), String> {
+        let device = self.device_id()?;
+        let query: Vec<(&str, &str)> = device.iter().map(|id| ("device_id", id.as_str())).collect();
+        self.request(method, path, &query, body)?;
+        Ok(())
+    }
+
+    /**
+     * The playback state, None if nothing is playing on any device
+     **/
+    fn state(&self) -> Result<Option<Value>, String> {
+        self.request("GET", "/me/player", &[], None)
+    }
+
+    /**
+     * The uri of the best match for the query: an artist, album or song with exactly that name,
+     * otherwise a playlist, which is what fits genres and moods like "jazz" best
+     **/
+    fn search(&self, query: &str) -> Result<Option<(String, String)>, String> {
+        let params = [
+            ("q", query),
+            ("type", "artist,album,track,playlist"),
+            ("limit", "1"),
+        ];
+        let results = self.request("GET", "/search", &params, None)?;
+        let results = results.unwrap_or_default();
+        let first = |kind: &str| {
+            let item = &results[format!("{}s", kind)]["items"][0];
+            let name = item["name"].as_str()?;
+            Some((item["uri"].as_str()?.to_string(), name.to_string()))
+        };
+
+        let exact = ["artist", "album", "track"]
+            .into_iter()
+            .filter_map(first)
+            .find(|(_, name)| name.eq_ignore_ascii_case(query.trim()));
+        Ok(exact
+            .or_else(|| first("playlist"))
+            .or_else(|| first("track")))
+    }
+
+    /**
+     * What is playing right now
+     **/
+    pub fn current(&self) -> Result<String, String> {
+        let Some(state) = self.state()? else {
+            return Ok("Nothing is playing on Spotify".to_string());
+        };
+        let item = &state["item"];
+        let artists: Vec<&str> = (item["artists"].as_array().into_iter().flatten())
+            .filter_map(|artist| artist["name"].as_str())
+            .collect();
+        let title = item["name"].as_str().unwrap_or("nothing");
+        let title = if artists.is_empty() {
+            title.to_string()
+        } else {
+            format!("{} - {}", artists.join(", "), title)
+        };
+
+        Ok(if state["is_playing"].as_bool() == Some(true) {
+            format!("Playing {}", title)
+        } else {
+            format!("Paused {}", title)
+        })
+    }
+
+    pub fn playing(&self) -> bool {
+        self.state()
+            .is_ok_and(|state| state.is_some_and(|state| state["is_playing"] == true))
+    }
+
+    pub fn play(&self, query: Option<&str>) -> Result<String, String> {
+        let Some(query) = query else {
+            self.command("PUT", "/me/player/play", None)?;
+            return self.current();
+        };
+
+        let Some((uri, name)) = self.search(query)? else {
+            return Ok(format!("Found no music for {}", query));
+        };
+        let body = if uri.starts_with("spotify:track:") {
+            json!({ "uris": [uri] })
+        } else {
+            json!({ "context_uri": uri })
+        };
+        self.command("PUT", "/me/player/play", Some(body))?;
+        Ok(format!("Playing {}", name))
+    }
+
+    /**
+     * Add the best matching song to the queue, Spotify can only queue single songs
+     **/
+    pub fn queue(&self, query: &str) -> Result<String, String> {
+        let params = [("q", query), ("type", "track"), ("limit", "1")];
+        let results = self.request("GET", "/search", &params, None)?;
+        let track = &results.unwrap_or_default()["tracks"]["items"][0];
+        let (Some(uri), Some(name)) = (track["uri"].as_str(), track["name"].as_str()) else {
+            return Ok(format!("Found no song for {}", query));
+        };
+
+        let device = self.device_id()?;
+        let mut params = vec![("uri", uri)];
+        params.extend(device.iter().map(|id| ("device_id", id.as_str())));
+        self.request("POST", "/me/player/queue", &params, None)?;
+        Ok(format!("Added {} to the queue", name))
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.command("PUT", "/me/player/pause", None)
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.command("PUT", "/me/player/play", None)
+    }
+
+    pub fn next(&self) -> Result<(), String> {
+        self.command("POST", "/me/player/next", None)
+    }
+
+    pub fn previous(&self) -> Result<(), String> {
+        self.command("POST", "/me/player/previous", None)
+    }
+
+    /**
+     * The volume of the playing device from 0 to 1, None if it can't be changed
+     **/
+    pub fn volume(&self) -> Option<f32> {
+        let state = self.state().ok()??;
+        let volume = state["device"]["volume_percent"].as_u64()?;
+        Some(volume as f32 / 100.0)
+    }
+
+    pub fn set_volume(&self, volume: f32) -> Result<(), String> {
+        let percent = ((volume * 100.0).round() as u32).to_string();
+        self.request(
+            "PUT",
+            "/me/player/volume",
+            &[("volume_percent", &percent)],
+            None,
+        )?;
+        Ok(())
+    }
+}
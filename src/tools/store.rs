@@ -0,0 +1,70 @@
+use std::error::Error;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/**
+ * The data a tool keeps as JSON in a file, the default if there is no file yet.
+ * A file that can't be parsed is moved to "<path>.broken", so saving the data
+ * again doesn't overwrite what the user might still want to rescue.
+ **/
+pub fn load<T: DeserializeOwned + Default>(path: &str) -> Result<T, Box<dyn Error + Send + Sync>> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(err.into()),
+    };
+
+    serde_json::from_str(&json).map_err(|err| -> Box<dyn Error + Send + Sync> {
+        let broken = format!("{}.broken", path);
+        match std::fs::rename(path, &broken) {
+            Ok(()) => format!("{}, the file was moved to {}", err, broken).into(),
+            Err(_) => err.into(),
+        }
+    })
+}
+
+pub fn save<T: Serialize>(path: &str, data: &T) -> Result<(), Box<dyn Error + Send + Sync>> {
+    std::fs::write(path, serde_json::to_string_pretty(data)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn temp_file(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("my_ai_friend_{}_{}", std::process::id(), name));
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let path = temp_file("missing.json");
+        assert_eq!(load::<Vec<String>>(&path).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn saved_data_is_loaded() {
+        let path = temp_file("saved.json");
+        let data = BTreeMap::from([("shopping".to_string(), vec!["milk".to_string()])]);
+        save(&path, &data).unwrap();
+        assert_eq!(load::<BTreeMap<String, Vec<String>>>(&path).unwrap(), data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn broken_file_is_moved_away() {
+        let path = temp_file("broken.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        assert!(load::<Vec<String>>(&path).is_err());
+        let broken = format!("{}.broken", path);
+        assert_eq!(std::fs::read_to_string(&broken).unwrap(), "{ not json");
+        assert!(load::<Vec<String>>(&path).unwrap().is_empty());
+        std::fs::remove_file(&broken).unwrap();
+    }
+}
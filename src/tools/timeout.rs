@@ -1,19 +1,102 @@
-use std::{ops::Add, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Local, TimeDelta};
 use ollama_rs::generation::tools::Tool;
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /**
- * A tool used to temporarily disable the chatbot from responding to the user.
- * You can bring back the chatbot by saying TimeoutTool::MAGIC_WORD
+ * Timeout related settings, these are part of the main config
  **/
-pub struct TimeoutTool {
-    pub timeout: Arc<Mutex<Instant>>,
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TimeoutConfig {
+    #[serde(default = "default_magic_words")]
+    pub magic_words: Vec<String>, // Saying one of them ends a timeout early, e.g. ["cat", "wake up"]
+    #[serde(default)]
+    pub timeout_file: Option<String>, // Keeps the end of a timeout in this file, so it lasts through a restart
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            magic_words: default_magic_words(),
+            timeout_file: None,
+        }
+    }
+}
+
+fn default_magic_words() -> Vec<String> {
+    vec!["cat".to_string()]
+}
+
+/**
+ * Until when the chatbot doesn't respond, shared between the tool and the main loop
+ **/
+#[derive(Clone)]
+pub struct Timeout {
+    until: Arc<Mutex<Instant>>,
+    file: Option<String>,
+}
+
+impl Timeout {
+    /**
+     * A timeout that was still running when the program stopped is continued
+     **/
+    pub fn new(cfg: &TimeoutConfig) -> Self {
+        let remaining = cfg.timeout_file.as_deref().and_then(load);
+        if let Some(remaining) = remaining {
+            println!("Timeout continues for {} seconds", remaining.as_secs());
+        }
+
+        Self {
+            until: Arc::new(Mutex::new(Instant::now() + remaining.unwrap_or_default())),
+            file: cfg.timeout_file.clone(),
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        *self.until.lock().unwrap() > Instant::now()
+    }
+
+    pub fn set(&self, duration: Duration) {
+        *self.until.lock().unwrap() = Instant::now() + duration;
+
+        let Some(path) = &self.file else {
+            return;
+        };
+        let until = Local::now() + TimeDelta::seconds(duration.as_secs() as i64);
+        if let Err(err) = std::fs::write(path, until.to_rfc3339()) {
+            eprintln!("Failed to save the timeout to {}: {}", path, err);
+        }
+    }
+
+    pub fn clear(&self) {
+        let was_active = self.active();
+        *self.until.lock().unwrap() = Instant::now();
+
+        if was_active && let Some(path) = &self.file {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/**
+ * How much of the saved timeout is left, None if it is over or there is none
+ **/
+fn load(path: &str) -> Option<Duration> {
+    let until = std::fs::read_to_string(path).ok()?;
+    let until = DateTime::parse_from_rfc3339(until.trim()).ok()?;
+    (until.with_timezone(&Local) - Local::now()).to_std().ok()
 }
 
-impl TimeoutTool {
-    pub const MAGIC_WORD: &'static str = "cat";
+/**
+ * A tool used to temporarily disable the chatbot from responding to the user.
+ * You can bring back the chatbot by saying one of the magic words
+ **/
+pub struct TimeoutTool {
+    pub timeout: Timeout,
+    pub magic_words: Vec<String>,
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -39,8 +122,16 @@ impl Tool for TimeoutTool {
         &mut self,
         parameters: Self::Params,
     ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
-        *self.timeout.lock().unwrap() = Instant::now().add(Duration::from_secs(parameters.timeout.into()));
+        self.timeout
+            .set(Duration::from_secs(parameters.timeout.into()));
         println!("TimeoutTool: {}", parameters.timeout);
-        Ok("Timeout set to {} seconds".to_string())
+
+        match self.magic_words.first() {
+            Some(word) => Ok(format!(
+                "Timeout set to {} seconds, saying \"{}\" ends it early",
+                parameters.timeout, word
+            )),
+            None => Ok(format!("Timeout set to {} seconds", parameters.timeout)),
+        }
     }
 }
@@ -0,0 +1,188 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, TimeDelta};
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+/**
+ * Timer related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TimerConfig {
+    #[serde(default)]
+    pub timer_sound: Option<String>, // WAV file played when a timer or alarm goes off, a chime by default
+}
+
+#[derive(Clone, Debug)]
+pub struct Timer {
+    pub name: String,
+    pub due: DateTime<Local>,
+    pub alarm: bool, // Set for a time of day instead of a duration
+}
+
+impl Timer {
+    /**
+     * What is said when the timer goes off
+     **/
+    pub fn announcement(&self) -> String {
+        match (self.alarm, self.name.is_empty()) {
+            (true, true) => format!("It is {}, this is your alarm.", self.due.format("%H:%M")),
+            (true, false) => format!(
+                "It is {}, time for {}.",
+                self.due.format("%H:%M"),
+                self.name
+            ),
+            (false, true) => "Your timer is done.".to_string(),
+            (false, false) => format!("Your {} timer is done.", self.name),
+        }
+    }
+}
+
+/**
+ * The AI can use this tool to set, cancel and list timers and alarms,
+ * the main loop announces them once they went off
+ **/
+pub struct TimerTool {
+    pub timers: Arc<Mutex<Vec<Timer>>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "Either \"set\", \"cancel\" or \"list\".")]
+    action: String,
+    #[schemars(
+        description = "What the timer or alarm is for, e.g. \"pasta\". Cancelling without a name cancels all of them."
+    )]
+    name: Option<String>,
+    #[schemars(description = "For a timer, in how many seconds it should go off.")]
+    seconds: Option<u64>,
+    #[schemars(description = "For an alarm, the time of day it should go off, e.g. \"07:30\".")]
+    time: Option<String>,
+}
+
+impl Tool for TimerTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "timer"
+    }
+
+    fn description() -> &'static str {
+        "Sets a countdown timer or an alarm that rings at a time of day, cancels them or lists the ones that are running."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let name = parameters.name.unwrap_or_default().trim().to_string();
+        println!("TimerTool: {} {}", parameters.action, name);
+
+        let mut timers = self.timers.lock().unwrap();
+        match parameters.action.to_lowercase().as_str() {
+            "set" => {
+                let now = Local::now();
+                let timer = match (parameters.seconds, parameters.time) {
+                    (Some(seconds), _) => {
+                        let due = i64::try_from(seconds)
+                            .ok()
+                            .and_then(TimeDelta::try_seconds)
+                            .and_then(|delta| now.checked_add_signed(delta))
+                            .ok_or_else(|| {
+                                format!("{} seconds is too long for a timer", seconds)
+                            })?;
+                        Timer {
+                            name,
+                            due,
+                            alarm: false,
+                        }
+                    }
+                    (None, Some(time)) => {
+                        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                            .map_err(|_| format!("{} is not a time like 07:30", time))?;
+                        let mut due = now.with_time(time).single().ok_or("Invalid time")?;
+                        if due <= now {
+                            due += TimeDelta::days(1);
+                        }
+                        Timer {
+                            name,
+                            due,
+                            alarm: true,
+                        }
+                    }
+                    (None, None) => return Err("Either seconds or time is needed".into()),
+                };
+
+                let answer = format!(
+                    "{} set for {}",
+                    if timer.alarm { "Alarm" } else { "Timer" },
+                    timer.due.format("%Y-%m-%d %H:%M:%S")
+                );
+                timers.push(timer);
+                Ok(answer)
+            }
+            "cancel" => {
+                let before = timers.len();
+                timers.retain(|timer| !name.is_empty() && !timer.name.eq_ignore_ascii_case(&name));
+                Ok(format!("Cancelled {} timers", before - timers.len()))
+            }
+            "list" => {
+                if timers.is_empty() {
+                    return Ok("There are no timers".to_string());
+                }
+                let now = Local::now();
+                let list: Vec<String> = timers
+                    .iter()
+                    .map(|timer| {
+                        format!(
+                            "{} goes off at {} in {} seconds",
+                            if timer.name.is_empty() {
+                                "unnamed"
+                            } else {
+                                &timer.name
+                            },
+                            timer.due.format("%H:%M:%S"),
+                            (timer.due - now).num_seconds()
+                        )
+                    })
+                    .collect();
+                Ok(list.join("\n"))
+            }
+            action => Err(format!("Unknown action {}", action).into()),
+        }
+    }
+}
+
+/**
+ * Check the timers in the background and send the ones that went off
+ **/
+pub fn spawn_scheduler(timers: Arc<Mutex<Vec<Timer>>>) -> UnboundedReceiver<Timer> {
+    let (sender, receiver) = unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+
+            let expired: Vec<Timer> = {
+                let mut timers = timers.lock().unwrap();
+                let now = Local::now();
+                let (expired, pending) = std::mem::take(&mut *timers)
+                    .into_iter()
+                    .partition(|timer| timer.due <= now);
+                *timers = pending;
+                expired
+            };
+
+            for timer in expired {
+                if sender.send(timer).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,186 @@
+use ollama_rs::generation::chat::ChatMessage;
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::llm::{GenerationOptions, LlmBackend};
+
+/**
+ * Translation related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TranslateConfig {
+    #[serde(default)]
+    pub translate_url: Option<String>, // LibreTranslate server to translate with, e.g. "http://localhost:5000"
+    #[serde(default)]
+    pub translate_api_key: Option<String>, // Only needed by servers that require one, like libretranslate.com
+    #[serde(default)]
+    pub translate_model: Option<String>, // Otherwise translate with this model of the LLM backend, e.g. "aya-expanse:8b"
+}
+
+impl TranslateConfig {
+    pub fn enabled(&self) -> bool {
+        self.translate_url.is_some() || self.translate_model.is_some()
+    }
+}
+
+/**
+ * The AI can use this tool to translate text, so it doesn't have to make translations up
+ **/
+pub struct TranslateTool {
+    cfg: TranslateConfig,
+    backend: Box<dyn LlmBackend>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "The text to translate.")]
+    text: String,
+    #[schemars(
+        description = "The language to translate to as a two letter code, e.g. \"ja\" for Japanese."
+    )]
+    language: String,
+}
+
+impl TranslateTool {
+    /**
+     * The backend is only used when there is no LibreTranslate server
+     **/
+    pub fn new(cfg: &TranslateConfig, backend: Box<dyn LlmBackend>) -> Self {
+        Self {
+            cfg: cfg.clone(),
+            backend,
+        }
+    }
+
+    /**
+     * Translate with the model, it is told to answer with nothing but the translation
+     **/
+    async fn translate_with_model(
+        &self,
+        model: &str,
+        text: &str,
+        language: &str,
+    ) -> Result<String, String> {
+        let messages = vec![
+            ChatMessage::system(format!(
+                "Translate the text of the user to the language with the code \"{}\". Answer only with the translation.",
+                language
+            )),
+            ChatMessage::user(text.to_string()),
+        ];
+        let options = GenerationOptions {
+            temperature: Some(0.0),
+            ..Default::default()
+        };
+
+        let mut reply = String::new();
+        let mut receiver = self
+            .backend
+            .stream_chat(model, messages, Vec::new(), &options);
+        while let Some(chunk) = receiver.recv().await {
+            let delta = chunk.map_err(|err| format!("Translation failed: {}", err))?;
+            reply.push_str(&delta.content);
+            if delta.done {
+                break;
+            }
+        }
+
+        // Thinking models put the translation after the thoughts
+        let reply = match reply.rfind("</think>") {
+            Some(i) => &reply[i + 8..],
+            None => &reply,
+        };
+        Ok(reply.trim().to_string())
+    }
+}
+
+/**
+ * Translate with a LibreTranslate server, the language of the text is detected by it
+ **/
+fn translate_with_server(
+    url: &str,
+    api_key: Option<&str>,
+    text: &str,
+    language: &str,
+) -> Result<String, String> {
+    let mut body = json!({
+        "q": text,
+        "source": "auto",
+        "target": language,
+        "format": "text",
+    });
+    if let Some(api_key) = api_key {
+        body["api_key"] = api_key.into();
+    }
+
+    let url = format!("{}/translate", url.trim_end_matches('/'));
+    let response = ureq::post(&url)
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .map_err(|err| match err {
+            ureq::Error::Status(_, response) => {
+                let json: Value =
+                    serde_json::from_reader(response.into_reader()).unwrap_or_default();
+                match json["error"].as_str() {
+                    Some(error) => format!("Translation failed: {}", error),
+                    None => "Translation failed".to_string(),
+                }
+            }
+            err => format!("Failed to reach {}: {}", url, err),
+        })?;
+    let json: Value = serde_json::from_reader(response.into_reader())
+        .map_err(|err| format!("Invalid answer from {}: {}", url, err))?;
+
+    json["translatedText"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "The translation is missing".to_string())
+}
+
+impl Tool for TranslateTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "translate"
+    }
+
+    fn description() -> &'static str {
+        "Translates text to another language, use it whenever the user asks how to say something in another language."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let language = parameters.language.trim().to_lowercase();
+        println!("TranslateTool: {} to {}", parameters.text, language);
+
+        let translation = match (&self.cfg.translate_url, &self.cfg.translate_model) {
+            (Some(url), _) => {
+                let url = url.clone();
+                let api_key = self.cfg.translate_api_key.clone();
+                let text = parameters.text.clone();
+                let language = language.clone();
+                tokio::task::spawn_blocking(move || {
+                    translate_with_server(&url, api_key.as_deref(), &text, &language)
+                })
+                .await??
+            }
+            (None, Some(model)) => {
+                self.translate_with_model(model, &parameters.text, &language)
+                    .await?
+            }
+            (None, None) => return Err("No translation server or model is set".into()),
+        };
+        if translation.is_empty() {
+            return Err("The translation is empty".into());
+        }
+
+        Ok(format!(
+            "{} in {} is: {}",
+            parameters.text, language, translation
+        ))
+    }
+}
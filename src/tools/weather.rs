@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/**
+ * Weather related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct WeatherConfig {
+    #[serde(default)]
+    pub weather_location: Option<String>, // Enables the weather tool, a city like "Vienna" or coordinates like "48.21,16.37"
+    #[serde(default)]
+    pub weather_fahrenheit: bool, // Temperatures in Fahrenheit and wind in mph
+}
+
+/**
+ * The AI can use this tool to get the current weather and the forecast from Open-Meteo, no API key needed
+ **/
+pub struct WeatherTool {
+    cfg: WeatherConfig,
+    places: HashMap<String, Place>, // Already looked up locations
+}
+
+#[derive(Clone)]
+struct Place {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(
+        description = "A city to get the weather for, leave it empty for where the user lives."
+    )]
+    location: Option<String>,
+    #[schemars(
+        description = "For how many days, including today, the forecast is needed, 1 to 7."
+    )]
+    days: Option<u8>,
+}
+
+impl WeatherTool {
+    pub fn new(cfg: &WeatherConfig) -> Self {
+        Self {
+            cfg: cfg.clone(),
+            places: HashMap::new(),
+        }
+    }
+}
+
+impl Tool for WeatherTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "weather"
+    }
+
+    fn description() -> &'static str {
+        "Returns the current weather and the forecast, e.g. to know if an umbrella is needed."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        let location = parameters
+            .location
+            .filter(|location| !location.trim().is_empty())
+            .or_else(|| self.cfg.weather_location.clone())
+            .ok_or("No location was given")?;
+        let days = parameters.days.unwrap_or(3).clamp(1, 7);
+        println!("WeatherTool: {} for {} days", location, days);
+
+        let place = match self.places.get(&location) {
+            Some(place) => place.clone(),
+            None => {
+                let name = location.clone();
+                let place = tokio::task::spawn_blocking(move || find_place(&name)).await??;
+                self.places.insert(location, place.clone());
+                place
+            }
+        };
+
+        let fahrenheit = self.cfg.weather_fahrenheit;
+        let forecast =
+            tokio::task::spawn_blocking(move || fetch_forecast(&place, days, fahrenheit)).await??;
+        Ok(forecast)
+    }
+}
+
+/**
+ * Coordinates are used as they are, everything else is looked up with the Open-Meteo geocoding
+ **/
+fn find_place(location: &str) -> Result<Place, Box<dyn std::error::Error + Sync + Send>> {
+    if let Some((latitude, longitude)) = location.split_once(',')
+        && let (Ok(latitude), Ok(longitude)) = (latitude.trim().parse(), longitude.trim().parse())
+    {
+        return Ok(Place {
+            name: location.to_string(),
+            latitude,
+            longitude,
+        });
+    }
+
+    let response = ureq::get("https://geocoding-api.open-meteo.com/v1/search")
+        .query("name", location)
+        .query("count", "1")
+        .call()?;
+    let response: Value = serde_json::from_reader(response.into_reader())?;
+
+    let result = &response["results"][0];
+    let (Some(latitude), Some(longitude)) =
+        (result["latitude"].as_f64(), result["longitude"].as_f64())
+    else {
+        return Err(format!("Couldn't find a place called {}", location).into());
+    };
+
+    let name = [&result["name"], &result["admin1"], &result["country"]]
+        .iter()
+        .filter_map(|part| part.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(Place {
+        name,
+        latitude,
+        longitude,
+    })
+}
+
+fn fetch_forecast(
+    place: &Place,
+    days: u8,
+    fahrenheit: bool,
+) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+    let mut request = ureq::get("https://api.open-meteo.com/v1/forecast")
+        .query("latitude", &place.latitude.to_string())
+        .query("longitude", &place.longitude.to_string())
+        .query(
+            "current",
+            "temperature_2m,apparent_temperature,precipitation,weather_code,wind_speed_10m",
+        )
+        .query(
+            "daily",
+            "weather_code,temperature_2m_max,temperature_2m_min,precipitation_probability_max,precipitation_sum",
+        )
+        .query("forecast_days", &days.to_string())
+        .query("timezone", "auto");
+    if fahrenheit {
+        request = request
+            .query("temperature_unit", "fahrenheit")
+            .query("wind_speed_unit", "mph")
+            .query("precipitation_unit", "inch");
+    }
+    let response: Value = serde_json::from_reader(request.call()?.into_reader())?;
+
+    let (degrees, speed, amount) = if fahrenheit {
+        ("°F", "mph", "inch")
+    } else {
+        ("°C", "km/h", "mm")
+    };
+
+    let current = &response["current"];
+    let mut text = format!(
+        "Weather in {}: now {}, {}{} (feels like {}{}), wind {} {}, precipitation {} {}.",
+        place.name,
+        describe(&current["weather_code"]),
+        current["temperature_2m"],
+        degrees,
+        current["apparent_temperature"],
+        degrees,
+        current["wind_speed_10m"],
+        speed,
+        current["precipitation"],
+        amount
+    );
+
+    let daily = &response["daily"];
+    let dates = daily["time"].as_array().cloned().unwrap_or_default();
+    for (i, date) in dates.iter().enumerate() {
+        text.push_str(&format!(
+            "\n{}: {}, {} to {}{}, {}% chance of rain, {} {}.",
+            date.as_str().unwrap_or_default(),
+            describe(&daily["weather_code"][i]),
+            daily["temperature_2m_min"][i],
+            daily["temperature_2m_max"][i],
+            degrees,
+            daily["precipitation_probability_max"][i],
+            daily["precipitation_sum"][i],
+            amount
+        ));
+    }
+    Ok(text)
+}
+
+/**
+ * Open-Meteo reports the weather as WMO code
+ **/
+fn describe(code: &Value) -> &'static str {
+    match code.as_u64().unwrap_or(u64::MAX) {
+        0 => "clear sky",
+        1 => "mainly clear",
+        2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51 | 53 | 55 => "drizzle",
+        56 | 57 => "freezing drizzle",
+        61 => "light rain",
+        63 => "rain",
+        65 => "heavy rain",
+        66 | 67 => "freezing rain",
+        71 => "light snow",
+        73 => "snow",
+        75 => "heavy snow",
+        77 => "snow grains",
+        80..=82 => "rain showers",
+        85 | 86 => "snow showers",
+        95 => "thunderstorm",
+        96 | 99 => "thunderstorm with hail",
+        _ => "unknown weather",
+    }
+}
@@ -0,0 +1,123 @@
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ollama_rs::generation::images::Image;
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/**
+ * Webcam related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct WebcamConfig {
+    #[serde(default)]
+    pub webcam: Option<String>, // Enables the webcam tool, the camera to take pictures with, e.g. "/dev/video0", needs a vision model
+    #[serde(default)]
+    pub webcam_command: Option<Vec<String>>, // Program that prints a JPEG or PNG of the camera, {device} is replaced, ffmpeg by default
+}
+
+/**
+ * Takes a single frame with ffmpeg. The first frames are often too dark while the camera
+ * adjusts its exposure, so the first half second is skipped.
+ **/
+fn default_command() -> Vec<String> {
+    [
+        "ffmpeg",
+        "-loglevel",
+        "error",
+        "-f",
+        "v4l2",
+        "-i",
+        "{device}",
+        "-ss",
+        "0.5",
+        "-frames:v",
+        "1",
+        "-f",
+        "image2",
+        "-c:v",
+        "mjpeg",
+        "-",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/**
+ * The AI can use this tool to look through the webcam, e.g. when the user asks what they are holding.
+ * The picture is shown to the model together with the result of the tool.
+ **/
+pub struct WebcamTool {
+    device: String,
+    command: Vec<String>,
+    attachments: Arc<Mutex<Vec<Image>>>,
+}
+
+impl WebcamTool {
+    pub fn new(cfg: &WebcamConfig, attachments: Arc<Mutex<Vec<Image>>>) -> Self {
+        let command = cfg.webcam_command.clone().unwrap_or_else(default_command);
+        assert!(!command.is_empty(), "webcam_command is empty");
+
+        Self {
+            device: cfg.webcam.clone().unwrap_or_default(),
+            command,
+            attachments,
+        }
+    }
+
+    fn take_picture(device: &str, command: &[String]) -> Result<Vec<u8>, String> {
+        let args = command[1..]
+            .iter()
+            .map(|arg| arg.replace("{device}", device));
+        let output = Command::new(&command[0])
+            .args(args)
+            .output()
+            .map_err(|err| format!("Failed to run {}: {}", command[0], err))?;
+
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Failed to take a picture: {}", err.trim()));
+        }
+
+        // Only JPEG and PNG, anything else is probably an error message
+        let picture = output.stdout;
+        if !picture.starts_with(&[0xFF, 0xD8]) && !picture.starts_with(b"\x89PNG") {
+            return Err("The camera didn't return a picture".to_string());
+        }
+        Ok(picture)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {}
+
+impl Tool for WebcamTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "webcam"
+    }
+
+    fn description() -> &'static str {
+        "Takes a picture with the webcam and shows it to you. Use it when the user asks about something you would have to see, e.g. what they are holding or wearing."
+    }
+
+    async fn call(
+        &mut self,
+        _parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        println!("WebcamTool: {}", self.device);
+
+        let device = self.device.clone();
+        let command = self.command.clone();
+        let picture =
+            tokio::task::spawn_blocking(move || Self::take_picture(&device, &command)).await??;
+
+        let image = Image::from_base64(STANDARD.encode(picture));
+        self.attachments.lock().unwrap().push(image);
+        Ok("The picture from the webcam is attached, answer with what you see".to_string())
+    }
+}
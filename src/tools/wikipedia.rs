@@ -0,0 +1,118 @@
+use ollama_rs::generation::tools::Tool;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/**
+ * Wikipedia related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WikipediaConfig {
+    #[serde(default = "default_wikipedia_language")]
+    pub wikipedia_language: String, // Which Wikipedia to search, e.g. "de"
+    #[serde(default = "default_wikipedia_max_chars")]
+    pub wikipedia_max_chars: usize, // Longer articles are cut off, so they don't fill the whole context
+}
+
+impl Default for WikipediaConfig {
+    fn default() -> Self {
+        Self {
+            wikipedia_language: default_wikipedia_language(),
+            wikipedia_max_chars: default_wikipedia_max_chars(),
+        }
+    }
+}
+
+fn default_wikipedia_language() -> String {
+    "en".to_string()
+}
+
+fn default_wikipedia_max_chars() -> usize {
+    2000
+}
+
+/**
+ * The AI can use this tool to look up facts on Wikipedia instead of making them up
+ **/
+pub struct WikipediaTool {
+    pub cfg: WikipediaConfig,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct Params {
+    #[schemars(description = "What to search for, e.g. the name of a person, place or thing.")]
+    query: String,
+}
+
+impl Tool for WikipediaTool {
+    type Params = Params;
+
+    fn name() -> &'static str {
+        "wikipedia"
+    }
+
+    fn description() -> &'static str {
+        "Returns the beginning of the Wikipedia article that fits the query best, use it for factual questions."
+    }
+
+    async fn call(
+        &mut self,
+        parameters: Self::Params,
+    ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+        println!("WikipediaTool: {}", parameters.query);
+        let cfg = self.cfg.clone();
+        tokio::task::spawn_blocking(move || lookup(&cfg, &parameters.query)).await?
+    }
+}
+
+fn lookup(
+    cfg: &WikipediaConfig,
+    query: &str,
+) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+    let url = format!("https://{}.wikipedia.org/w/api.php", cfg.wikipedia_language);
+    let response = ureq::get(&url)
+        .set(
+            "User-Agent",
+            "my_ai_friend (https://github.com/danielfvm/my_ai_friend)",
+        )
+        .query("action", "query")
+        .query("format", "json")
+        .query("generator", "search")
+        .query("gsrsearch", query)
+        .query("gsrlimit", "1")
+        .query("prop", "extracts")
+        .query("explaintext", "1")
+        .query("redirects", "1")
+        .call()?;
+    let response: Value = serde_json::from_reader(response.into_reader())?;
+
+    let Some(page) = response["query"]["pages"]
+        .as_object()
+        .and_then(|pages| pages.values().next())
+    else {
+        return Ok(format!("Wikipedia has no article about {}", query));
+    };
+
+    let title = page["title"].as_str().unwrap_or(query);
+    let extract = page["extract"].as_str().unwrap_or_default();
+    Ok(format!(
+        "Wikipedia article \"{}\":\n{}",
+        title,
+        truncate(extract, cfg.wikipedia_max_chars)
+    ))
+}
+
+/**
+ * Cuts the text after the last sentence that fits into max_chars
+ **/
+pub fn truncate(text: &str, max_chars: usize) -> &str {
+    let Some((end, _)) = text.char_indices().nth(max_chars) else {
+        return text;
+    };
+
+    let text = &text[..end];
+    match text.rfind(". ").or_else(|| text.rfind('\n')) {
+        Some(i) if i > end / 2 => &text[..=i],
+        _ => text,
+    }
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+use std::sync::Mutex;
+
+use crate::tts::style::VoiceStyle;
+use crate::tts::{Speech, TtsEngine};
+
+/**
+ * Remembers the audio of the last synthesized sentences, so phrases the bot
+ * says often like greetings are played instantly instead of synthesized again.
+ * The least recently used sentence is dropped once the cache is full.
+ **/
+pub struct CachedTts {
+    engine: Box<dyn TtsEngine>,
+    voice: String,
+    capacity: usize,
+    entries: Mutex<Vec<((String, String), Speech)>>, // Most recently used last
+}
+
+impl CachedTts {
+    /**
+     * voice identifies the voice of the engine, it is part of the key so
+     * a sentence spoken by another voice isn't taken from the cache
+     **/
+    pub fn new(engine: Box<dyn TtsEngine>, voice: String, capacity: usize) -> Self {
+        Self {
+            engine,
+            voice,
+            capacity,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl TtsEngine for CachedTts {
+    fn synthesize(
+        &self,
+        text: &str,
+        style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+        let voice = format!("{}:{:?}", self.voice, style);
+        let key = (voice, text.trim().to_lowercase());
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(index) = entries.iter().position(|(k, _)| *k == key) {
+                let entry = entries.remove(index);
+                let speech = entry.1.clone();
+                entries.push(entry);
+                return Ok(speech);
+            }
+        }
+
+        // Not locked while synthesizing, so other sentences can use the cache meanwhile
+        let speech = self.engine.synthesize(text, style)?;
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.is_empty() {
+            entries.remove(0);
+        }
+        entries.push((key, speech.clone()));
+
+        Ok(speech)
+    }
+}
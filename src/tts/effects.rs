@@ -0,0 +1,33 @@
+use std::error::Error;
+
+use crate::audio::effects::Effect;
+use crate::tts::style::VoiceStyle;
+use crate::tts::{Speech, TtsEngine};
+
+/**
+ * Runs the synthesized voice through the configured effects, one after the other
+ **/
+pub struct EffectsTts {
+    engine: Box<dyn TtsEngine>,
+    effects: Vec<Effect>,
+}
+
+impl EffectsTts {
+    pub fn new(engine: Box<dyn TtsEngine>, effects: Vec<Effect>) -> Self {
+        Self { engine, effects }
+    }
+}
+
+impl TtsEngine for EffectsTts {
+    fn synthesize(
+        &self,
+        text: &str,
+        style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+        let mut speech = self.engine.synthesize(text, style)?;
+        for effect in &self.effects {
+            speech.samples = effect.apply(&speech.samples, speech.sample_rate, speech.channels);
+        }
+        Ok(speech)
+    }
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tts::style::VoiceStyle;
+use crate::tts::{Speech, TtsEngine, decode_wav};
+
+/**
+ * Words per minute espeak-ng talks by default
+ **/
+const DEFAULT_SPEED: f32 = 175.0;
+
+/**
+ * espeak-ng related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct EspeakConfig {
+    #[serde(default)]
+    pub espeak_voice: Option<String>, // Voice or language for espeak-ng, e.g. "en-gb" or "de"
+}
+
+/**
+ * Text to speech by running the espeak-ng command
+ **/
+pub struct EspeakTts {
+    voice: Option<String>,
+    speed: f32,
+}
+
+impl EspeakTts {
+    pub fn new(cfg: &EspeakConfig, speech_rate: Option<f32>) -> Self {
+        Self {
+            voice: cfg.espeak_voice.clone(),
+            speed: DEFAULT_SPEED * speech_rate.unwrap_or(1.0),
+        }
+    }
+}
+
+impl TtsEngine for EspeakTts {
+    fn synthesize(
+        &self,
+        text: &str,
+        style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+        let speed = self.speed * style.speech_rate.unwrap_or(1.0);
+
+        let mut command = Command::new("espeak-ng");
+        command
+            .arg("--stdout")
+            .arg("-s")
+            .arg((speed as u32).to_string());
+        if let Some(voice) = style.espeak_voice.as_ref().or(self.voice.as_ref()) {
+            command.arg("-v").arg(voice);
+        }
+
+        // "--" so text starting with a dash isn't taken as an option
+        let output = command.arg("--").arg(text).output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into());
+        }
+
+        decode_wav(output.stdout)
+    }
+}
@@ -0,0 +1,50 @@
+use std::error::Error;
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tts::style::VoiceStyle;
+use crate::tts::{Speech, TtsEngine, decode_wav};
+
+/**
+ * HTTP text to speech related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct HttpConfig {
+    #[serde(default)]
+    pub tts_url: Option<String>, // e.g. "http://localhost:5002/api/tts" for a Coqui TTS server
+}
+
+/**
+ * Text to speech using a server, the text is sent as the `text` query parameter
+ * and a WAV file is expected back. This works with the Coqui TTS and Piper servers.
+ * Voice styles aren't supported, the server decides how the voice sounds.
+ **/
+pub struct HttpTts {
+    url: String,
+}
+
+impl HttpTts {
+    pub fn new(cfg: &HttpConfig) -> Self {
+        let url = cfg
+            .tts_url
+            .clone()
+            .expect("tts_url must be set to use the http text to speech");
+
+        Self { url }
+    }
+}
+
+impl TtsEngine for HttpTts {
+    fn synthesize(
+        &self,
+        text: &str,
+        _style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+        let response = ureq::get(&self.url).query("text", text).call()?;
+
+        let mut wav = Vec::new();
+        response.into_reader().read_to_end(&mut wav)?;
+        decode_wav(wav)
+    }
+}
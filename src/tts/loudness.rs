@@ -0,0 +1,37 @@
+use std::error::Error;
+
+use crate::audio::loudness::normalize_loudness;
+use crate::tts::style::VoiceStyle;
+use crate::tts::{Speech, TtsEngine};
+
+/**
+ * Brings every sentence to the same loudness, voices and engines
+ * differ a lot in how loud they are and sentences would too
+ **/
+pub struct NormalizedTts {
+    engine: Box<dyn TtsEngine>,
+    target: f32,
+}
+
+impl NormalizedTts {
+    pub fn new(engine: Box<dyn TtsEngine>, target: f32) -> Self {
+        Self { engine, target }
+    }
+}
+
+impl TtsEngine for NormalizedTts {
+    fn synthesize(
+        &self,
+        text: &str,
+        style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+        let mut speech = self.engine.synthesize(text, style)?;
+        normalize_loudness(
+            &mut speech.samples,
+            speech.sample_rate,
+            speech.channels,
+            self.target,
+        );
+        Ok(speech)
+    }
+}
@@ -0,0 +1,166 @@
+pub mod cache;
+pub mod effects;
+pub mod espeak;
+pub mod http;
+pub mod loudness;
+pub mod piper;
+pub mod style;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Cursor;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::effects::Effect;
+use crate::tts::cache::CachedTts;
+use crate::tts::effects::EffectsTts;
+use crate::tts::espeak::{EspeakConfig, EspeakTts};
+use crate::tts::http::{HttpConfig, HttpTts};
+use crate::tts::loudness::NormalizedTts;
+use crate::tts::piper::{PiperConfig, PiperTts};
+use crate::tts::style::VoiceStyle;
+
+/**
+ * Audio the text to speech engine generated
+ **/
+#[derive(Clone)]
+pub struct Speech {
+    pub samples: Vec<f32>, // Interleaved if there are multiple channels
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/**
+ * A text to speech engine turns the responses into audio.
+ * It is shared with the threads that synthesize, so it can't be mutable.
+ * Engines ignore the parts of the style they don't support.
+ **/
+pub trait TtsEngine: Send + Sync {
+    fn synthesize(
+        &self,
+        text: &str,
+        style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>>;
+}
+
+/**
+ * The available text to speech engines, selected with `tts` in the config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TtsKind {
+    #[default]
+    Piper, // Neural voices running locally
+    Espeak, // Robotic but very fast, needs espeak-ng to be installed
+    Http,   // Any server that returns a WAV file for GET <tts_url>?text=...
+}
+
+/**
+ * Text to speech related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct TtsConfig {
+    #[serde(default)]
+    pub tts: TtsKind, // Which text to speech engine to use: "piper", "espeak" or "http"
+    #[serde(default)]
+    pub speech_rate: Option<f32>, // How fast the bot talks, e.g. 1.5 is 50% faster than the voice normally is
+    #[serde(default)]
+    pub voice_effects: Vec<Effect>, // Effects applied to the voice in this order, e.g. [{"effect": "robot"}]
+    #[serde(default = "default_tts_loudness")]
+    pub tts_loudness: f32, // RMS level every sentence is brought to, so all voices are equally loud, 0 turns it off
+    #[serde(default = "default_tts_volume")]
+    pub tts_volume: f32, // Volume the responses are played with, e.g. 0.5 is half as loud
+    #[serde(default = "default_tts_cache_size")]
+    pub tts_cache_size: usize, // How many synthesized sentences are kept to be played again, 0 turns it off
+    #[serde(default)]
+    pub voice_tags: HashMap<String, VoiceStyle>, // Styles the LLM can switch to with tags like [whisper]
+
+    #[serde(flatten)]
+    pub piper: PiperConfig,
+    #[serde(flatten)]
+    pub espeak: EspeakConfig,
+    #[serde(flatten)]
+    pub http: HttpConfig,
+}
+
+fn default_tts_loudness() -> f32 {
+    0.15
+}
+
+fn default_tts_volume() -> f32 {
+    1.0
+}
+
+fn default_tts_cache_size() -> usize {
+    64
+}
+
+impl TtsConfig {
+    /**
+     * Everything that changes how the voice sounds, sentences are only
+     * taken from the cache if they were spoken by the same voice
+     **/
+    fn voice(&self) -> String {
+        let voice = match self.tts {
+            TtsKind::Piper => format!(
+                "{}:{:?}:{:?}:{:?}",
+                self.piper.piper,
+                self.piper.piper_speaker,
+                self.piper.noise_scale,
+                self.piper.noise_w
+            ),
+            TtsKind::Espeak => format!("{:?}", self.espeak.espeak_voice),
+            TtsKind::Http => format!("{:?}", self.http.tts_url),
+        };
+        format!("{:?}:{}:{:?}", self.tts, voice, self.speech_rate)
+    }
+}
+
+pub fn create_tts(cfg: &TtsConfig) -> Box<dyn TtsEngine> {
+    let mut engine: Box<dyn TtsEngine> = match cfg.tts {
+        TtsKind::Piper => Box::new(PiperTts::new(&cfg.piper, cfg.speech_rate)),
+        TtsKind::Espeak => Box::new(EspeakTts::new(&cfg.espeak, cfg.speech_rate)),
+        TtsKind::Http => Box::new(HttpTts::new(&cfg.http)),
+    };
+
+    if !cfg.voice_effects.is_empty() {
+        engine = Box::new(EffectsTts::new(engine, cfg.voice_effects.clone()));
+    }
+
+    if cfg.tts_loudness > 0.0 {
+        engine = Box::new(NormalizedTts::new(engine, cfg.tts_loudness));
+    }
+
+    if cfg.tts_cache_size == 0 {
+        return engine;
+    }
+    Box::new(CachedTts::new(engine, cfg.voice(), cfg.tts_cache_size))
+}
+
+/**
+ * Decode a WAV file that an engine returned
+ **/
+pub fn decode_wav(bytes: Vec<u8>) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+    let reader = hound::WavReader::new(Cursor::new(bytes))?;
+    let spec = reader.spec();
+
+    // Streamed WAV files don't know their length, so read until the samples end
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().map_while(Result::ok).collect(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map_while(Result::ok)
+                .map(|sample| sample as f32 / scale)
+                .collect()
+        }
+    };
+
+    Ok(Speech {
+        samples,
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+    })
+}
@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::Mutex;
+
+use piper_rs::PiperSynthesisConfig;
+use piper_rs::synth::PiperSpeechSynthesizer;
+use serde::{Deserialize, Serialize};
+
+use crate::tts::style::VoiceStyle;
+use crate::tts::{Speech, TtsEngine};
+
+/**
+ * Piper related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct PiperConfig {
+    #[serde(default)]
+    pub piper: String, // Path to the .onnx.json of the Piper voice
+    #[serde(default)]
+    pub piper_speaker: Option<i64>, // Which speaker to use for voices with multiple speakers
+    #[serde(default)]
+    pub noise_scale: Option<f32>, // How much the voice varies, uses the value of the voice by default
+    #[serde(default)]
+    pub noise_w: Option<f32>, // How much the length of the sounds varies, uses the value of the voice by default
+}
+
+/**
+ * Text to speech using Piper
+ **/
+pub struct PiperTts {
+    synth: PiperSpeechSynthesizer,
+    voice: PiperSynthesisConfig,
+    styling: Mutex<()>, // The voice settings are shared by the model, only one style can be used at a time
+}
+
+impl PiperTts {
+    pub fn new(cfg: &PiperConfig, speech_rate: Option<f32>) -> Self {
+        let model = piper_rs::from_config_path(Path::new(&cfg.piper))
+            .expect("Failed to load config file for Piper model");
+
+        // Apply the voice settings, everything not set keeps the value from the .onnx.json
+        let mut voice = model
+            .get_fallback_synthesis_config()
+            .ok()
+            .and_then(|voice| voice.downcast::<PiperSynthesisConfig>().ok())
+            .expect("Failed to read Piper voice settings");
+        if let Some(speaker) = cfg.piper_speaker {
+            voice.speaker = Some(speaker);
+        }
+        if let Some(speech_rate) = speech_rate {
+            voice.length_scale /= speech_rate;
+        }
+        if let Some(noise_scale) = cfg.noise_scale {
+            voice.noise_scale = noise_scale;
+        }
+        if let Some(noise_w) = cfg.noise_w {
+            voice.noise_w = noise_w;
+        }
+        model
+            .set_fallback_synthesis_config(&*voice)
+            .expect("Invalid Piper voice settings");
+
+        let synth = PiperSpeechSynthesizer::new(model).expect("Failed to load Piper model");
+        Self {
+            synth,
+            voice: *voice,
+            styling: Mutex::new(()),
+        }
+    }
+}
+
+impl TtsEngine for PiperTts {
+    fn synthesize(
+        &self,
+        text: &str,
+        style: &VoiceStyle,
+    ) -> Result<Speech, Box<dyn Error + Send + Sync>> {
+        let _styling = self.styling.lock().unwrap();
+
+        let mut voice = self.voice.clone();
+        if let Some(speaker) = style.speaker {
+            voice.speaker = Some(speaker);
+        }
+        if let Some(speech_rate) = style.speech_rate {
+            voice.length_scale /= speech_rate;
+        }
+        if let Some(noise_scale) = style.noise_scale {
+            voice.noise_scale = noise_scale;
+        }
+        if let Some(noise_w) = style.noise_w {
+            voice.noise_w = noise_w;
+        }
+        self.synth
+            .clone_model()
+            .set_fallback_synthesis_config(&voice)?;
+
+        let mut samples = Vec::new();
+        let mut sample_rate = 22_050;
+        let mut channels = 1;
+
+        for audio in self.synth.synthesize_parallel(text.to_string(), None)? {
+            let audio = audio?;
+            sample_rate = audio.info.sample_rate;
+            channels = audio.info.num_channels;
+            samples.append(&mut audio.into_vec());
+        }
+
+        Ok(Speech {
+            samples,
+            sample_rate: sample_rate as u32,
+            channels: channels as u16,
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/**
+ * How the voice is changed by a tag like [whisper], everything not set stays as configured
+ **/
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct VoiceStyle {
+    #[serde(default)]
+    pub speaker: Option<i64>, // Piper speaker to switch to
+    #[serde(default)]
+    pub speech_rate: Option<f32>, // Relative to the normal speech_rate, e.g. 0.8 is 20% slower
+    #[serde(default)]
+    pub noise_scale: Option<f32>, // Piper noise_scale to use instead
+    #[serde(default)]
+    pub noise_w: Option<f32>, // Piper noise_w to use instead
+    #[serde(default)]
+    pub espeak_voice: Option<String>, // espeak-ng voice to switch to
+}
+
+impl VoiceStyle {
+    /**
+     * This style with everything it doesn't set taken from `base`
+     **/
+    pub fn or(&self, base: &VoiceStyle) -> VoiceStyle {
+        VoiceStyle {
+            speaker: self.speaker.or(base.speaker),
+            speech_rate: self.speech_rate.or(base.speech_rate),
+            noise_scale: self.noise_scale.or(base.noise_scale),
+            noise_w: self.noise_w.or(base.noise_w),
+            espeak_voice: self.espeak_voice.clone().or(base.espeak_voice.clone()),
+        }
+    }
+}
+
+/**
+ * Split a response at tags like [whisper], [excited] or [voice:alt] into the parts that are
+ * spoken with the same style. A tag applies until the next one, tags that aren't in `styles`
+ * switch back to the `base` voice. The tags themselves are removed. `style` is the style the
+ * text starts with, it is updated so the response can be split one sentence at a time.
+ **/
+pub fn split_styles(
+    text: &str,
+    styles: &HashMap<String, VoiceStyle>,
+    base: &VoiceStyle,
+    style: &mut VoiceStyle,
+) -> Vec<(VoiceStyle, String)> {
+    let regex = Regex::new(r"\[(?:voice:)?([A-Za-z_-]+)\]").unwrap();
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for caps in regex.captures_iter(text) {
+        let tag = caps.get(0).unwrap();
+        parts.push((style.clone(), text[start..tag.start()].to_string()));
+
+        *style = match styles.get(&caps[1].to_lowercase()) {
+            Some(tagged) => tagged.or(base),
+            None => base.clone(),
+        };
+        start = tag.end();
+    }
+    parts.push((style.clone(), text[start..].to_string()));
+
+    parts.retain(|(_, part)| !part.trim().is_empty());
+    parts
+}
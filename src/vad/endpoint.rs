@@ -0,0 +1,50 @@
+use crate::vad::Vad;
+
+/**
+ * Wraps another VAD to smooth its decisions over time
+ * Speech only starts once it lasted for at least min_speech samples, so short noises like
+ * a cough or a door slam are ignored. After speech ends it is still reported for another
+ * hangover samples, which bridges short pauses between words.
+ **/
+pub struct Endpointer {
+    inner: Box<dyn Vad>,
+    min_speech: usize,
+    hangover: usize,
+
+    speech_run: usize, // Samples of uninterrupted speech so far
+    hangover_left: usize,
+    active: bool,
+}
+
+impl Endpointer {
+    pub fn new(inner: Box<dyn Vad>, min_speech: usize, hangover: usize) -> Self {
+        Self {
+            inner,
+            min_speech,
+            hangover,
+            speech_run: 0,
+            hangover_left: 0,
+            active: false,
+        }
+    }
+}
+
+impl Vad for Endpointer {
+    fn is_speech(&mut self, samples: &[f32]) -> bool {
+        if self.inner.is_speech(samples) {
+            self.speech_run += samples.len();
+            if self.speech_run >= self.min_speech {
+                self.active = true;
+                self.hangover_left = self.hangover;
+            }
+        } else {
+            self.speech_run = 0;
+            self.hangover_left = self.hangover_left.saturating_sub(samples.len());
+            if self.hangover_left == 0 {
+                self.active = false;
+            }
+        }
+
+        self.active
+    }
+}
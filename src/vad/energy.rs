@@ -0,0 +1,88 @@
+use crate::vad::Vad;
+
+/**
+ * How fast the noise floor follows the volume of non-speech audio when adapting
+ **/
+const ADAPT_RATE: f32 = 0.05;
+
+/**
+ * The simplest VAD, everything louder than the threshold is considered speech.
+ * Optionally the threshold is derived from the noise floor of the room, which is
+ * measured at startup and can keep adapting while nobody is speaking.
+ **/
+pub struct EnergyVad {
+    threshold: f32,
+    noise_floor_factor: f32,
+    noise_floor: f32,
+    adapt: bool,
+
+    calibration_samples: usize, // Samples left to measure before calibration is done
+    calibration_sum: f32,
+    calibration_chunks: usize,
+}
+
+impl EnergyVad {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            noise_floor_factor: 1.0,
+            noise_floor: threshold,
+            adapt: false,
+            calibration_samples: 0,
+            calibration_sum: 0.0,
+            calibration_chunks: 0,
+        }
+    }
+
+    /**
+     * Measure the ambient noise for the given amount of samples and set the
+     * threshold to noise_floor_factor times the measured floor
+     **/
+    pub fn calibrate(mut self, samples: usize, noise_floor_factor: f32) -> Self {
+        println!("Calibrating noise floor, please be quiet...");
+        self.calibration_samples = samples;
+        self.noise_floor_factor = noise_floor_factor;
+        self
+    }
+
+    /**
+     * Keep adjusting the noise floor while nobody is speaking
+     **/
+    pub fn adapt(mut self, noise_floor_factor: f32) -> Self {
+        self.adapt = true;
+        self.noise_floor_factor = noise_floor_factor;
+        self.noise_floor = self.threshold / noise_floor_factor;
+        self
+    }
+}
+
+impl Vad for EnergyVad {
+    fn is_speech(&mut self, samples: &[f32]) -> bool {
+        let rms = samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+
+        if self.calibration_samples > 0 {
+            self.calibration_sum += rms;
+            self.calibration_chunks += 1;
+            self.calibration_samples = self.calibration_samples.saturating_sub(samples.len());
+
+            if self.calibration_samples == 0 {
+                self.noise_floor = self.calibration_sum / self.calibration_chunks as f32;
+                self.threshold = self.noise_floor * self.noise_floor_factor;
+                println!(
+                    "Noise floor: {}, silence threshold set to {}",
+                    self.noise_floor, self.threshold
+                );
+            }
+            return false;
+        }
+
+        let speech = rms > self.threshold;
+
+        if self.adapt && !speech {
+            self.noise_floor = self.noise_floor * (1.0 - ADAPT_RATE) + rms * ADAPT_RATE;
+            self.threshold = self.noise_floor * self.noise_floor_factor;
+        }
+
+        speech
+    }
+}
@@ -0,0 +1,112 @@
+pub mod endpoint;
+pub mod energy;
+#[cfg(feature = "silero")]
+pub mod silero;
+pub mod webrtc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vad::endpoint::Endpointer;
+use crate::vad::energy::EnergyVad;
+use crate::vad::webrtc::WebRtcVad;
+
+/**
+ * A voice activity detector decides whether a chunk of audio contains speech.
+ * The samples are always mono f32 at the sample rate of the input device.
+ **/
+pub trait Vad: Send {
+    fn is_speech(&mut self, samples: &[f32]) -> bool;
+}
+
+/**
+ * The available VAD implementations, selected with `vad` in the config
+ **/
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum VadKind {
+    #[default]
+    Energy, // Compares the volume against silence_threshold
+    WebRtc, // Uses the VAD from WebRTC, much better at ignoring noise
+    Silero, // Neural network based VAD, needs the "silero" feature and a model
+}
+
+/**
+ * VAD related settings, these are part of the main config
+ **/
+#[derive(Serialize, Deserialize)]
+pub struct VadConfig {
+    #[serde(default)]
+    pub vad: VadKind, // Which voice activity detection to use: "energy", "webrtc" or "silero"
+    #[serde(default)]
+    pub vad_aggressiveness: u8, // Only used by webrtc, from 0 (least) to 3 (most aggressive)
+    #[serde(default)]
+    pub silero_model: Option<String>, // Path to the Silero VAD onnx model
+
+    #[serde(default)]
+    pub calibrate: bool, // Measure the noise floor at startup to set silence_threshold
+    #[serde(default = "default_calibration_duration")]
+    pub calibration_duration: u64, // How long to measure the noise floor in milliseconds
+    #[serde(default = "default_noise_floor_factor")]
+    pub noise_floor_factor: f32, // silence_threshold is set to this times the noise floor
+    #[serde(default)]
+    pub adapt_noise_floor: bool, // Keep following the noise floor while nobody speaks
+
+    #[serde(default = "default_min_speech_duration")]
+    pub min_speech_duration: u64, // Speech shorter than this in milliseconds is ignored
+    #[serde(default = "default_hangover")]
+    pub hangover: u64, // Milliseconds speech is still reported after it stopped
+}
+
+fn default_calibration_duration() -> u64 {
+    3000
+}
+
+fn default_noise_floor_factor() -> f32 {
+    4.0
+}
+
+fn default_min_speech_duration() -> u64 {
+    200
+}
+
+fn default_hangover() -> u64 {
+    200
+}
+
+pub fn create_vad(cfg: &VadConfig, sample_rate: usize, silence_threshold: f32) -> Box<dyn Vad> {
+    let vad = create_backend(cfg, sample_rate, silence_threshold);
+
+    let min_speech = sample_rate * cfg.min_speech_duration as usize / 1000;
+    let hangover = sample_rate * cfg.hangover as usize / 1000;
+    Box::new(Endpointer::new(vad, min_speech, hangover))
+}
+
+fn create_backend(cfg: &VadConfig, sample_rate: usize, silence_threshold: f32) -> Box<dyn Vad> {
+    match cfg.vad {
+        VadKind::Energy => {
+            let mut vad = EnergyVad::new(silence_threshold);
+            if cfg.calibrate {
+                let samples = sample_rate * cfg.calibration_duration as usize / 1000;
+                vad = vad.calibrate(samples, cfg.noise_floor_factor);
+            }
+            if cfg.adapt_noise_floor {
+                vad = vad.adapt(cfg.noise_floor_factor);
+            }
+            Box::new(vad)
+        }
+        VadKind::WebRtc => Box::new(WebRtcVad::new(sample_rate, cfg.vad_aggressiveness)),
+        #[cfg(feature = "silero")]
+        VadKind::Silero => {
+            let model = cfg
+                .silero_model
+                .as_deref()
+                .expect("silero_model must be set to use the Silero VAD");
+            Box::new(silero::SileroVad::new(
+                std::path::Path::new(model),
+                sample_rate,
+            ))
+        }
+        #[cfg(not(feature = "silero"))]
+        VadKind::Silero => panic!("Silero VAD requires building with --features silero"),
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use ort::session::Session;
+use ort::value::Tensor;
+
+use crate::audio::resample::Resampler;
+use crate::vad::Vad;
+
+const SAMPLE_RATE: usize = 16_000;
+
+/**
+ * Silero v5 works on chunks of 512 samples at 16kHz, with the last 64 samples
+ * of the previous chunk prepended as context
+ **/
+const FRAME_LEN: usize = 512;
+const CONTEXT_LEN: usize = 64;
+const STATE_LEN: usize = 2 * 128;
+
+/**
+ * Probability above which a chunk is considered speech
+ **/
+const THRESHOLD: f32 = 0.5;
+
+/**
+ * Neural network based VAD using the Silero model.
+ * It is a lot better than the others at ignoring music or a TV in the background.
+ * The model can be downloaded from https://github.com/snakers4/silero-vad
+ **/
+pub struct SileroVad {
+    session: Session,
+    resampler: Resampler,
+    state: Vec<f32>,
+    context: Vec<f32>,
+    pending: Vec<f32>,
+}
+
+impl SileroVad {
+    pub fn new(model: &Path, input_rate: usize) -> Self {
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_file(model))
+            .expect("Failed to load Silero VAD model");
+
+        Self {
+            session,
+            resampler: Resampler::new(input_rate, SAMPLE_RATE),
+            state: vec![0.0; STATE_LEN],
+            context: vec![0.0; CONTEXT_LEN],
+            pending: Vec::new(),
+        }
+    }
+
+    /**
+     * Run the model on a single chunk and return the probability of it containing speech
+     **/
+    fn speech_probability(&mut self, frame: &[f32]) -> ort::Result<f32> {
+        let input = [self.context.as_slice(), frame].concat();
+
+        let inputs = ort::inputs![
+            "input" => Tensor::from_array(([1, CONTEXT_LEN + FRAME_LEN], input))?,
+            "state" => Tensor::from_array(([2, 1, 128], self.state.clone()))?,
+            "sr" => Tensor::from_array(((), vec![SAMPLE_RATE as i64]))?,
+        ]?;
+
+        let outputs = self.session.run(inputs)?;
+        let (_, probability) = outputs["output"].try_extract_raw_tensor::<f32>()?;
+        let (_, state) = outputs["stateN"].try_extract_raw_tensor::<f32>()?;
+
+        self.state = state.to_vec();
+        self.context = frame[FRAME_LEN - CONTEXT_LEN..].to_vec();
+
+        Ok(probability[0])
+    }
+}
+
+impl Vad for SileroVad {
+    fn is_speech(&mut self, samples: &[f32]) -> bool {
+        self.pending.extend(self.resampler.process(samples));
+
+        let mut speech = false;
+        while self.pending.len() >= FRAME_LEN {
+            let frame: Vec<f32> = self.pending.drain(..FRAME_LEN).collect();
+            match self.speech_probability(&frame) {
+                Ok(probability) => speech |= probability > THRESHOLD,
+                Err(err) => eprintln!("Silero VAD failed: {}", err),
+            }
+        }
+
+        speech
+    }
+}
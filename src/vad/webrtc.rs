@@ -0,0 +1,75 @@
+use webrtc_vad::{SampleRate, VadMode};
+
+use crate::audio::resample::Resampler;
+use crate::vad::Vad;
+
+/**
+ * WebRTC only accepts frames of 10, 20 or 30ms, we always use 30ms
+ **/
+const FRAME_MS: usize = 30;
+
+/**
+ * VAD based on the one used in WebRTC. It looks at the spectrum of the audio instead
+ * of only the volume, so background noise is a lot less likely to be detected as speech.
+ **/
+pub struct WebRtcVad {
+    vad: webrtc_vad::Vad,
+    resampler: Resampler,
+    vad_rate: usize,
+    pending: Vec<i16>,
+}
+
+// SAFETY: The fvad instance is created for this WebRtcVad alone and freed when it is dropped,
+// it isn't Clone and only used through &mut self, so there is never more than one owner.
+// fvad keeps all of its state in the instance and uses no thread locals, so the instance can
+// be created on the main thread and then moved to the audio thread that calls is_speech.
+unsafe impl Send for WebRtcVad {}
+
+impl WebRtcVad {
+    /**
+     * The aggressiveness goes from 0 (accepts most audio as speech)
+     * to 3 (filters out as much non-speech as possible)
+     **/
+    pub fn new(input_rate: usize, aggressiveness: u8) -> Self {
+        // Use the input rate directly if WebRTC supports it, otherwise resample to 16kHz
+        let (rate, vad_rate) = match SampleRate::try_from(input_rate as i32) {
+            Ok(rate) => (rate, input_rate),
+            Err(_) => (SampleRate::Rate16kHz, 16_000),
+        };
+
+        let mode = match aggressiveness {
+            0 => VadMode::Quality,
+            1 => VadMode::LowBitrate,
+            2 => VadMode::Aggressive,
+            _ => VadMode::VeryAggressive,
+        };
+
+        Self {
+            vad: webrtc_vad::Vad::new_with_rate_and_mode(rate, mode),
+            resampler: Resampler::new(input_rate, vad_rate),
+            vad_rate,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Vad for WebRtcVad {
+    fn is_speech(&mut self, samples: &[f32]) -> bool {
+        let samples = self.resampler.process(samples);
+        self.pending.extend(
+            samples
+                .iter()
+                .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+        );
+
+        // Check every complete frame, the rest is kept for the next call
+        let frame_len = self.vad_rate * FRAME_MS / 1000;
+        let mut speech = false;
+        while self.pending.len() >= frame_len {
+            let frame: Vec<i16> = self.pending.drain(..frame_len).collect();
+            speech |= self.vad.is_voice_segment(&frame).unwrap_or(false);
+        }
+
+        speech
+    }
+}